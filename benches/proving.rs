@@ -0,0 +1,120 @@
+//! Benchmarks of tree construction and proving.
+//!
+//! Run with `cargo bench --bench proving`, or select a group, e.g. `cargo bench --bench proving --
+//! merkle_tree_proof`. Proving benchmarks sweep the number of threads of the proving thread pool, so
+//! that the recursive prover (`merkle_tree_proof`) can be compared with the monolithic backend
+//! (`monolithic_tree_proof`), which proves the whole tree with a single `MonolithicTreeCircuit`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use plonky2::{field::types::Field, hash::poseidon::PoseidonHash, plonk::config::Hasher};
+use recursive_merkle_tree_proofs::{
+    merkle_tree::MerkleTree,
+    pairwise_hash::PairwiseHash,
+    provable::Provable,
+    proving_options::{ProvingBackend, ProvingOptions},
+    recursive_hash::{RecursiveHash, RecursivePairwiseHash},
+    F,
+};
+
+const LEAF_LEN: usize = 4;
+const CREATE_NUM_LEAVES: [usize; 3] = [1 << 8, 1 << 12, 1 << 16];
+const PROOF_NUM_LEAVES: [usize; 3] = [4, 16, 64];
+const NUM_THREADS: [usize; 3] = [1, 2, 4];
+
+fn leaves(num_leaves: usize) -> Vec<Vec<F>> {
+    (0..num_leaves)
+        .map(|i| {
+            (0..LEAF_LEN)
+                .map(|j| F::from_canonical_usize(i * LEAF_LEN + j))
+                .collect()
+        })
+        .collect()
+}
+
+fn pairwise_hash(left_leaf: Vec<F>, right_leaf: Vec<F>) -> PairwiseHash {
+    let left_hash = PoseidonHash::hash_or_noop(&left_leaf);
+    let right_hash = PoseidonHash::hash_or_noop(&right_leaf);
+    PairwiseHash::new(left_leaf, left_hash, right_leaf, right_hash)
+}
+
+fn bench_create(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_tree_create");
+    for num_leaves in CREATE_NUM_LEAVES {
+        let leaves = leaves(num_leaves);
+        group.throughput(Throughput::Elements(num_leaves as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_leaves),
+            &leaves,
+            |b, leaves| b.iter(|| MerkleTree::create(leaves.clone())),
+        );
+    }
+    group.finish();
+}
+
+fn bench_node_proofs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("node_proof");
+    group.sample_size(10);
+
+    let leaves = leaves(4);
+    group.bench_function("pairwise_hash", |b| {
+        b.iter(|| {
+            pairwise_hash(leaves[0].clone(), leaves[1].clone())
+                .proof()
+                .unwrap()
+        })
+    });
+
+    let left_proof_data = pairwise_hash(leaves[0].clone(), leaves[1].clone())
+        .proof()
+        .unwrap();
+    let right_proof_data = pairwise_hash(leaves[2].clone(), leaves[3].clone())
+        .with_index(1)
+        .proof()
+        .unwrap();
+    group.bench_function("recursive_pairwise_hash", |b| {
+        b.iter(|| {
+            RecursivePairwiseHash::new(
+                RecursiveHash::new(left_proof_data.root(), &left_proof_data),
+                RecursiveHash::new(right_proof_data.root(), &right_proof_data),
+            )
+            .proof()
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_tree_proofs(c: &mut Criterion) {
+    for (group_name, backend) in [
+        ("merkle_tree_proof", ProvingBackend::Recursive),
+        ("monolithic_tree_proof", ProvingBackend::Monolithic),
+    ] {
+        let mut group = c.benchmark_group(group_name);
+        group.sample_size(10);
+        for num_leaves in PROOF_NUM_LEAVES {
+            let leaves = leaves(num_leaves);
+            for num_threads in NUM_THREADS {
+                let proving_options = ProvingOptions {
+                    backend,
+                    num_threads: Some(num_threads),
+                    ..ProvingOptions::default()
+                };
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{}_threads", num_threads), num_leaves),
+                    &leaves,
+                    |b, leaves| {
+                        b.iter(|| {
+                            MerkleTree::create(leaves.clone())
+                                .proof_with_options(&proving_options)
+                                .unwrap()
+                        })
+                    },
+                );
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_create, bench_node_proofs, bench_tree_proofs);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+/*
+ * C interface of recursive_merkle_tree_proofs, built with `cargo build --release --features ffi`
+ * (librecursive_merkle_tree_proofs.so, .dylib or .dll).
+ *
+ * Field elements are canonical Goldilocks elements (below 2^64 - 2^32 + 1), passed as uint64_t, and
+ * hashes are arrays of 4 elements. Trees and proofs are opaque handles owned by the caller, to be
+ * released with merkle_tree_free and merkle_proof_free. Functions returning int return 0 on success
+ * and -1 on failure, functions returning pointers return NULL on failure; the error message is then
+ * available from merkle_last_error.
+ */
+
+#ifndef MERKLE_TREE_PROOFS_H
+#define MERKLE_TREE_PROOFS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MerkleTree MerkleTree;
+typedef struct SerializedProofData SerializedProofData;
+
+/* Message of the last error of the calling thread, valid until the next failing call. */
+const char *merkle_last_error(void);
+
+/* Builds a tree from num_leaves * leaf_len elements, leaf after leaf. num_leaves should be a power
+ * of two greater than one. */
+MerkleTree *merkle_tree_create(const uint64_t *leaves, size_t num_leaves, size_t leaf_len);
+void merkle_tree_free(MerkleTree *merkle_tree);
+size_t merkle_tree_num_leaves(const MerkleTree *merkle_tree);
+/* Writes the 4 elements of the root. */
+int merkle_tree_root(const MerkleTree *merkle_tree, uint64_t *root);
+/* Writes the 4 * log2(num_leaves) elements of the siblings of a leaf, from the leaf level up.
+ * siblings_len is the number of elements siblings can hold. */
+int merkle_tree_inclusion_proof(const MerkleTree *merkle_tree, size_t leaf_index, uint64_t *siblings,
+                                size_t siblings_len);
+/* Returns 0 if the path of the leaf leads to root, -1 otherwise. */
+int merkle_verify_inclusion_path(const uint64_t *leaf, size_t leaf_len, size_t leaf_index,
+                                 const uint64_t *siblings, size_t num_siblings,
+                                 const uint64_t *root);
+
+/* Proves the tree root on num_threads threads (all available threads if 0). */
+SerializedProofData *merkle_tree_prove(const MerkleTree *merkle_tree, size_t num_threads);
+/* Copies a serialized proof and verifier key into a new handle. */
+SerializedProofData *merkle_proof_from_bytes(const uint8_t *proof, size_t proof_len,
+                                             const uint8_t *verifier_key, size_t verifier_key_len);
+/* Bytes owned by the handle, valid until it is released. */
+const uint8_t *merkle_proof_bytes(const SerializedProofData *proof_data, size_t *len);
+const uint8_t *merkle_proof_verifier_key(const SerializedProofData *proof_data, size_t *len);
+/* Writes the 4 elements of the digest of the verifier key of the root proofs of trees of
+ * num_leaves leaves of leaf_len elements, proved by merkle_tree_prove. */
+int merkle_root_verifier_key_digest(size_t num_leaves, size_t leaf_len,
+                                    uint64_t *verifier_key_digest);
+/* Returns 0 if the proof is valid for root, its verifier key matching the pinned
+ * verifier_key_digest, and writes the number of leaves (unless NULL). */
+int merkle_proof_verify(const SerializedProofData *proof_data, const uint64_t *root,
+                        const uint64_t *verifier_key_digest, size_t *num_leaves);
+void merkle_proof_free(SerializedProofData *proof_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MERKLE_TREE_PROOFS_H */
@@ -0,0 +1,154 @@
+//! Builds, proves and verifies Merkle trees from the command line.
+//!
+//! Leaves are read from `.csv` or `.json` files, or from binary files of little endian `u64` field
+//! elements, whose leaf length is given with `--leaf-len` (see `LeafFormat`). Roots are printed and
+//! read as hexadecimal strings (see `hash_to_hex`).
+
+use std::{env, fs, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Error};
+use recursive_merkle_tree_proofs::{
+    leaf_format::{hash_from_hex, hash_to_hex, LeafFormat},
+    merkle_tree::MerkleTree,
+    proving_options::ProvingOptions,
+    solidity::{solidity_test_vectors, solidity_verifier},
+    test_vectors::default_test_vectors,
+    verifier::verify_root_proof,
+};
+
+const USAGE: &str = "usage:
+    merkle-prove build <leaves> [--leaf-len <n>]
+    merkle-prove prove <leaves> <proof> <verifier-key> [--leaf-len <n>] [--threads <n>] [--leaf-chunk-size <n>]
+    merkle-prove verify <proof> <verifier-key> <root> <verifier-key-digest>
+    merkle-prove digest <num-leaves> <leaf-len> [--leaf-chunk-size <n>]
+    merkle-prove path <leaves> <index> [--leaf-len <n>]
+    merkle-prove solidity <verifier>
+    merkle-prove solidity-vectors <leaves> <vectors> [--leaf-len <n>]
+    merkle-prove test-vectors <vectors>";
+
+/// Command line arguments: positional arguments and `--flag <value>` options.
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, usize)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &'a [String]) -> Result<Self, Error> {
+        let mut positional = vec![];
+        let mut options = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(flag) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing value for --{}", flag))?;
+                    options.push((flag, value.parse()?));
+                }
+                None => positional.push(arg.as_str()),
+            }
+        }
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    fn option(&self, flag: &str) -> Option<usize> {
+        self.options
+            .iter()
+            .find(|(name, _)| *name == flag)
+            .map(|(_, value)| *value)
+    }
+}
+
+fn read_merkle_tree(path: &str, leaf_len: Option<usize>) -> Result<MerkleTree, Error> {
+    let path = Path::new(path);
+    let leaves = LeafFormat::from_path(path, leaf_len)?.parse_leaves(&fs::read(path)?)?;
+    ensure!(
+        leaves.len().is_power_of_two() && leaves.len() > 1,
+        "the number of leaves should be a power of two, greater than one, got {}",
+        leaves.len()
+    );
+    let leaf_len = leaves[0].len();
+    if let Some((index, leaf)) = leaves
+        .iter()
+        .enumerate()
+        .find(|(_, leaf)| leaf.len() != leaf_len)
+    {
+        bail!(
+            "all leaves should have the same length, leaf {} has {} elements instead of {}",
+            index,
+            leaf.len(),
+            leaf_len
+        );
+    }
+    Ok(MerkleTree::create(leaves))
+}
+
+fn proving_options(args: &Args) -> ProvingOptions {
+    let default_options = ProvingOptions::default();
+    ProvingOptions {
+        num_threads: args.option("threads"),
+        leaf_chunk_size: args
+            .option("leaf-chunk-size")
+            .unwrap_or(default_options.leaf_chunk_size),
+        ..default_options
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = Args::parse(&args)?;
+    let leaf_len = args.option("leaf-len");
+
+    match args.positional.as_slice() {
+        ["build", leaves] => {
+            let merkle_tree = read_merkle_tree(leaves, leaf_len)?;
+            println!("{}", hash_to_hex(merkle_tree.root()));
+        }
+        ["prove", leaves, proof, verifier_key] => {
+            let merkle_tree = read_merkle_tree(leaves, leaf_len)?;
+            let root = merkle_tree.root();
+            let proof_data = merkle_tree.proof_with_options(&proving_options(&args))?;
+            fs::write(proof, proof_data.proof_to_bytes())?;
+            fs::write(verifier_key, proof_data.verifier_key_to_bytes()?)?;
+            println!("{}", hash_to_hex(root));
+        }
+        ["verify", proof, verifier_key, root, verifier_key_digest] => {
+            let num_leaves = verify_root_proof(
+                &fs::read(proof)?,
+                &fs::read(verifier_key)?,
+                hash_from_hex(root)?,
+                hash_from_hex(verifier_key_digest)?,
+            )?;
+            println!("valid proof of a tree with {} leaves", num_leaves);
+        }
+        ["digest", num_leaves, leaf_len] => {
+            let verifier_key_digest = MerkleTree::root_verifier_key_digest(
+                num_leaves.parse()?,
+                leaf_len.parse()?,
+                &proving_options(&args),
+            )?;
+            println!("{}", hash_to_hex(verifier_key_digest));
+        }
+        ["path", leaves, index] => {
+            let merkle_tree = read_merkle_tree(leaves, leaf_len)?;
+            let inclusion_proof = merkle_tree.inclusion_proof(index.parse()?)?;
+            let path = inclusion_proof.to_json();
+            println!("{}", serde_json::to_string_pretty(&path)?);
+        }
+        ["solidity", verifier] => fs::write(verifier, solidity_verifier())?,
+        ["solidity-vectors", leaves, vectors] => {
+            let merkle_tree = read_merkle_tree(leaves, leaf_len)?;
+            let vectors_json = solidity_test_vectors(&merkle_tree);
+            fs::write(vectors, serde_json::to_string_pretty(&vectors_json)?)?;
+        }
+        ["test-vectors", vectors] => fs::write(
+            vectors,
+            serde_json::to_string_pretty(&default_test_vectors())?,
+        )?,
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
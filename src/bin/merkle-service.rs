@@ -0,0 +1,31 @@
+//! Local HTTP proving service, see `ProvingService` for its endpoints.
+//!
+//! Listens on `127.0.0.1:8080`, or on `--listen <address>`, and proves jobs with `--threads <n>`
+//! threads (all available threads by default).
+
+use std::env;
+
+use anyhow::{bail, Error};
+use recursive_merkle_tree_proofs::{proving_options::ProvingOptions, service::ProvingService};
+
+const USAGE: &str = "usage: merkle-service [--listen <address>] [--threads <n>]";
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut address = "127.0.0.1:8080".to_string();
+    let mut proving_options = ProvingOptions::default();
+    for option in args.chunks(2) {
+        match option {
+            [flag, value] if flag == "--listen" => address = value.clone(),
+            [flag, value] if flag == "--threads" => {
+                proving_options.num_threads = Some(value.parse()?)
+            }
+            _ => bail!(USAGE),
+        }
+    }
+
+    let service = ProvingService::bind(address, proving_options)?;
+    println!("listening on {}", service.local_address());
+    service.run();
+    Ok(())
+}
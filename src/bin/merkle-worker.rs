@@ -0,0 +1,17 @@
+//! Proving worker for `Coordinator`.
+//!
+//! Serves node proof jobs over its standard input and output, or, with `--listen <address>`, over TCP.
+
+use std::{env, io};
+
+use anyhow::{bail, Error};
+use recursive_merkle_tree_proofs::distributed::{listen_worker, serve_worker};
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => serve_worker(io::stdin().lock(), io::stdout().lock()),
+        [flag, address] if flag == "--listen" => listen_worker(address),
+        _ => bail!("usage: merkle-worker [--listen <address>]"),
+    }
+}
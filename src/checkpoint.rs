@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, ensure, Error};
+
+use crate::{circuit_compiler::ProofData, C, D, F};
+
+const MANIFEST_FILE_NAME: &str = "manifest";
+
+/// `Checkpoint` struct:
+///     Persists completed node proofs of a tree proof to a directory, so that an interrupted proof can
+///     be resumed without proving these nodes again.
+///
+/// Description:
+///
+///     Node proofs are stored as `height_{height}/node_{index}.bin` files, serialized with
+///     `ProofData::to_bytes`. Each file is first written to a temporary file and then renamed, so that a
+///     crash never leaves a truncated node proof behind.
+///
+///     A manifest identifying the proved tree and its proving options is written when the checkpoint
+///     is created, and is required to match when resuming, so that proofs of another tree (or built
+///     with other circuit parameters) are never reused.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    directory: PathBuf,
+}
+
+impl Checkpoint {
+    /// Method `open`:
+    ///
+    ///     Opens a checkpoint directory for the tree identified by `manifest`.
+    ///
+    /// Arguments:
+    ///
+    ///     resume: If true, the node proofs of the directory are kept, provided the directory manifest
+    ///         matches `manifest`. Otherwise, node proofs left over by a previous run are removed.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the Checkpoint or an Error if the directory can not be accessed,
+    ///     or if resuming a checkpoint of another tree.
+    pub fn open(directory: impl AsRef<Path>, manifest: &str, resume: bool) -> Result<Self, Error> {
+        let directory = directory.as_ref().to_path_buf();
+        let manifest_path = directory.join(MANIFEST_FILE_NAME);
+
+        if resume && manifest_path.exists() {
+            let checkpoint_manifest = fs::read_to_string(&manifest_path)?;
+            ensure!(
+                checkpoint_manifest == manifest,
+                "the checkpoint in {} was created for another tree or other proving options",
+                directory.display()
+            );
+            return Ok(Self { directory });
+        }
+
+        fs::create_dir_all(&directory)?;
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            let is_height_directory = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .is_some_and(|file_name| file_name.starts_with("height_"));
+            if path.is_dir() && is_height_directory {
+                fs::remove_dir_all(path)?;
+            }
+        }
+        fs::write(&manifest_path, manifest)?;
+
+        Ok(Self { directory })
+    }
+
+    /// Method `load`:
+    ///
+    ///     Loads the proof of the node `index` at height `height`, if it has been checkpointed.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the node proof, if any, or an Error if the checkpointed proof can
+    ///     not be deserialized or does not verify.
+    pub fn load(&self, height: usize, index: usize) -> Result<Option<ProofData<F, C, D>>, Error> {
+        let path = self.node_path(height, index);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let proof_data = ProofData::from_bytes(&fs::read(&path)?)?;
+        proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis.clone())
+            .map_err(|e| anyhow!("invalid checkpointed proof {}: {}", path.display(), e))?;
+
+        Ok(Some(proof_data))
+    }
+
+    /// Method `store`:
+    ///
+    ///     Checkpoints the proof of the node `index` at height `height`.
+    pub fn store(
+        &self,
+        height: usize,
+        index: usize,
+        proof_data: &ProofData<F, C, D>,
+    ) -> Result<(), Error> {
+        let path = self.node_path(height, index);
+        fs::create_dir_all(self.directory.join(format!("height_{}", height)))?;
+
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, proof_data.to_bytes()?)?;
+        fs::rename(temporary_path, path)?;
+
+        Ok(())
+    }
+
+    fn node_path(&self, height: usize, index: usize) -> PathBuf {
+        self.directory
+            .join(format!("height_{}", height))
+            .join(format!("node_{}.bin", index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::{
+        merkle_tree::MerkleTree, progress::ProofProgress, proving_options::ProvingOptions,
+    };
+
+    fn merkle_tree() -> MerkleTree {
+        MerkleTree::create((0..4).map(|i| vec![F::from_canonical_u64(i)]).collect())
+    }
+
+    fn checkpoint_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("merkle_tree_checkpoint_{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn test_checkpoint_and_resume() {
+        let directory = checkpoint_directory("resume");
+        let root = merkle_tree().root();
+
+        let proving_options = ProvingOptions {
+            checkpoint_directory: Some(directory.clone()),
+            ..Default::default()
+        };
+        let proof_data = merkle_tree().proof_with_options(&proving_options).unwrap();
+
+        let checkpoint = Checkpoint::open(
+            &directory,
+            &merkle_tree().manifest(&proving_options, 0),
+            true,
+        )
+        .unwrap();
+        assert!(checkpoint.node_path(2, 0).exists());
+
+        // the root and one base proof are lost
+        fs::remove_file(checkpoint.node_path(2, 0)).unwrap();
+        fs::remove_file(checkpoint.node_path(1, 1)).unwrap();
+
+        let num_proved = Arc::new(AtomicUsize::new(0));
+        let observer_num_proved = num_proved.clone();
+        let proving_options = ProvingOptions {
+            resume: true,
+            progress_observer: Some(Arc::new(move |_: &ProofProgress| {
+                observer_num_proved.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..proving_options
+        };
+        let resumed_proof_data = merkle_tree().proof_with_options(&proving_options).unwrap();
+
+        // only the lost base proof and the root proof are generated again
+        assert_eq!(num_proved.load(Ordering::SeqCst), 2);
+        assert_eq!(resumed_proof_data.root(), root);
+        assert_eq!(
+            resumed_proof_data.circuit_data.verifier_only,
+            proof_data.circuit_data.verifier_only
+        );
+        assert!(resumed_proof_data
+            .circuit_data
+            .verify(resumed_proof_data.proof_with_pis)
+            .is_ok());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_resume_fails_for_other_tree() {
+        let directory = checkpoint_directory("other_tree");
+        let proving_options = ProvingOptions::default();
+
+        Checkpoint::open(
+            &directory,
+            &merkle_tree().manifest(&proving_options, 0),
+            false,
+        )
+        .unwrap();
+
+        let other_merkle_tree =
+            MerkleTree::create((1..5).map(|i| vec![F::from_canonical_u64(i)]).collect());
+        assert!(Checkpoint::open(
+            &directory,
+            &other_merkle_tree.manifest(&proving_options, 0),
+            true
+        )
+        .is_err());
+
+        // a fresh run discards the previous checkpoint
+        assert!(Checkpoint::open(
+            &directory,
+            &other_merkle_tree.manifest(&proving_options, 0),
+            false
+        )
+        .is_ok());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::Error;
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig, config::Hasher},
+};
+
+use crate::{
+    circuit_compiler::{CircuitCompiler, EvaluateFillCircuit, ProofData},
+    pairwise_hash::{register_base_node_public_inputs, HashData},
+    provable::Provable,
+    C, D, F,
+};
+
+/// `ChunkHash` struct:
+///     Generalizes `PairwiseHash` to a chunk of `2^k` consecutive leaves, whose subtree root is
+///     computed in a single circuit. Larger chunks trade a bigger base circuit for fewer base proofs
+///     and recursive steps.
+///
+///     As for `PairwiseHash`, the `NUM_NODE_PUBLIC_INPUTS` public inputs are the subtree root,
+///     height, number of leaves and index, whereas the leaf data and leaf hashes are part of the
+///     witness.
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkHash {
+    pub(crate) leaves: Vec<HashData>,
+    pub(crate) root: HashOut<F>,
+    pub(crate) index: usize,
+    pub(crate) circuit_config: CircuitConfig,
+}
+
+impl ChunkHash {
+    /// Method `new`:
+    ///
+    ///     Creates a new chunk from the leaf data and their hashes.
+    ///
+    /// Panics:
+    ///
+    ///     Panics if the number of leaves is not a power of two greater than one, or does not match the
+    ///     number of leaf hashes.
+    pub fn new(leaves_data: Vec<Vec<F>>, leaves_hashes: Vec<HashOut<F>>) -> Self {
+        assert!(leaves_data.len().is_power_of_two() && leaves_data.len() > 1);
+        assert_eq!(leaves_data.len(), leaves_hashes.len());
+
+        let mut hashes = leaves_hashes.clone();
+        while hashes.len() > 1 {
+            hashes = hashes
+                .chunks(2)
+                .map(|pair| {
+                    PoseidonHash::hash_or_noop(&[pair[0].elements, pair[1].elements].concat())
+                })
+                .collect();
+        }
+
+        Self {
+            leaves: leaves_data
+                .into_iter()
+                .zip(leaves_hashes)
+                .map(|(data, hash)| HashData::new(data, hash))
+                .collect(),
+            root: hashes[0],
+            index: 0,
+            circuit_config: CircuitConfig::standard_recursion_zk_config(),
+        }
+    }
+
+    /// Method `with_index`:
+    ///
+    ///     Sets the index of the chunk subtree within its level (0 by default), i.e. the chunk starts at
+    ///     leaf `index * leaves.len()` of the tree.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Method `with_circuit_config`:
+    ///
+    ///     Sets the configuration the circuit is built with (`standard_recursion_zk_config` by default).
+    pub fn with_circuit_config(mut self, circuit_config: CircuitConfig) -> Self {
+        self.circuit_config = circuit_config;
+        self
+    }
+}
+
+impl CircuitCompiler<C, F, D> for ChunkHash {
+    type Targets = (Vec<Vec<Target>>, Vec<HashOutTarget>);
+    type OutTargets = (HashOutTarget, Target);
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(self.circuit_config.clone());
+
+        let data_targets: Vec<Vec<Target>> = self
+            .leaves
+            .iter()
+            .map(|leaf| circuit_builder.add_virtual_targets(leaf.data.len()))
+            .collect();
+        let hash_targets: Vec<HashOutTarget> = data_targets
+            .iter()
+            .map(|leaf_data_targets| {
+                let leaf_hash_targets = circuit_builder.add_virtual_hash();
+                let should_be_leaf_hash_targets =
+                    circuit_builder.hash_or_noop::<PoseidonHash>(leaf_data_targets.clone());
+                circuit_builder.connect_hashes(should_be_leaf_hash_targets, leaf_hash_targets);
+                leaf_hash_targets
+            })
+            .collect();
+
+        let root_hash_targets = circuit_builder.add_virtual_hash();
+
+        // register public inputs
+        circuit_builder.register_public_inputs(&root_hash_targets.elements);
+        let index_target = register_base_node_public_inputs(
+            &mut circuit_builder,
+            self.leaves.len().ilog2() as usize,
+        );
+
+        let mut level_hash_targets = hash_targets.clone();
+        while level_hash_targets.len() > 1 {
+            level_hash_targets = level_hash_targets
+                .chunks(2)
+                .map(|pair| {
+                    circuit_builder
+                        .hash_or_noop::<PoseidonHash>([pair[0].elements, pair[1].elements].concat())
+                })
+                .collect();
+        }
+
+        circuit_builder.connect_hashes(level_hash_targets[0], root_hash_targets);
+
+        (
+            circuit_builder,
+            (data_targets, hash_targets),
+            (root_hash_targets, index_target),
+        )
+    }
+}
+
+impl EvaluateFillCircuit<C, F, D> for ChunkHash {
+    type Value = HashOut<F>;
+
+    fn evaluate(&self) -> Self::Value {
+        self.root
+    }
+
+    fn fill(
+        &self,
+        targets: Self::Targets,
+        out_targets: Self::OutTargets,
+    ) -> Result<PartialWitness<F>, Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        let (data_targets, hash_targets) = targets;
+
+        self.leaves
+            .iter()
+            .zip(data_targets.iter().zip(hash_targets))
+            .for_each(|(leaf, (leaf_data_targets, leaf_hash_targets))| {
+                partial_witness.set_target_arr(leaf_data_targets, &leaf.data);
+                partial_witness.set_hash_target(leaf_hash_targets, leaf.hash);
+            });
+        let (root_hash_targets, index_target) = out_targets;
+        partial_witness.set_hash_target(root_hash_targets, self.root);
+        partial_witness.set_target(index_target, F::from_canonical_usize(self.index));
+
+        Ok(partial_witness)
+    }
+}
+
+impl Provable<F, C, D> for ChunkHash {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        let (circuit_data, targets, out_targets) = self.compile_and_build();
+        let partial_witness = self.fill(targets, out_targets)?;
+
+        let proof_with_pis = circuit_data.prove(partial_witness)?;
+
+        Ok(ProofData {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_chunk_hash() {
+        let leaves: Vec<Vec<F>> = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let leaves_hashes = leaves
+            .iter()
+            .map(|leaf| PoseidonHash::hash_or_noop(leaf))
+            .collect();
+
+        let chunk_hash = ChunkHash::new(leaves.clone(), leaves_hashes);
+        assert_eq!(chunk_hash.evaluate(), MerkleTree::create(leaves).root());
+        assert!(chunk_hash.prove_and_verify().is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chunk_hash_fails_for_invalid_leaf_hash() {
+        let leaves: Vec<Vec<F>> = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let mut leaves_hashes: Vec<HashOut<F>> = leaves
+            .iter()
+            .map(|leaf| PoseidonHash::hash_or_noop(leaf))
+            .collect();
+        leaves_hashes[3] = PoseidonHash::hash_or_noop(&[F::ZERO]);
+
+        let chunk_hash = ChunkHash::new(leaves, leaves_hashes);
+        assert!(chunk_hash.prove_and_verify().is_err());
+    }
+}
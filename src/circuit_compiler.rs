@@ -4,7 +4,9 @@ use plonky2::{
     hash::hash_types::RichField,
     iop::witness::PartialWitness,
     plonk::{
-        circuit_builder::CircuitBuilder, circuit_data::CircuitData, config::GenericConfig,
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{GenericConfig, Hasher},
         proof::ProofWithPublicInputs,
     },
 };
@@ -17,6 +19,48 @@ where
     pub(crate) circuit_data: CircuitData<F, C, D>,
 }
 
+impl<F, C: GenericConfig<D, F = F>, const D: usize> ProofData<F, C, D>
+where
+    F: RichField + Extendable<D>,
+{
+    /// Method `estimated_size`:
+    ///
+    ///     Returns an estimate, in bytes, of the memory held by the proof data. It is dominated by the
+    ///     prover data of the circuit, in particular the committed constants and sigmas polynomials.
+    pub fn estimated_size(&self) -> usize {
+        let prover_only = &self.circuit_data.prover_only;
+        let commitment = &prover_only.constants_sigmas_commitment;
+
+        let num_field_elements = commitment
+            .polynomials
+            .iter()
+            .map(|polynomial| polynomial.len())
+            .sum::<usize>()
+            + commitment
+                .merkle_tree
+                .leaves
+                .iter()
+                .map(|leaf| leaf.len())
+                .sum::<usize>()
+            + prover_only
+                .sigmas
+                .iter()
+                .map(|sigma| sigma.len())
+                .sum::<usize>()
+            + prover_only.subgroup.len()
+            + prover_only
+                .fft_root_table
+                .as_ref()
+                .map_or(0, |table| table.iter().map(|roots| roots.len()).sum());
+
+        num_field_elements * std::mem::size_of::<F>()
+            + commitment.merkle_tree.digests.len()
+                * std::mem::size_of::<<C::Hasher as Hasher<F>>::Hash>()
+            + prover_only.representative_map.len() * std::mem::size_of::<usize>()
+            + self.proof_with_pis.to_bytes().len()
+    }
+}
+
 pub trait CircuitCompiler<C, F, const D: usize>
 where
     C: GenericConfig<D, F = F>,
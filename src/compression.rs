@@ -0,0 +1,280 @@
+use std::fmt;
+
+use anyhow::Error;
+use plonky2::{
+    fri::{reduction_strategies::FriReductionStrategy, FriConfig},
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData},
+        config::PoseidonGoldilocksConfig,
+        proof::ProofWithPublicInputsTarget,
+    },
+};
+
+use crate::{
+    circuit_compiler::{CircuitCompiler, EvaluateFillCircuit, ProofData},
+    provable::Provable,
+    C, D, F,
+};
+
+/// Function `compression_config`:
+///
+///     Returns the circuit configuration of the final compression stage: a high FRI rate (`2^-7`)
+///     allows for few query rounds, which dominate the proof size, at the cost of a slower prover.
+pub fn compression_config() -> CircuitConfig {
+    CircuitConfig {
+        fri_config: FriConfig {
+            rate_bits: 7,
+            cap_height: 0,
+            proof_of_work_bits: 16,
+            reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+            num_query_rounds: 12,
+        },
+        ..CircuitConfig::standard_recursion_config()
+    }
+}
+
+/// `WrapperCircuit` struct:
+///     Recursively verifies a single proof, against its fixed verifier data, and forwards its public
+///     inputs as its own.
+pub(crate) struct WrapperCircuit<'a> {
+    pub(crate) proof_data: &'a ProofData<F, C, D>,
+    pub(crate) circuit_config: CircuitConfig,
+}
+
+impl<'a> WrapperCircuit<'a> {
+    pub fn new(proof_data: &'a ProofData<F, C, D>, circuit_config: CircuitConfig) -> Self {
+        Self {
+            proof_data,
+            circuit_config,
+        }
+    }
+}
+
+/// Builds the circuit of a wrapper of proofs of the circuit of `circuit_data`, see `WrapperCircuit`.
+pub(crate) fn compile_wrapper(
+    circuit_config: &CircuitConfig,
+    circuit_data: &CircuitData<F, C, D>,
+) -> (CircuitBuilder<F, D>, ProofWithPublicInputsTarget<D>) {
+    let mut circuit_builder = CircuitBuilder::<F, D>::new(circuit_config.clone());
+
+    let proof_with_pis_targets = circuit_builder.add_virtual_proof_with_pis(&circuit_data.common);
+    // the wrapped circuit is fixed, so that its verifier data is a constant of the wrapper
+    let verifier_data_targets =
+        circuit_builder.constant_verifier_data::<C>(&circuit_data.verifier_only);
+    circuit_builder.verify_proof::<PoseidonGoldilocksConfig>(
+        &proof_with_pis_targets,
+        &verifier_data_targets,
+        &circuit_data.common,
+    );
+
+    // register public inputs
+    circuit_builder.register_public_inputs(&proof_with_pis_targets.public_inputs);
+
+    (circuit_builder, proof_with_pis_targets)
+}
+
+impl<'a> CircuitCompiler<C, F, D> for WrapperCircuit<'a> {
+    type Targets = ProofWithPublicInputsTarget<D>;
+    type OutTargets = ();
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        let (circuit_builder, proof_with_pis_targets) =
+            compile_wrapper(&self.circuit_config, &self.proof_data.circuit_data);
+        (circuit_builder, proof_with_pis_targets, ())
+    }
+}
+
+impl<'a> EvaluateFillCircuit<C, F, D> for WrapperCircuit<'a> {
+    type Value = Vec<F>;
+
+    fn evaluate(&self) -> Self::Value {
+        self.proof_data.proof_with_pis.public_inputs.clone()
+    }
+
+    fn fill(
+        &self,
+        targets: Self::Targets,
+        _out_targets: Self::OutTargets,
+    ) -> Result<PartialWitness<F>, Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        partial_witness.set_proof_with_pis_target(&targets, &self.proof_data.proof_with_pis);
+        Ok(partial_witness)
+    }
+}
+
+impl<'a> Provable<F, C, D> for WrapperCircuit<'a> {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        let (circuit_data, targets, out_targets) = self.compile_and_build();
+        let partial_witness = self.fill(targets, out_targets)?;
+
+        let proof_with_pis = circuit_data.prove(partial_witness)?;
+
+        Ok(ProofData {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+}
+
+/// `CompressionReport` struct:
+///     Sizes of a proof before and after compression.
+///
+/// Fields:
+///
+///     original_proof_bytes: The serialized size of the original proof, public inputs included.
+///     original_degree_bits: The degree (`log_2`) of the original circuit.
+///     compressed_proof_bytes: The serialized size of the compressed proof, public inputs included.
+///     compressed_degree_bits: The degree (`log_2`) of the final compression circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressionReport {
+    pub original_proof_bytes: usize,
+    pub original_degree_bits: usize,
+    pub compressed_proof_bytes: usize,
+    pub compressed_degree_bits: usize,
+}
+
+impl fmt::Display for CompressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "proof size: {} bytes (degree 2^{}) -> {} bytes (degree 2^{}), {:.1}% saved",
+            self.original_proof_bytes,
+            self.original_degree_bits,
+            self.compressed_proof_bytes,
+            self.compressed_degree_bits,
+            100.0 * (1.0 - self.compressed_proof_bytes as f64 / self.original_proof_bytes as f64)
+        )
+    }
+}
+
+/// `ProofCompressor` struct:
+///     Shrinks a proof by recursively wrapping it, once per stage configuration.
+///
+/// Description:
+///
+///     By default, the proof is first wrapped with `CircuitConfig::standard_recursion_config`, which
+///     reduces the circuit to the (small) size of a recursive verifier, and then with
+///     `compression_config`, whose high rate minimizes the proof size. The wrapping circuits forward
+///     the public inputs of the original proof, e.g. the Merkle root.
+///
+///     Wrapping circuits are not zero knowledge: their witness is the wrapped proof, which hides the
+///     original witness as long as the original circuit is zero knowledge.
+#[derive(Clone, Debug)]
+pub struct ProofCompressor {
+    stage_configs: Vec<CircuitConfig>,
+}
+
+impl Default for ProofCompressor {
+    fn default() -> Self {
+        Self {
+            stage_configs: vec![
+                CircuitConfig::standard_recursion_config(),
+                compression_config(),
+            ],
+        }
+    }
+}
+
+impl ProofCompressor {
+    /// Method `with_stage_configs`:
+    ///
+    ///     Creates a compressor wrapping proofs once per configuration of `stage_configs`, in order.
+    pub fn with_stage_configs(stage_configs: Vec<CircuitConfig>) -> Self {
+        Self { stage_configs }
+    }
+
+    /// Method `compress`:
+    ///
+    ///     Wraps `proof_data` through each compression stage.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the compressed ProofData with its CompressionReport, or an Error
+    ///     if a wrapping proof fails.
+    pub fn compress(
+        &self,
+        proof_data: ProofData<F, C, D>,
+    ) -> Result<(ProofData<F, C, D>, CompressionReport), Error> {
+        let original_proof_bytes = proof_data.proof_with_pis.to_bytes().len();
+        let original_degree_bits = proof_data.circuit_data.common.degree_bits();
+
+        let compressed_proof_data =
+            self.stage_configs
+                .iter()
+                .try_fold(proof_data, |proof_data, stage_config| {
+                    WrapperCircuit::new(&proof_data, stage_config.clone()).proof()
+                })?;
+
+        let report = CompressionReport {
+            original_proof_bytes,
+            original_degree_bits,
+            compressed_proof_bytes: compressed_proof_data.proof_with_pis.to_bytes().len(),
+            compressed_degree_bits: compressed_proof_data.circuit_data.common.degree_bits(),
+        };
+
+        Ok((compressed_proof_data, report))
+    }
+
+    /// Method `compressed_circuit_data`:
+    ///
+    ///     Builds, without proving, the circuit of the proofs `compress` outputs for proofs of the
+    ///     circuit of `circuit_data`.
+    pub(crate) fn compressed_circuit_data(
+        &self,
+        circuit_data: CircuitData<F, C, D>,
+    ) -> CircuitData<F, C, D> {
+        self.stage_configs
+            .iter()
+            .fold(circuit_data, |circuit_data, stage_config| {
+                compile_wrapper(stage_config, &circuit_data).0.build::<C>()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::{merkle_tree::MerkleTree, proving_options::ProvingOptions};
+
+    #[test]
+    fn test_proof_compression() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+        let proof_data = merkle_tree.proof().unwrap();
+
+        let (compressed_proof_data, report) =
+            ProofCompressor::default().compress(proof_data).unwrap();
+
+        assert_eq!(compressed_proof_data.root(), root);
+        assert!(report.compressed_proof_bytes < report.original_proof_bytes);
+        assert_eq!(
+            compressed_proof_data.circuit_data.common.config,
+            compression_config()
+        );
+        assert!(compressed_proof_data
+            .circuit_data
+            .verify(compressed_proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_merkle_tree_proof_with_compression() {
+        let proving_options = ProvingOptions {
+            compress: true,
+            ..Default::default()
+        };
+
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+
+        let proof_data = merkle_tree.proof_with_options(&proving_options).unwrap();
+        assert_eq!(proof_data.root(), root);
+        assert_eq!(proof_data.circuit_data.common.config, compression_config());
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    io::{BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    panic,
+    process::{Child, Command, Stdio},
+    sync::{Condvar, Mutex},
+};
+
+use anyhow::{anyhow, bail, ensure, Error};
+use plonky2::{
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::{circuit_data::CircuitConfig, config::Hasher},
+    util::serialization::{Buffer, IoError, Read as BufferRead, Write as BufferWrite},
+};
+
+use crate::{
+    chunk_hash::ChunkHash,
+    circuit_compiler::{EvaluateFillCircuit, ProofData},
+    pairwise_hash::{HashData, PairwiseHash, NUM_NODE_PUBLIC_INPUTS},
+    provable::Provable,
+    recursive_hash::{RecursiveHash, RecursivePairwiseHash},
+    C, D, F,
+};
+
+const BASE_JOB_TAG: u8 = 0;
+const RECURSIVE_JOB_TAG: u8 = 1;
+
+const SUCCESS_TAG: u8 = 0;
+const FAILURE_TAG: u8 = 1;
+
+/// Maximum length of a frame, well above the largest job or node proof, so that a malformed frame
+/// can not make its reader allocate arbitrary amounts of memory.
+pub const MAX_FRAME_LEN: usize = 1 << 30;
+
+/// `ProvingJob` enum:
+///     A single node proof of a Merkle tree, that can be proved locally or shipped to a worker.
+///
+/// Variants:
+///
+///     Base: Proves the subtree root of consecutive leaves, with `PairwiseHash` for two leaves and
+///         `ChunkHash` otherwise. `index` is the index of the subtree within its level.
+///     Recursive: Proves a parent node from the proofs of its children, with `RecursivePairwiseHash`.
+pub(crate) enum ProvingJob<'a> {
+    Base {
+        leaves: Vec<HashData>,
+        index: usize,
+        circuit_config: CircuitConfig,
+    },
+    Recursive {
+        left_recursive_hash: RecursiveHash<'a>,
+        right_recursive_hash: RecursiveHash<'a>,
+        circuit_config: CircuitConfig,
+    },
+}
+
+impl<'a> ProvingJob<'a> {
+    /// Method `parent_hash`:
+    ///
+    ///     Returns the hash the job proof commits to, as its first public inputs.
+    pub(crate) fn parent_hash(&self) -> HashOut<F> {
+        match self {
+            Self::Base { leaves, .. } => {
+                let (leaves_data, leaves_hashes) = leaves
+                    .iter()
+                    .map(|leaf| (leaf.data.clone(), leaf.hash))
+                    .unzip();
+                ChunkHash::new(leaves_data, leaves_hashes).evaluate()
+            }
+            Self::Recursive {
+                left_recursive_hash,
+                right_recursive_hash,
+                ..
+            } => PoseidonHash::hash_or_noop(
+                &[
+                    left_recursive_hash.hash.elements,
+                    right_recursive_hash.hash.elements,
+                ]
+                .concat(),
+            ),
+        }
+    }
+
+    /// Method `to_bytes`:
+    ///
+    ///     Serializes the job, child proofs included, to be sent to a worker.
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        match self {
+            Self::Base {
+                leaves,
+                index,
+                circuit_config,
+            } => {
+                bytes.write_u8(BASE_JOB_TAG).map_err(Error::msg)?;
+                bytes
+                    .write_circuit_config(circuit_config)
+                    .map_err(Error::msg)?;
+                bytes.write_usize(*index).map_err(Error::msg)?;
+                bytes.write_usize(leaves.len()).map_err(Error::msg)?;
+                for leaf in leaves {
+                    bytes.write_usize(leaf.data.len()).map_err(Error::msg)?;
+                    bytes.write_field_vec(&leaf.data).map_err(Error::msg)?;
+                    bytes
+                        .write_hash::<F, PoseidonHash>(leaf.hash)
+                        .map_err(Error::msg)?;
+                }
+            }
+            Self::Recursive {
+                left_recursive_hash,
+                right_recursive_hash,
+                circuit_config,
+            } => {
+                bytes.write_u8(RECURSIVE_JOB_TAG).map_err(Error::msg)?;
+                bytes
+                    .write_circuit_config(circuit_config)
+                    .map_err(Error::msg)?;
+                for recursive_hash in [left_recursive_hash, right_recursive_hash] {
+                    bytes
+                        .write_hash::<F, PoseidonHash>(recursive_hash.hash)
+                        .map_err(Error::msg)?;
+                    let proof_data_bytes = recursive_hash.proof_data.to_bytes()?;
+                    bytes
+                        .write_usize(proof_data_bytes.len())
+                        .map_err(Error::msg)?;
+                    bytes.extend(proof_data_bytes);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+impl<'a> Provable<F, C, D> for ProvingJob<'a> {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        match self {
+            Self::Base {
+                leaves,
+                index,
+                circuit_config,
+            } => {
+                if leaves.len() == 2 {
+                    let [left_child, right_child]: [HashData; 2] =
+                        leaves.try_into().expect("two leaves");
+                    PairwiseHash::new(
+                        left_child.data,
+                        left_child.hash,
+                        right_child.data,
+                        right_child.hash,
+                    )
+                    .with_index(index)
+                    .with_circuit_config(circuit_config)
+                    .proof()
+                } else {
+                    let (leaves_data, leaves_hashes) = leaves
+                        .into_iter()
+                        .map(|leaf| (leaf.data, leaf.hash))
+                        .unzip();
+                    ChunkHash::new(leaves_data, leaves_hashes)
+                        .with_index(index)
+                        .with_circuit_config(circuit_config)
+                        .proof()
+                }
+            }
+            Self::Recursive {
+                left_recursive_hash,
+                right_recursive_hash,
+                circuit_config,
+            } => RecursivePairwiseHash::new(left_recursive_hash, right_recursive_hash)
+                .with_circuit_config(circuit_config)
+                .proof(),
+        }
+    }
+}
+
+/// Deserializes a job serialized with `ProvingJob::to_bytes` and proves it.
+fn prove_job_bytes(bytes: &[u8]) -> Result<ProofData<F, C, D>, Error> {
+    let mut buffer = Buffer::new(bytes);
+    let tag = buffer.read_u8().map_err(Error::msg)?;
+    let circuit_config = buffer.read_circuit_config().map_err(Error::msg)?;
+
+    match tag {
+        BASE_JOB_TAG => {
+            let index = buffer.read_usize().map_err(Error::msg)?;
+            let num_leaves = buffer.read_usize().map_err(Error::msg)?;
+            ensure!(
+                num_leaves.is_power_of_two() && num_leaves > 1,
+                "invalid number of leaves: {}",
+                num_leaves
+            );
+            let leaves = (0..num_leaves)
+                .map(|_| {
+                    let data_length = buffer.read_usize()?;
+                    let data = buffer.read_field_vec(data_length)?;
+                    let hash = buffer.read_hash::<F, PoseidonHash>()?;
+                    Ok(HashData::new(data, hash))
+                })
+                .collect::<Result<Vec<_>, IoError>>()
+                .map_err(Error::msg)?;
+            ProvingJob::Base {
+                leaves,
+                index,
+                circuit_config,
+            }
+            .proof()
+        }
+        RECURSIVE_JOB_TAG => {
+            let mut read_child = || -> Result<(HashOut<F>, ProofData<F, C, D>), Error> {
+                let hash = buffer.read_hash::<F, PoseidonHash>().map_err(Error::msg)?;
+                let proof_data_length = buffer.read_usize().map_err(Error::msg)?;
+                ensure!(
+                    proof_data_length <= buffer.unread_bytes().len(),
+                    "truncated child proof"
+                );
+                let mut proof_data_bytes = vec![0; proof_data_length];
+                buffer
+                    .read_exact(&mut proof_data_bytes)
+                    .map_err(Error::msg)?;
+                Ok((hash, ProofData::from_bytes(&proof_data_bytes)?))
+            };
+            let (left_hash, left_proof_data) = read_child()?;
+            let (right_hash, right_proof_data) = read_child()?;
+            ProvingJob::Recursive {
+                left_recursive_hash: RecursiveHash::new(left_hash, &left_proof_data),
+                right_recursive_hash: RecursiveHash::new(right_hash, &right_proof_data),
+                circuit_config,
+            }
+            .proof()
+        }
+        _ => bail!("unknown job tag {}", tag),
+    }
+}
+
+/// Writes `bytes` as a frame, prefixed by its length as a little endian `u64`.
+fn write_frame(writer: &mut impl Write, bytes: &[u8]) -> Result<(), Error> {
+    ensure!(
+        bytes.len() <= MAX_FRAME_LEN,
+        "frame of {} bytes exceeds the limit of {} bytes",
+        bytes.len(),
+        MAX_FRAME_LEN
+    );
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a frame written with `write_frame`, or returns `None` if the stream has been closed. Frames
+/// longer than `MAX_FRAME_LEN` are rejected before being read.
+fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>, Error> {
+    let mut length_bytes = [0; 8];
+    match reader.read_exact(&mut length_bytes) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let length = u64::from_le_bytes(length_bytes);
+    ensure!(
+        length <= MAX_FRAME_LEN as u64,
+        "frame of {} bytes exceeds the limit of {} bytes",
+        length,
+        MAX_FRAME_LEN
+    );
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+/// Function `serve_worker`:
+///
+///     Runs a proving worker: reads jobs from `reader`, proves them and writes the resulting
+///     `ProofData` (or the error message, if the job fails) to `writer`, until `reader` is closed.
+///
+/// Returns:
+///
+///     Returns a Result which is an Error if the coordinator connection fails. Failing (or panicking)
+///     jobs are reported to the coordinator and do not stop the worker.
+pub fn serve_worker(reader: impl Read, writer: impl Write) -> Result<(), Error> {
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+
+    while let Some(job_bytes) = read_frame(&mut reader)? {
+        // plonky2 panics on inconsistent witnesses, which should not take the worker down
+        let proof_data = panic::catch_unwind(|| prove_job_bytes(&job_bytes))
+            .unwrap_or_else(|_| Err(anyhow!("the job proof panicked")));
+        let response = match proof_data.and_then(|proof_data| proof_data.to_bytes()) {
+            Ok(proof_data_bytes) => [vec![SUCCESS_TAG], proof_data_bytes].concat(),
+            Err(e) => [vec![FAILURE_TAG], e.to_string().into_bytes()].concat(),
+        };
+        write_frame(&mut writer, &response)?;
+    }
+
+    Ok(())
+}
+
+/// Function `listen_worker`:
+///
+///     Runs a proving worker listening on `address`, serving coordinators one connection at a time.
+pub fn listen_worker(address: impl ToSocketAddrs) -> Result<(), Error> {
+    let listener = TcpListener::bind(address)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        serve_worker(stream.try_clone()?, stream)?;
+    }
+    Ok(())
+}
+
+struct WorkerConnection {
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+}
+
+struct WorkerPool {
+    idle_connections: Vec<WorkerConnection>,
+    num_connections: usize,
+}
+
+/// `Coordinator` struct:
+///     Dispatches node proofs of a Merkle tree to a set of proving workers, see `serve_worker`.
+///
+/// Description:
+///
+///     Workers are connected either through the standard input and output of child processes
+///     (`Coordinator::spawn`), or through TCP (`Coordinator::connect`). Each job is sent to an idle
+///     worker, together with the child proofs it depends on, and the worker replies with the node proof.
+///     Proofs returned by workers are verified, and checked to commit to the expected node hash.
+///
+///     A worker whose connection fails is discarded, and the job fails. Proving fails once no worker
+///     is left.
+pub struct Coordinator {
+    worker_pool: Mutex<WorkerPool>,
+    worker_available: Condvar,
+    num_workers: usize,
+    children: Vec<Child>,
+}
+
+impl Coordinator {
+    fn new(connections: Vec<WorkerConnection>, children: Vec<Child>) -> Result<Self, Error> {
+        ensure!(!connections.is_empty(), "at least one worker is required");
+        let num_workers = connections.len();
+        Ok(Self {
+            worker_pool: Mutex::new(WorkerPool {
+                idle_connections: connections,
+                num_connections: num_workers,
+            }),
+            worker_available: Condvar::new(),
+            num_workers,
+            children,
+        })
+    }
+
+    /// Method `spawn`:
+    ///
+    ///     Spawns `num_workers` worker processes running `command`, which should serve jobs over its
+    ///     standard input and output (e.g. the `merkle-worker` binary).
+    pub fn spawn(command: &mut Command, num_workers: usize) -> Result<Self, Error> {
+        let mut connections = vec![];
+        let mut children = vec![];
+        for _ in 0..num_workers {
+            let mut child = command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+            connections.push(WorkerConnection {
+                reader: Box::new(child.stdout.take().expect("piped stdout")),
+                writer: Box::new(child.stdin.take().expect("piped stdin")),
+            });
+            children.push(child);
+        }
+        Self::new(connections, children)
+    }
+
+    /// Method `connect`:
+    ///
+    ///     Connects to workers listening on `addresses`, see `listen_worker`.
+    pub fn connect<A: ToSocketAddrs>(addresses: &[A]) -> Result<Self, Error> {
+        let connections = addresses
+            .iter()
+            .map(|address| {
+                let stream = TcpStream::connect(address)?;
+                Ok(WorkerConnection {
+                    reader: Box::new(stream.try_clone()?),
+                    writer: Box::new(stream),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Self::new(connections, vec![])
+    }
+
+    /// Method `num_workers`:
+    ///
+    ///     Returns the number of workers the coordinator was created with.
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    /// Method `prove`:
+    ///
+    ///     Proves `job` on the next idle worker, blocking until a worker is available.
+    pub(crate) fn prove(&self, job: ProvingJob) -> Result<ProofData<F, C, D>, Error> {
+        let parent_hash = job.parent_hash();
+        let job_bytes = job.to_bytes()?;
+
+        let mut connection = self.take_connection()?;
+        let response = write_frame(&mut connection.writer, &job_bytes)
+            .and_then(|_| read_frame(&mut connection.reader))
+            .and_then(|response| response.ok_or_else(|| anyhow!("worker closed the connection")));
+        let response = match response {
+            Ok(response) => {
+                self.release_connection(Some(connection));
+                response
+            }
+            Err(e) => {
+                self.release_connection(None);
+                return Err(e);
+            }
+        };
+
+        let (tag, payload) = response
+            .split_first()
+            .ok_or_else(|| anyhow!("empty worker response"))?;
+        match *tag {
+            SUCCESS_TAG => {
+                let proof_data = ProofData::from_bytes(payload)?;
+                ensure!(
+                    proof_data.proof_with_pis.public_inputs.len() >= NUM_NODE_PUBLIC_INPUTS
+                        && proof_data.root() == parent_hash,
+                    "worker proof does not commit to the expected hash"
+                );
+                proof_data
+                    .circuit_data
+                    .verify(proof_data.proof_with_pis.clone())?;
+                Ok(proof_data)
+            }
+            FAILURE_TAG => bail!("worker failed: {}", String::from_utf8_lossy(payload)),
+            _ => bail!("unknown worker response tag {}", tag),
+        }
+    }
+
+    fn take_connection(&self) -> Result<WorkerConnection, Error> {
+        let mut worker_pool = self.worker_pool.lock().unwrap();
+        loop {
+            if let Some(connection) = worker_pool.idle_connections.pop() {
+                return Ok(connection);
+            }
+            ensure!(worker_pool.num_connections > 0, "no worker is left");
+            worker_pool = self.worker_available.wait(worker_pool).unwrap();
+        }
+    }
+
+    /// Returns a connection to the pool, or discards it if `None`.
+    fn release_connection(&self, connection: Option<WorkerConnection>) {
+        let mut worker_pool = self.worker_pool.lock().unwrap();
+        match connection {
+            Some(connection) => worker_pool.idle_connections.push(connection),
+            None => worker_pool.num_connections -= 1,
+        }
+        self.worker_available.notify_all();
+    }
+}
+
+impl Drop for Coordinator {
+    fn drop(&mut self) {
+        // closing the worker standard inputs stops the spawned workers
+        self.worker_pool.lock().unwrap().idle_connections.clear();
+        for child in self.children.iter_mut() {
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::{merkle_tree::MerkleTree, proving_options::ProvingOptions};
+
+    fn spawn_tcp_worker() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_worker(stream.try_clone().unwrap(), stream)
+        });
+        address
+    }
+
+    #[test]
+    fn test_distributed_merkle_tree_proof() {
+        let addresses = vec![spawn_tcp_worker(), spawn_tcp_worker()];
+        let coordinator = Coordinator::connect(&addresses).unwrap();
+        assert_eq!(coordinator.num_workers(), 2);
+
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+
+        let proof_data = merkle_tree
+            .proof_with_coordinator(&coordinator, &ProvingOptions::default())
+            .unwrap();
+        assert_eq!(proof_data.root(), root);
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_worker_reports_failing_jobs() {
+        let coordinator = Coordinator::connect(&[spawn_tcp_worker()]).unwrap();
+
+        // the leaf hash does not match the leaf data
+        let job = ProvingJob::Base {
+            leaves: vec![
+                HashData::new(vec![F::ZERO], HashOut::ZERO),
+                HashData::new(vec![F::ONE], HashOut::ZERO),
+            ],
+            index: 0,
+            circuit_config: CircuitConfig::standard_recursion_zk_config(),
+        };
+        assert!(coordinator.prove(job).is_err());
+
+        // the worker keeps serving jobs after a failure
+        let mut connection = coordinator.take_connection().unwrap();
+        write_frame(&mut connection.writer, &[RECURSIVE_JOB_TAG]).unwrap();
+        let response = read_frame(&mut connection.reader).unwrap().unwrap();
+        assert_eq!(response[0], FAILURE_TAG);
+    }
+
+    #[test]
+    fn test_read_frame_rejects_oversized_frames() {
+        let mut bytes = vec![];
+        write_frame(&mut bytes, &[1, 2, 3]).unwrap();
+        assert_eq!(
+            read_frame(&mut bytes.as_slice()).unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(read_frame(&mut [].as_slice()).unwrap(), None);
+
+        let length_bytes = (MAX_FRAME_LEN as u64 + 1).to_le_bytes();
+        assert!(read_frame(&mut length_bytes.as_slice()).is_err());
+        let length_bytes = u64::MAX.to_le_bytes();
+        assert!(read_frame(&mut length_bytes.as_slice()).is_err());
+
+        // a child proof longer than its job
+        let mut job_bytes = vec![RECURSIVE_JOB_TAG];
+        job_bytes
+            .write_circuit_config(&CircuitConfig::standard_recursion_zk_config())
+            .unwrap();
+        job_bytes
+            .write_hash::<F, PoseidonHash>(HashOut::ZERO)
+            .unwrap();
+        job_bytes.write_usize(usize::MAX).unwrap();
+        assert!(prove_job_bytes(&job_bytes).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use anyhow::{anyhow, ensure, Error};
+use plonky2::{field::types::PrimeField64, hash::hash_types::HashOut};
+
+use crate::{
+    leaf_format::canonical_element, merkle_tree::MerkleTree, proving_options::ProvingOptions,
+    verifier, F,
+};
+
+/// `SerializedProofData` struct:
+///     A root proof and its verifier key, serialized with `ProofData::proof_to_bytes` and
+///     `ProofData::verifier_key_to_bytes`.
+pub struct SerializedProofData {
+    proof: Vec<u8>,
+    verifier_key: Vec<u8>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Runs `f`, recording its error (or panic, which must not unwind into C) as the last error.
+fn ffi_call<T>(on_error: T, f: impl FnOnce() -> Result<T, Error>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow!("the call panicked")))
+    {
+        Ok(value) => value,
+        Err(e) => {
+            let message =
+                CString::new(e.to_string().replace('\0', " ")).expect("nul bytes are replaced");
+            LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+            on_error
+        }
+    }
+}
+
+fn ffi_status(f: impl FnOnce() -> Result<(), Error>) -> c_int {
+    ffi_call(-1, || f().map(|_| 0))
+}
+
+unsafe fn ffi_slice<'a, T>(data: *const T, len: usize) -> Result<&'a [T], Error> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    ensure!(!data.is_null(), "null pointer to {} elements", len);
+    Ok(slice::from_raw_parts(data, len))
+}
+
+unsafe fn ffi_ref<'a, T>(handle: *const T) -> Result<&'a T, Error> {
+    handle.as_ref().ok_or_else(|| anyhow!("null handle"))
+}
+
+fn elements(values: &[u64]) -> Result<Vec<F>, Error> {
+    values
+        .iter()
+        .map(|value| canonical_element(*value))
+        .collect()
+}
+
+unsafe fn ffi_hash(hash: *const u64) -> Result<HashOut<F>, Error> {
+    Ok(HashOut::from_partial(&elements(ffi_slice(hash, 4)?)?))
+}
+
+unsafe fn write_hash(hash: HashOut<F>, out: *mut u64) -> Result<(), Error> {
+    ensure!(!out.is_null(), "null output pointer");
+    for (i, element) in hash.elements.iter().enumerate() {
+        *out.add(i) = element.to_canonical_u64();
+    }
+    Ok(())
+}
+
+/// Function `merkle_last_error`:
+///
+///     Returns the message of the last error of the calling thread, valid until the next failing call
+///     on this thread. The message is empty if no call failed.
+#[no_mangle]
+pub extern "C" fn merkle_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ptr())
+}
+
+/// Function `merkle_tree_create`:
+///
+///     Builds a tree, with `MerkleTree::create`, from `num_leaves * leaf_len` field elements, leaf
+///     after leaf. The number of leaves should be a power of two greater than one, and the elements
+///     canonical.
+///
+/// Returns:
+///
+///     Returns a tree handle, to be released with `merkle_tree_free`, or null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_create(
+    leaves: *const u64,
+    num_leaves: usize,
+    leaf_len: usize,
+) -> *mut MerkleTree {
+    ffi_call(ptr::null_mut(), || {
+        ensure!(
+            num_leaves.is_power_of_two() && num_leaves > 1,
+            "the number of leaves should be a power of two, greater than one, got {}",
+            num_leaves
+        );
+        let len = num_leaves
+            .checked_mul(leaf_len)
+            .ok_or_else(|| anyhow!("the leaves are too large"))?;
+        let elements = elements(ffi_slice(leaves, len)?)?;
+        let leaves = (0..num_leaves)
+            .map(|i| elements[i * leaf_len..(i + 1) * leaf_len].to_vec())
+            .collect();
+        Ok(Box::into_raw(Box::new(MerkleTree::create(leaves))))
+    })
+}
+
+/// Function `merkle_tree_free`:
+///
+///     Releases a tree handle returned by `merkle_tree_create`. Null handles are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_free(merkle_tree: *mut MerkleTree) {
+    if !merkle_tree.is_null() {
+        drop(Box::from_raw(merkle_tree));
+    }
+}
+
+/// Function `merkle_tree_num_leaves`:
+///
+///     Returns the number of leaves of the tree, or 0 for a null handle.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_num_leaves(merkle_tree: *const MerkleTree) -> usize {
+    ffi_call(0, || Ok(ffi_ref(merkle_tree)?.leaves.len()))
+}
+
+/// Function `merkle_tree_root`:
+///
+///     Writes the 4 elements of the tree root to `root`.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_root(merkle_tree: *const MerkleTree, root: *mut u64) -> c_int {
+    ffi_status(|| write_hash(ffi_ref(merkle_tree)?.root(), root))
+}
+
+/// Function `merkle_tree_inclusion_proof`:
+///
+///     Writes the siblings of the authentication path of the leaf `leaf_index` (see
+///     `MerkleTree::inclusion_proof`) to `siblings`, 4 elements per sibling, from the leaf level up.
+///     `siblings_len`, the number of elements `siblings` can hold, should be at least
+///     `4 * log2(num_leaves)`.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_inclusion_proof(
+    merkle_tree: *const MerkleTree,
+    leaf_index: usize,
+    siblings: *mut u64,
+    siblings_len: usize,
+) -> c_int {
+    ffi_status(|| {
+        let inclusion_proof = ffi_ref(merkle_tree)?.inclusion_proof(leaf_index)?;
+        ensure!(
+            siblings_len >= 4 * inclusion_proof.siblings.len(),
+            "the siblings need {} elements",
+            4 * inclusion_proof.siblings.len()
+        );
+        for (i, sibling) in inclusion_proof.siblings.iter().enumerate() {
+            write_hash(*sibling, siblings.add(4 * i))?;
+        }
+        Ok(())
+    })
+}
+
+/// Function `merkle_verify_inclusion_path`:
+///
+///     Verifies the authentication path of a leaf of `leaf_len` elements against the 4 elements of
+///     `root`, `siblings` holding `4 * num_siblings` elements, as written by
+///     `merkle_tree_inclusion_proof` (see `verifier::verify_inclusion_path`).
+///
+/// Returns:
+///
+///     Returns 0 if the path is valid, and -1 otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_verify_inclusion_path(
+    leaf: *const u64,
+    leaf_len: usize,
+    leaf_index: usize,
+    siblings: *const u64,
+    num_siblings: usize,
+    root: *const u64,
+) -> c_int {
+    ffi_status(|| {
+        let siblings_len = num_siblings
+            .checked_mul(4)
+            .ok_or_else(|| anyhow!("too many siblings"))?;
+        let siblings = elements(ffi_slice(siblings, siblings_len)?)?
+            .chunks(4)
+            .map(HashOut::from_partial)
+            .collect::<Vec<_>>();
+        verifier::verify_inclusion_path(
+            &elements(ffi_slice(leaf, leaf_len)?)?,
+            leaf_index,
+            &siblings,
+            ffi_hash(root)?,
+        )
+    })
+}
+
+/// Function `merkle_tree_prove`:
+///
+///     Proves the tree root with `MerkleTree::proof_with_options`, on `num_threads` threads (all
+///     available threads if 0).
+///
+/// Returns:
+///
+///     Returns a proof handle, to be released with `merkle_proof_free`, or null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_prove(
+    merkle_tree: *const MerkleTree,
+    num_threads: usize,
+) -> *mut SerializedProofData {
+    ffi_call(ptr::null_mut(), || {
+        let merkle_tree = ffi_ref(merkle_tree)?.clone();
+        let proving_options = ProvingOptions {
+            num_threads: (num_threads > 0).then_some(num_threads),
+            ..ProvingOptions::default()
+        };
+        let proof_data = merkle_tree.proof_with_options(&proving_options)?;
+        Ok(Box::into_raw(Box::new(SerializedProofData {
+            proof: proof_data.proof_to_bytes(),
+            verifier_key: proof_data.verifier_key_to_bytes()?,
+        })))
+    })
+}
+
+/// Function `merkle_proof_from_bytes`:
+///
+///     Copies a serialized root proof and verifier key, e.g. received from another process, into a
+///     proof handle. The bytes are only checked by `merkle_proof_verify`.
+///
+/// Returns:
+///
+///     Returns a proof handle, to be released with `merkle_proof_free`, or null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_from_bytes(
+    proof: *const u8,
+    proof_len: usize,
+    verifier_key: *const u8,
+    verifier_key_len: usize,
+) -> *mut SerializedProofData {
+    ffi_call(ptr::null_mut(), || {
+        Ok(Box::into_raw(Box::new(SerializedProofData {
+            proof: ffi_slice(proof, proof_len)?.to_vec(),
+            verifier_key: ffi_slice(verifier_key, verifier_key_len)?.to_vec(),
+        })))
+    })
+}
+
+/// Function `merkle_proof_bytes`:
+///
+///     Returns the serialized proof, and writes its length to `len`. The bytes are owned by the
+///     handle, and valid until it is released.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_bytes(
+    proof_data: *const SerializedProofData,
+    len: *mut usize,
+) -> *const u8 {
+    ffi_call(ptr::null(), || {
+        let proof = &ffi_ref(proof_data)?.proof;
+        *len.as_mut().ok_or_else(|| anyhow!("null length pointer"))? = proof.len();
+        Ok(proof.as_ptr())
+    })
+}
+
+/// Function `merkle_proof_verifier_key`:
+///
+///     Returns the serialized verifier key, and writes its length to `len`. The bytes are owned by the
+///     handle, and valid until it is released.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_verifier_key(
+    proof_data: *const SerializedProofData,
+    len: *mut usize,
+) -> *const u8 {
+    ffi_call(ptr::null(), || {
+        let verifier_key = &ffi_ref(proof_data)?.verifier_key;
+        *len.as_mut().ok_or_else(|| anyhow!("null length pointer"))? = verifier_key.len();
+        Ok(verifier_key.as_ptr())
+    })
+}
+
+/// Function `merkle_root_verifier_key_digest`:
+///
+///     Writes the 4 elements of the digest of the verifier key of the root proofs of trees of
+///     `num_leaves` leaves of `leaf_len` elements, proved by `merkle_tree_prove`, to `verifier_key_digest`
+///     (see `MerkleTree::root_verifier_key_digest`).
+#[no_mangle]
+pub unsafe extern "C" fn merkle_root_verifier_key_digest(
+    num_leaves: usize,
+    leaf_len: usize,
+    verifier_key_digest: *mut u64,
+) -> c_int {
+    ffi_status(|| {
+        write_hash(
+            MerkleTree::root_verifier_key_digest(num_leaves, leaf_len, &ProvingOptions::default())?,
+            verifier_key_digest,
+        )
+    })
+}
+
+/// Function `merkle_proof_verify`:
+///
+///     Verifies the proof against the 4 elements of `root` and of the pinned `verifier_key_digest` (see
+///     `merkle_root_verifier_key_digest`), with `verifier::verify_root_proof`, and writes the number of
+///     leaves of the tree to `num_leaves`, unless it is null.
+///
+/// Returns:
+///
+///     Returns 0 if the proof is valid, and -1 otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_verify(
+    proof_data: *const SerializedProofData,
+    root: *const u64,
+    verifier_key_digest: *const u64,
+    num_leaves: *mut usize,
+) -> c_int {
+    ffi_status(|| {
+        let proof_data = ffi_ref(proof_data)?;
+        let verified_num_leaves = verifier::verify_root_proof(
+            &proof_data.proof,
+            &proof_data.verifier_key,
+            ffi_hash(root)?,
+            ffi_hash(verifier_key_digest)?,
+        )?;
+        if let Some(num_leaves) = num_leaves.as_mut() {
+            *num_leaves = verified_num_leaves;
+        }
+        Ok(())
+    })
+}
+
+/// Function `merkle_proof_free`:
+///
+///     Releases a proof handle. Null handles are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_free(proof_data: *mut SerializedProofData) {
+    if !proof_data.is_null() {
+        drop(Box::from_raw(proof_data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use plonky2::field::types::Field64;
+
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(merkle_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_ffi_tree_and_inclusion_proof() {
+        unsafe {
+            let leaves: Vec<u64> = (0..16).collect();
+            let merkle_tree = merkle_tree_create(leaves.as_ptr(), 8, 2);
+            assert!(!merkle_tree.is_null());
+            assert_eq!(merkle_tree_num_leaves(merkle_tree), 8);
+
+            let mut root = [0u64; 4];
+            assert_eq!(merkle_tree_root(merkle_tree, root.as_mut_ptr()), 0);
+            let expected_root = MerkleTree::create(
+                leaves
+                    .chunks(2)
+                    .map(|leaf| elements(leaf).unwrap())
+                    .collect(),
+            )
+            .root();
+            assert_eq!(
+                HashOut::from_partial(&elements(&root).unwrap()),
+                expected_root
+            );
+
+            let mut siblings = [0u64; 12];
+            assert_eq!(
+                merkle_tree_inclusion_proof(merkle_tree, 5, siblings.as_mut_ptr(), 12),
+                0
+            );
+            assert_eq!(
+                merkle_tree_inclusion_proof(merkle_tree, 5, siblings.as_mut_ptr(), 8),
+                -1
+            );
+            assert_eq!(last_error(), "the siblings need 12 elements");
+
+            let leaf = &leaves[10..12];
+            let verify = |leaf_index| {
+                merkle_verify_inclusion_path(
+                    leaf.as_ptr(),
+                    2,
+                    leaf_index,
+                    siblings.as_ptr(),
+                    3,
+                    root.as_ptr(),
+                )
+            };
+            assert_eq!(verify(5), 0);
+            assert_eq!(verify(4), -1);
+
+            merkle_tree_free(merkle_tree);
+            merkle_tree_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_ffi_rejects_invalid_arguments() {
+        unsafe {
+            let leaves = [0, 1, 2];
+            assert!(merkle_tree_create(leaves.as_ptr(), 3, 1).is_null());
+            assert!(last_error().contains("power of two"));
+
+            let leaves = [0, F::ORDER];
+            assert!(merkle_tree_create(leaves.as_ptr(), 2, 1).is_null());
+            assert!(merkle_tree_create(ptr::null(), 2, 1).is_null());
+            assert_eq!(merkle_tree_num_leaves(ptr::null()), 0);
+            assert_eq!(merkle_tree_root(ptr::null(), ptr::null_mut()), -1);
+            assert!(merkle_proof_from_bytes(ptr::null(), 1, ptr::null(), 0).is_null());
+
+            let hash = [0u64; 4];
+            assert_eq!(
+                merkle_verify_inclusion_path(
+                    hash.as_ptr(),
+                    4,
+                    0,
+                    hash.as_ptr(),
+                    usize::MAX,
+                    hash.as_ptr()
+                ),
+                -1
+            );
+            assert_eq!(last_error(), "too many siblings");
+        }
+    }
+
+    #[test]
+    fn test_ffi_proof() {
+        unsafe {
+            let leaves: Vec<u64> = (0..4).collect();
+            let merkle_tree = merkle_tree_create(leaves.as_ptr(), 4, 1);
+            let mut root = [0u64; 4];
+            assert_eq!(merkle_tree_root(merkle_tree, root.as_mut_ptr()), 0);
+
+            let proof_data = merkle_tree_prove(merkle_tree, 0);
+            merkle_tree_free(merkle_tree);
+            assert!(!proof_data.is_null());
+
+            let mut verifier_key_digest = [0u64; 4];
+            assert_eq!(
+                merkle_root_verifier_key_digest(4, 1, verifier_key_digest.as_mut_ptr()),
+                0
+            );
+            let mut num_leaves = 0;
+            assert_eq!(
+                merkle_proof_verify(
+                    proof_data,
+                    root.as_ptr(),
+                    verifier_key_digest.as_ptr(),
+                    &mut num_leaves
+                ),
+                0
+            );
+            assert_eq!(num_leaves, 4);
+
+            // round trip the serialized proof through a new handle
+            let (mut proof_len, mut verifier_key_len) = (0, 0);
+            let proof = merkle_proof_bytes(proof_data, &mut proof_len);
+            let verifier_key = merkle_proof_verifier_key(proof_data, &mut verifier_key_len);
+            let copied_proof_data =
+                merkle_proof_from_bytes(proof, proof_len, verifier_key, verifier_key_len);
+            merkle_proof_free(proof_data);
+
+            let verify = |root: &[u64; 4], verifier_key_digest: &[u64; 4]| {
+                merkle_proof_verify(
+                    copied_proof_data,
+                    root.as_ptr(),
+                    verifier_key_digest.as_ptr(),
+                    ptr::null_mut(),
+                )
+            };
+            assert_eq!(verify(&root, &verifier_key_digest), 0);
+            let mut other_root = root;
+            other_root[0] += 1;
+            assert_eq!(verify(&other_root, &verifier_key_digest), -1);
+            assert_eq!(last_error(), "the proof does not commit to the root");
+            let mut other_verifier_key_digest = [0u64; 4];
+            assert_eq!(
+                merkle_root_verifier_key_digest(8, 1, other_verifier_key_digest.as_mut_ptr()),
+                0
+            );
+            assert_eq!(verify(&root, &other_verifier_key_digest), -1);
+            assert!(last_error().contains("verifier key"));
+            merkle_proof_free(copied_proof_data);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::{ensure, Error};
+use plonky2::{
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::config::Hasher,
+};
+
+#[cfg(feature = "prover")]
+use crate::merkle_tree::MerkleTree;
+use crate::F;
+
+/// `InclusionProof` struct:
+///     A native authentication path for a single leaf of a `MerkleTree`.
+///
+/// Fields:
+///
+///     leaf_index: The position of the leaf in the tree.
+///     leaf: The leaf data.
+///     siblings: The sibling digests, ordered from the leaf level up to (but excluding) the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub leaf_index: usize,
+    pub leaf: Vec<F>,
+    pub siblings: Vec<HashOut<F>>,
+}
+
+impl InclusionProof {
+    /// Method `num_leaves`:
+    ///
+    ///     Returns the number of leaves of the tree this path was extracted from.
+    ///
+    ///     Panics if the path has `usize::BITS` siblings or more, which `verify` rejects.
+    pub fn num_leaves(&self) -> usize {
+        1 << self.siblings.len()
+    }
+
+    /// Method `compute_root`:
+    ///
+    ///     Recomputes the root of the tree by hashing the leaf together with its siblings,
+    ///     using the same `PoseidonHash::hash_or_noop` convention as `MerkleTree::create`.
+    pub fn compute_root(&self) -> HashOut<F> {
+        let mut index = self.leaf_index;
+        let mut current_hash = PoseidonHash::hash_or_noop(&self.leaf);
+        for sibling in &self.siblings {
+            let (left, right) = if index & 1 == 0 {
+                (current_hash, *sibling)
+            } else {
+                (*sibling, current_hash)
+            };
+            current_hash = PoseidonHash::hash_or_noop(&[left.elements, right.elements].concat());
+            index /= 2;
+        }
+        current_hash
+    }
+
+    /// Method `verify`:
+    ///
+    ///     Verifies the authentication path against the provided root.
+    ///
+    /// Returns:
+    ///
+    ///     Returns an Error if the path is too long for the leaf count to be addressable, if the leaf
+    ///     index is out of range or if the recomputed root does not match.
+    pub fn verify(&self, root: HashOut<F>) -> Result<(), Error> {
+        ensure!(
+            self.siblings.len() < usize::BITS as usize,
+            "a path of {} siblings has too many leaves",
+            self.siblings.len()
+        );
+        ensure!(
+            self.leaf_index < self.num_leaves(),
+            "leaf index {} out of range for a tree with {} leaves",
+            self.leaf_index,
+            self.num_leaves()
+        );
+        ensure!(
+            self.compute_root() == root,
+            "authentication path does not match the provided root"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "prover")]
+impl MerkleTree {
+    /// Method `inclusion_proof`:
+    ///
+    ///     Extracts the authentication path of the leaf at `leaf_index` from the tree digests.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the InclusionProof or an Error if the index is out of range.
+    pub fn inclusion_proof(&self, leaf_index: usize) -> Result<InclusionProof, Error> {
+        let num_leaves = self.leaves.len();
+        ensure!(
+            leaf_index < num_leaves,
+            "leaf index {} out of range for a tree with {} leaves",
+            leaf_index,
+            num_leaves
+        );
+
+        let mut siblings = vec![];
+        let mut level_offset = 0;
+        let mut level_size = num_leaves;
+        let mut index = leaf_index;
+        while level_size > 1 {
+            siblings.push(self.digests[level_offset + (index ^ 1)]);
+            level_offset += level_size;
+            level_size /= 2;
+            index /= 2;
+        }
+
+        Ok(InclusionProof {
+            leaf_index,
+            leaf: self.leaves[leaf_index].clone(),
+            siblings,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod tests {
+    use plonky2::field::types::Field;
+
+    use super::*;
+
+    #[test]
+    fn test_inclusion_proof_for_every_leaf() {
+        let merkle_tree_leaves = (0..8).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        for leaf_index in 0..8 {
+            let inclusion_proof = merkle_tree.inclusion_proof(leaf_index).unwrap();
+            assert_eq!(inclusion_proof.siblings.len(), 3);
+            assert!(inclusion_proof.verify(merkle_tree.root).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_inclusion_proof_fails_for_tampered_leaf() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        let mut inclusion_proof = merkle_tree.inclusion_proof(1).unwrap();
+        inclusion_proof.leaf = vec![F::from_canonical_u64(42)];
+        assert!(inclusion_proof.verify(merkle_tree.root).is_err());
+
+        let mut inclusion_proof = merkle_tree.inclusion_proof(1).unwrap();
+        inclusion_proof.leaf_index = 2;
+        assert!(inclusion_proof.verify(merkle_tree.root).is_err());
+    }
+
+    #[test]
+    fn test_inclusion_proof_fails_for_out_of_range_index() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        assert!(merkle_tree.inclusion_proof(4).is_err());
+    }
+
+    #[test]
+    fn test_inclusion_proof_fails_for_too_many_siblings() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        let mut inclusion_proof = merkle_tree.inclusion_proof(1).unwrap();
+        inclusion_proof.siblings.resize(64, HashOut::ZERO);
+        assert!(inclusion_proof.verify(merkle_tree.root).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::{ensure, Error};
+use plonky2::{
+    field::types::PrimeField64,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData, VerifierCircuitTarget},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+    recursion::{
+        cyclic_recursion::check_cyclic_proof_verifier_data, dummy_circuit::cyclic_base_proof,
+    },
+};
+
+use crate::{
+    merkle_tree::MerkleTree,
+    uniform_hash::{cyclic_common_data, select_hash},
+    C, D, F,
+};
+
+/// The maximum height of trees folded by `IvcTreeCircuit`, that is, trees have at most
+/// `2^IVC_MAX_HEIGHT` leaves.
+pub const IVC_MAX_HEIGHT: usize = 32;
+
+/// The IVC circuit has `2^IVC_CIRCUIT_DEGREE_BITS` gates, which fits the verification of a proof of
+/// the circuit itself.
+const IVC_CIRCUIT_DEGREE_BITS: usize = 13;
+
+/// The number of public inputs of the IVC state: the number of leaves, followed by the frontier.
+const NUM_STATE_PUBLIC_INPUTS: usize = 1 + 4 * IVC_MAX_HEIGHT;
+
+/// `IvcTreeProof` struct:
+///     A proof of the frontier of a tree whose leaves have been folded, one by one, from left to right.
+///
+/// Description:
+///
+///     The frontier holds, for each height `h` such that the bit `h` of the number of leaves is set,
+///     the root of the complete subtree of height `h` covering the leaves not yet covered by higher
+///     frontier subtrees. Other frontier entries are zero. The proof size does not depend on the
+///     number of leaves.
+#[derive(Clone, Debug)]
+pub struct IvcTreeProof {
+    pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
+}
+
+impl IvcTreeProof {
+    /// Method `num_leaves`:
+    ///
+    ///     Returns the number of leaves folded so far.
+    pub fn num_leaves(&self) -> usize {
+        self.proof_with_pis.public_inputs[0].to_canonical_u64() as usize
+    }
+
+    /// Method `frontier`:
+    ///
+    ///     Returns the frontier of the tree, see `IvcTreeProof`.
+    pub fn frontier(&self) -> Vec<HashOut<F>> {
+        self.proof_with_pis.public_inputs[1..NUM_STATE_PUBLIC_INPUTS]
+            .chunks(4)
+            .map(HashOut::from_partial)
+            .collect()
+    }
+
+    /// Method `root`:
+    ///
+    ///     Returns the root of the tree, if its number of leaves is a power of two.
+    pub fn root(&self) -> Option<HashOut<F>> {
+        let num_leaves = self.num_leaves();
+        num_leaves
+            .is_power_of_two()
+            .then(|| self.frontier()[num_leaves.ilog2() as usize])
+    }
+}
+
+struct IvcTargets {
+    has_previous_proof: BoolTarget,
+    leaf_targets: Vec<Target>,
+    previous_proof_with_pis_targets: ProofWithPublicInputsTarget<D>,
+    verifier_data_targets: VerifierCircuitTarget,
+}
+
+/// `IvcTreeCircuit` struct:
+///     An incrementally verifiable computation (IVC) of a Merkle tree: each proof appends a single
+///     leaf to the tree proved by the previous proof, relying on plonky2 cyclic recursion.
+///
+/// Description:
+///
+///     The state carried from proof to proof is the number of leaves and the frontier of the tree
+///     (see `IvcTreeProof`). Appending a leaf mirrors a binary increment of the number of leaves: the
+///     leaf hash is merged with the frontier subtrees of the heights whose bit is set, until reaching a
+///     height whose bit is unset, where the merged subtree is stored.
+///
+///     The public inputs are the number of leaves, the frontier, and the verifier data of the circuit.
+///     Proofs hence have a constant size and verification time, whatever the number of leaves, and
+///     can be extended with new leaves at any time. As for `UniformTreeCircuit`, proofs are not zero
+///     knowledge, and leaves have a fixed length.
+pub struct IvcTreeCircuit {
+    leaf_len: usize,
+    circuit_data: CircuitData<F, C, D>,
+    targets: IvcTargets,
+    base_proof_with_pis: ProofWithPublicInputs<F, C, D>,
+}
+
+impl IvcTreeCircuit {
+    /// Method `new`:
+    ///
+    ///     Builds the IVC circuit for leaves of `leaf_len` field elements, with the
+    ///     `standard_recursion_config` configuration.
+    pub fn new(leaf_len: usize) -> Self {
+        let circuit_config = CircuitConfig::standard_recursion_config();
+        let mut common_data = cyclic_common_data(&circuit_config, 1, IVC_CIRCUIT_DEGREE_BITS)
+            .expect("the standard recursion configuration fits a cyclic proof verification");
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(circuit_config);
+
+        let has_previous_proof = circuit_builder.add_virtual_bool_target_safe();
+        let leaf_targets = circuit_builder.add_virtual_targets(leaf_len);
+
+        // register public inputs, the verifier data public inputs should come last
+        let num_leaves_target = circuit_builder.add_virtual_public_input();
+        let frontier_targets: Vec<HashOutTarget> = (0..IVC_MAX_HEIGHT)
+            .map(|_| {
+                let hash_targets = circuit_builder.add_virtual_hash();
+                circuit_builder.register_public_inputs(&hash_targets.elements);
+                hash_targets
+            })
+            .collect();
+        let verifier_data_targets = circuit_builder.add_verifier_data_public_inputs();
+        common_data.num_public_inputs = circuit_builder.num_public_inputs();
+
+        let previous_proof_with_pis_targets =
+            circuit_builder.add_virtual_proof_with_pis(&common_data);
+        let previous_public_inputs = &previous_proof_with_pis_targets.public_inputs;
+
+        // without previous proof, the tree is empty
+        let zero_target = circuit_builder.zero();
+        let zero_hash_targets = HashOutTarget {
+            elements: [zero_target; 4],
+        };
+        let previous_num_leaves_target =
+            circuit_builder.mul(has_previous_proof.target, previous_public_inputs[0]);
+        let previous_frontier_targets: Vec<HashOutTarget> = (0..IVC_MAX_HEIGHT)
+            .map(|height| {
+                let hash_targets = HashOutTarget::try_from(
+                    &previous_public_inputs[1 + 4 * height..1 + 4 * (height + 1)],
+                )
+                .unwrap();
+                select_hash(
+                    &mut circuit_builder,
+                    has_previous_proof,
+                    hash_targets,
+                    zero_hash_targets,
+                )
+            })
+            .collect();
+
+        // also range checks the number of leaves
+        let previous_num_leaves_bits =
+            circuit_builder.split_le(previous_num_leaves_target, IVC_MAX_HEIGHT);
+        let one_target = circuit_builder.one();
+        let should_be_num_leaves_target =
+            circuit_builder.add(previous_num_leaves_target, one_target);
+        circuit_builder.connect(should_be_num_leaves_target, num_leaves_target);
+
+        // appends the leaf, carrying while the bits of the number of leaves are set
+        let mut node_hash_targets =
+            circuit_builder.hash_or_noop::<PoseidonHash>(leaf_targets.clone());
+        let mut is_carrying = circuit_builder._true();
+        for height in 0..IVC_MAX_HEIGHT {
+            let previous_hash_targets = previous_frontier_targets[height];
+            let merges = circuit_builder.and(is_carrying, previous_num_leaves_bits[height]);
+            let not_bit = circuit_builder.not(previous_num_leaves_bits[height]);
+            let stores = circuit_builder.and(is_carrying, not_bit);
+
+            // merged subtrees leave the frontier, the carried subtree is stored at the first unset bit
+            let kept_hash_targets = select_hash(
+                &mut circuit_builder,
+                merges,
+                zero_hash_targets,
+                previous_hash_targets,
+            );
+            let should_be_hash_targets = select_hash(
+                &mut circuit_builder,
+                stores,
+                node_hash_targets,
+                kept_hash_targets,
+            );
+            circuit_builder.connect_hashes(should_be_hash_targets, frontier_targets[height]);
+
+            let merged_hash_targets = circuit_builder.hash_or_noop::<PoseidonHash>(
+                [previous_hash_targets.elements, node_hash_targets.elements].concat(),
+            );
+            node_hash_targets = select_hash(
+                &mut circuit_builder,
+                merges,
+                merged_hash_targets,
+                node_hash_targets,
+            );
+            is_carrying = merges;
+        }
+
+        circuit_builder
+            .conditionally_verify_cyclic_proof_or_dummy::<C>(
+                has_previous_proof,
+                &previous_proof_with_pis_targets,
+                &common_data,
+            )
+            .expect("the IVC common data fits a cyclic proof verification");
+
+        let circuit_data = circuit_builder.build::<C>();
+        let base_proof_with_pis = cyclic_base_proof(
+            &common_data,
+            &circuit_data.verifier_only,
+            Default::default(),
+        );
+
+        Self {
+            leaf_len,
+            circuit_data,
+            targets: IvcTargets {
+                has_previous_proof,
+                leaf_targets,
+                previous_proof_with_pis_targets,
+                verifier_data_targets,
+            },
+            base_proof_with_pis,
+        }
+    }
+
+    /// Method `circuit_data`:
+    ///
+    ///     Returns the data of the IVC circuit.
+    pub fn circuit_data(&self) -> &CircuitData<F, C, D> {
+        &self.circuit_data
+    }
+
+    /// Method `append`:
+    ///
+    ///     Appends `leaf` to the tree proved by `previous_proof`, or to an empty tree if `None`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the proof of the extended tree, or an Error if the leaf does not
+    ///     have `leaf_len` field elements, the tree is full, or the proof generation fails.
+    pub fn append(
+        &self,
+        previous_proof: Option<&IvcTreeProof>,
+        leaf: &[F],
+    ) -> Result<IvcTreeProof, Error> {
+        ensure!(
+            leaf.len() == self.leaf_len,
+            "the IVC circuit expects leaves of {} field elements",
+            self.leaf_len
+        );
+
+        let mut partial_witness = PartialWitness::<F>::new();
+        partial_witness.set_target_arr(&self.targets.leaf_targets, leaf);
+        match previous_proof {
+            Some(previous_proof) => {
+                ensure!(
+                    previous_proof.num_leaves() < (1 << IVC_MAX_HEIGHT) - 1,
+                    "the tree is full"
+                );
+                partial_witness.set_bool_target(self.targets.has_previous_proof, true);
+                partial_witness.set_proof_with_pis_target(
+                    &self.targets.previous_proof_with_pis_targets,
+                    &previous_proof.proof_with_pis,
+                );
+            }
+            None => {
+                partial_witness.set_bool_target(self.targets.has_previous_proof, false);
+                partial_witness.set_proof_with_pis_target(
+                    &self.targets.previous_proof_with_pis_targets,
+                    &self.base_proof_with_pis,
+                );
+            }
+        }
+        partial_witness.set_verifier_data_target(
+            &self.targets.verifier_data_targets,
+            &self.circuit_data.verifier_only,
+        );
+
+        Ok(IvcTreeProof {
+            proof_with_pis: self.circuit_data.prove(partial_witness)?,
+        })
+    }
+
+    /// Method `fold`:
+    ///
+    ///     Appends `leaves`, from left to right, to the tree proved by `previous_proof`, or to an empty
+    ///     tree if `None`.
+    pub fn fold(
+        &self,
+        previous_proof: Option<IvcTreeProof>,
+        leaves: &[Vec<F>],
+    ) -> Result<Option<IvcTreeProof>, Error> {
+        leaves
+            .iter()
+            .try_fold(previous_proof, |previous_proof, leaf| {
+                self.append(previous_proof.as_ref(), leaf).map(Some)
+            })
+    }
+
+    /// Method `verify`:
+    ///
+    ///     Verifies an IVC proof, including that it was generated for the IVC circuit.
+    pub fn verify(&self, ivc_tree_proof: &IvcTreeProof) -> Result<(), Error> {
+        check_cyclic_proof_verifier_data(
+            &ivc_tree_proof.proof_with_pis,
+            &self.circuit_data.verifier_only,
+            &self.circuit_data.common,
+        )?;
+        self.circuit_data
+            .verify(ivc_tree_proof.proof_with_pis.clone())
+    }
+}
+
+impl MerkleTree {
+    /// Method `ivc_proof`:
+    ///
+    ///     Generates an IVC proof for the constructed Merkle tree, folding its leaves from left to right.
+    ///     The root of the returned proof is the root of the tree.
+    pub fn ivc_proof(&self, ivc_tree_circuit: &IvcTreeCircuit) -> Result<IvcTreeProof, Error> {
+        let ivc_tree_proof = ivc_tree_circuit.fold(None, &self.leaves)?;
+        Ok(ivc_tree_proof.expect("a Merkle tree has leaves"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{field::types::Field, plonk::config::Hasher};
+
+    use super::*;
+
+    #[test]
+    fn test_ivc_tree_proof() {
+        let ivc_tree_circuit = IvcTreeCircuit::new(1);
+        let merkle_tree_leaves: Vec<Vec<F>> =
+            (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+
+        // folds three leaves, and then extends the tree with a fourth one
+        let ivc_tree_proof = ivc_tree_circuit
+            .fold(None, &merkle_tree_leaves[..3])
+            .unwrap()
+            .unwrap();
+        assert_eq!(ivc_tree_proof.num_leaves(), 3);
+        assert_eq!(ivc_tree_proof.root(), None);
+        assert!(ivc_tree_circuit.verify(&ivc_tree_proof).is_ok());
+
+        let frontier = ivc_tree_proof.frontier();
+        assert_eq!(
+            frontier[0],
+            PoseidonHash::hash_or_noop(&merkle_tree_leaves[2])
+        );
+        assert_eq!(
+            frontier[1],
+            MerkleTree::create(merkle_tree_leaves[..2].to_vec()).root()
+        );
+
+        let extended_ivc_tree_proof = ivc_tree_circuit
+            .append(Some(&ivc_tree_proof), &merkle_tree_leaves[3])
+            .unwrap();
+        assert_eq!(extended_ivc_tree_proof.num_leaves(), 4);
+        assert_eq!(
+            extended_ivc_tree_proof.root(),
+            Some(MerkleTree::create(merkle_tree_leaves).root())
+        );
+        assert_eq!(
+            extended_ivc_tree_proof.proof_with_pis.to_bytes().len(),
+            ivc_tree_proof.proof_with_pis.to_bytes().len()
+        );
+        assert!(ivc_tree_circuit.verify(&extended_ivc_tree_proof).is_ok());
+    }
+
+    #[test]
+    fn test_merkle_tree_ivc_proof() {
+        let ivc_tree_circuit = IvcTreeCircuit::new(2);
+        let merkle_tree_leaves = (0..2)
+            .map(|i| vec![F::from_canonical_u64(i), F::ONE])
+            .collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        let ivc_tree_proof = merkle_tree.ivc_proof(&ivc_tree_circuit).unwrap();
+        assert_eq!(ivc_tree_proof.root(), Some(merkle_tree.root()));
+        assert!(ivc_tree_circuit.verify(&ivc_tree_proof).is_ok());
+
+        assert!(ivc_tree_circuit
+            .append(Some(&ivc_tree_proof), &[F::ONE])
+            .is_err());
+    }
+}
@@ -0,0 +1,412 @@
+#[cfg(feature = "prover")]
+use std::fmt::Write;
+
+use anyhow::{anyhow, ensure, Error};
+use plonky2::{
+    field::types::{Field, Field64, PrimeField64},
+    hash::hash_types::HashOut,
+    plonk::{circuit_data::VerifierCircuitData, proof::ProofWithPublicInputs},
+    util::serialization::DefaultGateSerializer,
+};
+use serde_json::{json, Value};
+
+#[cfg(feature = "prover")]
+use crate::circuit_compiler::ProofData;
+use crate::{
+    inclusion_proof::InclusionProof,
+    leaf_format::{hash_from_hex, hash_to_hex},
+    verifier::{
+        HEIGHT_PUBLIC_INPUT_INDEX, INDEX_PUBLIC_INPUT_INDEX, NUM_LEAVES_PUBLIC_INPUT_INDEX,
+        NUM_NODE_PUBLIC_INPUTS,
+    },
+    C, D, F,
+};
+
+/// Version of the JSON schema, written in every envelope. Envelopes of other versions are rejected.
+pub const JSON_SCHEMA_VERSION: u64 = 1;
+
+/// Envelope type of a Merkle root.
+pub const ROOT_TYPE: &str = "root";
+/// Envelope type of an inclusion proof.
+pub const INCLUSION_PROOF_TYPE: &str = "inclusion_proof";
+/// Envelope type of a node (or root) proof.
+pub const PROOF_TYPE: &str = "proof";
+
+/// Function `to_envelope`:
+///
+///     Wraps `data` into the versioned envelope shared by all JSON objects of this crate:
+///     `{"version": 1, "type": <kind>, "data": <data>}`.
+pub fn to_envelope(kind: &str, data: Value) -> Value {
+    json!({
+        "version": JSON_SCHEMA_VERSION,
+        "type": kind,
+        "data": data,
+    })
+}
+
+/// Function `from_envelope`:
+///
+///     Unwraps an envelope built with `to_envelope`.
+///
+/// Returns:
+///
+///     Returns a Result containing the envelope data, or an Error if the envelope is malformed, of an
+///     unsupported version, or not of type `kind`.
+pub fn from_envelope<'a>(kind: &str, envelope: &'a Value) -> Result<&'a Value, Error> {
+    let version = envelope["version"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("the envelope has no version"))?;
+    ensure!(
+        version == JSON_SCHEMA_VERSION,
+        "unsupported JSON schema version {}",
+        version
+    );
+    ensure!(
+        envelope["type"] == kind,
+        "expected a {} envelope, got {}",
+        kind,
+        envelope["type"]
+    );
+    envelope
+        .get("data")
+        .ok_or_else(|| anyhow!("the envelope has no data"))
+}
+
+/// Function `element_to_json`:
+///
+///     Encodes a field element as a decimal string, as Goldilocks elements do not fit in the integers
+///     JSON numbers can represent exactly in most parsers.
+pub fn element_to_json(element: F) -> Value {
+    Value::from(element.to_canonical_u64().to_string())
+}
+
+/// Function `element_from_json`:
+///
+///     Decodes a field element encoded with `element_to_json`.
+///
+/// Returns:
+///
+///     Returns a Result containing the element, or an Error if the value is not a decimal string of a
+///     canonical field element.
+pub fn element_from_json(value: &Value) -> Result<F, Error> {
+    let element: u64 = value
+        .as_str()
+        .ok_or_else(|| anyhow!("a field element should be a decimal string, got {}", value))?
+        .parse()?;
+    ensure!(
+        element < F::ORDER,
+        "{} is not a canonical field element",
+        element
+    );
+    Ok(F::from_canonical_u64(element))
+}
+
+fn elements_from_json(value: &Value) -> Result<Vec<F>, Error> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow!("expected an array of field elements, got {}", value))?
+        .iter()
+        .map(element_from_json)
+        .collect()
+}
+
+fn hash_from_json(value: &Value) -> Result<HashOut<F>, Error> {
+    hash_from_hex(
+        value
+            .as_str()
+            .ok_or_else(|| anyhow!("a hash should be a hexadecimal string, got {}", value))?,
+    )
+}
+
+fn usize_from_json(value: &Value) -> Result<usize, Error> {
+    let integer = value
+        .as_u64()
+        .ok_or_else(|| anyhow!("expected an integer, got {}", value))?;
+    usize::try_from(integer).map_err(|_| anyhow!("{} does not fit in a usize", integer))
+}
+
+#[cfg(feature = "prover")]
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::from("0x"), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}
+
+fn bytes_from_hex(value: &Value) -> Result<Vec<u8>, Error> {
+    let hex = value
+        .as_str()
+        .ok_or_else(|| anyhow!("expected a hexadecimal string, got {}", value))?;
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    ensure!(
+        hex.len() % 2 == 0 && hex.is_ascii(),
+        "invalid hexadecimal string"
+    );
+    (0..hex.len() / 2)
+        .map(|i| Ok(u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?))
+        .collect()
+}
+
+/// Function `root_to_json`:
+///
+///     Encodes a Merkle root as `{"version": 1, "type": "root", "data": {"root": "0x..."}}`, the root
+///     being encoded with `hash_to_hex`.
+pub fn root_to_json(root: HashOut<F>) -> Value {
+    to_envelope(ROOT_TYPE, json!({ "root": hash_to_hex(root) }))
+}
+
+/// Function `root_from_json`:
+///
+///     Decodes a Merkle root encoded with `root_to_json`.
+///
+/// Returns:
+///
+///     Returns a Result containing the root, or an Error if the JSON is malformed.
+pub fn root_from_json(value: &Value) -> Result<HashOut<F>, Error> {
+    hash_from_json(&from_envelope(ROOT_TYPE, value)?["root"])
+}
+
+impl InclusionProof {
+    /// Method `to_json`:
+    ///
+    ///     Encodes the inclusion proof as an `inclusion_proof` envelope, whose data is
+    ///     `{"leaf_index": 2, "leaf": ["5", "6"], "siblings": ["0x...", ...]}`: the leaf elements are
+    ///     decimal strings (see `element_to_json`), and the siblings are hashes encoded with
+    ///     `hash_to_hex`, from the leaf level up.
+    pub fn to_json(&self) -> Value {
+        to_envelope(
+            INCLUSION_PROOF_TYPE,
+            json!({
+                "leaf_index": self.leaf_index,
+                "leaf": self.leaf.iter().copied().map(element_to_json).collect::<Vec<_>>(),
+                "siblings": self.siblings.iter().copied().map(hash_to_hex).collect::<Vec<_>>(),
+            }),
+        )
+    }
+
+    /// Method `from_json`:
+    ///
+    ///     Decodes an inclusion proof encoded with `InclusionProof::to_json`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the InclusionProof, or an Error if the JSON is malformed.
+    pub fn from_json(value: &Value) -> Result<Self, Error> {
+        let data = from_envelope(INCLUSION_PROOF_TYPE, value)?;
+        Ok(Self {
+            leaf_index: usize_from_json(&data["leaf_index"])?,
+            leaf: elements_from_json(&data["leaf"])?,
+            siblings: data["siblings"]
+                .as_array()
+                .ok_or_else(|| anyhow!("the siblings should be an array of hashes"))?
+                .iter()
+                .map(hash_from_json)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// `SerializedProof` struct:
+///     A proof and the verifier key of its circuit, serialized with `ProofData::proof_to_bytes` and
+///     `ProofData::verifier_key_to_bytes`, as decoded from a `proof` envelope, e.g. to be checked with
+///     `verify_root_proof`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SerializedProof {
+    pub proof: Vec<u8>,
+    pub verifier_key: Vec<u8>,
+}
+
+impl SerializedProof {
+    /// Method `from_json`:
+    ///
+    ///     Decodes a proof encoded with `ProofData::to_json`. The proof is deserialized, but not
+    ///     verified, to check that the fields of the envelope are its public inputs.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the SerializedProof, or an Error if the JSON is malformed, if the
+    ///     proof or the verifier key cannot be deserialized, or if the root, height, number of leaves,
+    ///     index or public inputs of the envelope differ from those of the proof.
+    pub fn from_json(value: &Value) -> Result<Self, Error> {
+        let data = from_envelope(PROOF_TYPE, value)?;
+        let serialized_proof = Self {
+            proof: bytes_from_hex(&data["proof"])?,
+            verifier_key: bytes_from_hex(&data["verifier_key"])?,
+        };
+
+        let verifier_data = VerifierCircuitData::<F, C, D>::from_bytes(
+            serialized_proof.verifier_key.clone(),
+            &DefaultGateSerializer,
+        )
+        .map_err(Error::msg)?;
+        let public_inputs = ProofWithPublicInputs::<F, C, D>::from_bytes(
+            serialized_proof.proof.clone(),
+            &verifier_data.common,
+        )?
+        .public_inputs;
+        ensure!(
+            public_inputs.len() >= NUM_NODE_PUBLIC_INPUTS,
+            "the proof is not a node proof"
+        );
+        ensure!(
+            elements_from_json(&data["public_inputs"])? == public_inputs,
+            "the public inputs do not match the serialized proof"
+        );
+        ensure!(
+            hash_from_json(&data["root"])?.elements == public_inputs[..4],
+            "the root does not match the serialized proof"
+        );
+        for (field, public_input_index) in [
+            ("height", HEIGHT_PUBLIC_INPUT_INDEX),
+            ("num_leaves", NUM_LEAVES_PUBLIC_INPUT_INDEX),
+            ("index", INDEX_PUBLIC_INPUT_INDEX),
+        ] {
+            ensure!(
+                data[field].as_u64() == Some(public_inputs[public_input_index].to_canonical_u64()),
+                "the {} does not match the serialized proof",
+                field
+            );
+        }
+
+        Ok(serialized_proof)
+    }
+}
+
+#[cfg(feature = "prover")]
+impl ProofData<F, C, D> {
+    /// Method `to_json`:
+    ///
+    ///     Encodes the proof data as a `proof` envelope, whose data is
+    ///     `{"root": "0x...", "height": 2, "num_leaves": 4, "index": 0, "public_inputs": ["1", ...],
+    ///     "proof": "0x...", "verifier_key": "0x..."}`. `proof` and `verifier_key` hold the bytes of
+    ///     `ProofData::proof_to_bytes` and `ProofData::verifier_key_to_bytes` in hexadecimal, leaving
+    ///     the prover data out, and the other fields expose the public inputs for readers which do not
+    ///     deserialize proofs.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the JSON value, or an Error if the verifier key cannot be
+    ///     serialized.
+    pub fn to_json(&self) -> Result<Value, Error> {
+        Ok(to_envelope(
+            PROOF_TYPE,
+            json!({
+                "root": hash_to_hex(self.root()),
+                "height": self.height(),
+                "num_leaves": self.num_leaves(),
+                "index": self.index(),
+                "public_inputs": self
+                    .proof_with_pis
+                    .public_inputs
+                    .iter()
+                    .copied()
+                    .map(element_to_json)
+                    .collect::<Vec<_>>(),
+                "proof": bytes_to_hex(&self.proof_to_bytes()),
+                "verifier_key": bytes_to_hex(&self.verifier_key_to_bytes()?),
+            }),
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod tests {
+    use super::*;
+    use crate::{
+        merkle_tree::MerkleTree, provable::Provable, proving_options::ProvingOptions,
+        verifier::verify_root_proof,
+    };
+
+    fn merkle_tree() -> MerkleTree {
+        MerkleTree::create(
+            (0..4)
+                .map(|i| vec![F::from_canonical_u64(i), F::NEG_ONE])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_root_json_round_trip() {
+        let root = merkle_tree().root();
+        let json = root_to_json(root);
+        assert_eq!(json["version"], JSON_SCHEMA_VERSION);
+        assert_eq!(json["type"], ROOT_TYPE);
+        assert_eq!(root_from_json(&json).unwrap(), root);
+
+        let text = serde_json::to_string(&json).unwrap();
+        assert_eq!(
+            root_from_json(&serde_json::from_str(&text).unwrap()).unwrap(),
+            root
+        );
+
+        let mut other_version = json.clone();
+        other_version["version"] = Value::from(JSON_SCHEMA_VERSION + 1);
+        assert!(root_from_json(&other_version).is_err());
+        assert!(InclusionProof::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn test_inclusion_proof_json_round_trip() {
+        let merkle_tree = merkle_tree();
+        let root = merkle_tree.root();
+        let inclusion_proof = merkle_tree.inclusion_proof(2).unwrap();
+        let json = inclusion_proof.to_json();
+        assert_eq!(
+            json["data"]["leaf"],
+            json!(["2", F::NEG_ONE.to_canonical_u64().to_string()])
+        );
+
+        let decoded = InclusionProof::from_json(&json).unwrap();
+        assert_eq!(decoded, inclusion_proof);
+        assert!(decoded.verify(root).is_ok());
+
+        let mut non_canonical = json.clone();
+        non_canonical["data"]["leaf"][0] = Value::from(F::ORDER.to_string());
+        assert!(InclusionProof::from_json(&non_canonical).is_err());
+    }
+
+    #[test]
+    fn test_proof_data_json_round_trip() {
+        let merkle_tree = merkle_tree();
+        let root = merkle_tree.root();
+        let proof_data = merkle_tree.proof().unwrap();
+        let json = proof_data.to_json().unwrap();
+        assert_eq!(json["data"]["root"], hash_to_hex(root));
+        assert_eq!(json["data"]["num_leaves"], 4);
+        assert_eq!(json["data"]["index"], 0);
+
+        assert!(json["data"].get("proof_data").is_none());
+
+        let decoded = SerializedProof::from_json(&json).unwrap();
+        assert_eq!(decoded.proof, proof_data.proof_to_bytes());
+        assert_eq!(
+            decoded.verifier_key,
+            proof_data.verifier_key_to_bytes().unwrap()
+        );
+        let verifier_key_digest =
+            MerkleTree::root_verifier_key_digest(4, 2, &ProvingOptions::default()).unwrap();
+        assert_eq!(
+            verify_root_proof(
+                &decoded.proof,
+                &decoded.verifier_key,
+                root,
+                verifier_key_digest
+            )
+            .unwrap(),
+            4
+        );
+
+        // envelopes whose fields lie about the proof are rejected
+        let mut tampered = json.clone();
+        tampered["data"]["public_inputs"][0] = element_to_json(F::ZERO);
+        assert!(SerializedProof::from_json(&tampered).is_err());
+        let mut tampered = json.clone();
+        tampered["data"]["root"] = Value::from(hash_to_hex(HashOut::ZERO));
+        assert!(SerializedProof::from_json(&tampered).is_err());
+        for (field, value) in [("height", 3), ("num_leaves", 8), ("index", 1)] {
+            let mut tampered = json.clone();
+            tampered["data"][field] = Value::from(value);
+            assert!(SerializedProof::from_json(&tampered).is_err());
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{fmt::Write, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Error};
+use plonky2::{
+    field::types::{Field, Field64, PrimeField64},
+    hash::hash_types::HashOut,
+};
+use serde_json::Value;
+
+use crate::F;
+
+/// `LeafFormat` enum:
+///     The encodings leaves can be read from.
+///
+/// Variants:
+///
+///     Csv: One leaf per line, as comma separated field elements in decimal. Empty lines are skipped.
+///     Json: An array of leaves, each an array of field elements, given as numbers or decimal strings.
+///     Binary: Consecutive leaves of `leaf_len` field elements, each a little endian `u64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeafFormat {
+    Csv,
+    Json,
+    Binary { leaf_len: usize },
+}
+
+impl LeafFormat {
+    /// Method `from_path`:
+    ///
+    ///     Infers the format from the file extension: `.csv` and `.json` files are read as such, other
+    ///     files as binary, with leaves of `leaf_len` field elements.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the LeafFormat, or an Error for binary files without `leaf_len`.
+    pub fn from_path(path: &Path, leaf_len: Option<usize>) -> Result<Self, Error> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Ok(Self::Csv),
+            Some("json") => Ok(Self::Json),
+            _ => match leaf_len {
+                Some(leaf_len) if leaf_len > 0 => Ok(Self::Binary { leaf_len }),
+                _ => bail!("binary leaves require a positive leaf length"),
+            },
+        }
+    }
+
+    /// Method `parse_leaves`:
+    ///
+    ///     Decodes leaves from `bytes`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the leaves, or an Error if the bytes are malformed, an element is
+    ///     not a canonical field element (i.e. at least the Goldilocks order), or the binary leaf length
+    ///     is zero.
+    pub fn parse_leaves(&self, bytes: &[u8]) -> Result<Vec<Vec<F>>, Error> {
+        match self {
+            Self::Csv => std::str::from_utf8(bytes)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    line.split(',')
+                        .map(|element| parse_element(element.trim()))
+                        .collect()
+                })
+                .collect(),
+            Self::Json => {
+                let Value::Array(leaves) = serde_json::from_slice(bytes)? else {
+                    bail!("JSON leaves should be an array of leaves");
+                };
+                leaves
+                    .iter()
+                    .map(|leaf| {
+                        leaf.as_array()
+                            .ok_or_else(|| anyhow!("a JSON leaf should be an array of elements"))?
+                            .iter()
+                            .map(|element| match element {
+                                Value::Number(number) => number
+                                    .as_u64()
+                                    .ok_or_else(|| anyhow!("invalid field element {}", number))
+                                    .and_then(canonical_element),
+                                Value::String(string) => parse_element(string),
+                                _ => bail!("invalid field element {}", element),
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            Self::Binary { leaf_len } => {
+                ensure!(
+                    *leaf_len > 0,
+                    "binary leaves require a positive leaf length"
+                );
+                ensure!(
+                    bytes.len() % (8 * leaf_len) == 0,
+                    "binary leaves should be {} bytes long",
+                    8 * leaf_len
+                );
+                bytes
+                    .chunks(8 * leaf_len)
+                    .map(|leaf| {
+                        leaf.chunks(8)
+                            .map(|element| {
+                                canonical_element(u64::from_le_bytes(element.try_into().unwrap()))
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+pub(crate) fn canonical_element(value: u64) -> Result<F, Error> {
+    ensure!(
+        value < F::ORDER,
+        "{} is not a canonical field element",
+        value
+    );
+    Ok(F::from_canonical_u64(value))
+}
+
+fn parse_element(element: &str) -> Result<F, Error> {
+    canonical_element(
+        element
+            .parse()
+            .map_err(|_| anyhow!("invalid field element {:?}", element))?,
+    )
+}
+
+/// Function `hash_to_hex`:
+///
+///     Encodes a hash as `0x` followed by the little endian bytes of its four elements, in hexadecimal.
+pub fn hash_to_hex(hash: HashOut<F>) -> String {
+    hash.elements
+        .iter()
+        .fold(String::from("0x"), |mut hex, element| {
+            for byte in element.to_canonical_u64().to_le_bytes() {
+                write!(hex, "{:02x}", byte).unwrap();
+            }
+            hex
+        })
+}
+
+/// Function `hash_from_hex`:
+///
+///     Decodes a hash encoded with `hash_to_hex`, the `0x` prefix being optional.
+pub fn hash_from_hex(hex: &str) -> Result<HashOut<F>, Error> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    ensure!(
+        hex.len() == 64 && hex.is_ascii(),
+        "a hash should have 64 hexadecimal digits"
+    );
+    let bytes = (0..32)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()?;
+    let elements = bytes
+        .chunks(8)
+        .map(|element| canonical_element(u64::from_le_bytes(element.try_into().unwrap())))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(HashOut::from_partial(&elements))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_leaves() {
+        let leaves = vec![
+            vec![F::from_canonical_u64(1), F::from_canonical_u64(2)],
+            vec![F::from_canonical_u64(3), F::NEG_ONE],
+        ];
+
+        let csv = format!("1, 2\n\n3,{}\n", F::ORDER - 1);
+        assert_eq!(
+            LeafFormat::Csv.parse_leaves(csv.as_bytes()).unwrap(),
+            leaves
+        );
+
+        let json = format!("[[1, \"2\"], [3, {}]]", F::ORDER - 1);
+        assert_eq!(
+            LeafFormat::Json.parse_leaves(json.as_bytes()).unwrap(),
+            leaves
+        );
+
+        let binary: Vec<u8> = [1, 2, 3, F::ORDER - 1]
+            .iter()
+            .flat_map(|element: &u64| element.to_le_bytes())
+            .collect();
+        assert_eq!(
+            LeafFormat::Binary { leaf_len: 2 }
+                .parse_leaves(&binary)
+                .unwrap(),
+            leaves
+        );
+
+        assert!(LeafFormat::Csv
+            .parse_leaves(format!("{}", F::ORDER).as_bytes())
+            .is_err());
+        assert!(LeafFormat::Json.parse_leaves(b"[[-1]]").is_err());
+        assert!(LeafFormat::Binary { leaf_len: 2 }
+            .parse_leaves(&binary[..24])
+            .is_err());
+        assert!(LeafFormat::Binary { leaf_len: 0 }
+            .parse_leaves(&binary)
+            .is_err());
+    }
+
+    #[test]
+    fn test_leaf_format_from_path() {
+        assert_eq!(
+            LeafFormat::from_path(Path::new("leaves.csv"), None).unwrap(),
+            LeafFormat::Csv
+        );
+        assert_eq!(
+            LeafFormat::from_path(Path::new("leaves.json"), Some(2)).unwrap(),
+            LeafFormat::Json
+        );
+        assert_eq!(
+            LeafFormat::from_path(Path::new("leaves.bin"), Some(2)).unwrap(),
+            LeafFormat::Binary { leaf_len: 2 }
+        );
+        assert!(LeafFormat::from_path(Path::new("leaves.bin"), None).is_err());
+    }
+
+    #[test]
+    fn test_hash_hex_round_trip() {
+        let hash = HashOut::from_partial(&[F::ONE, F::TWO, F::NEG_ONE, F::ZERO]);
+        let hex = hash_to_hex(hash);
+        assert_eq!(hex.len(), 66);
+        assert_eq!(hash_from_hex(&hex).unwrap(), hash);
+        assert_eq!(hash_from_hex(&hex[2..]).unwrap(), hash);
+        assert!(hash_from_hex(&hex[..64]).is_err());
+    }
+}
@@ -1,10 +1,61 @@
 use plonky2::{field::goldilocks_field::GoldilocksField, plonk::config::PoseidonGoldilocksConfig};
 
+#[cfg(feature = "prover")]
+pub mod checkpoint;
+#[cfg(feature = "prover")]
+pub mod chunk_hash;
+#[cfg(feature = "prover")]
 pub mod circuit_compiler;
+#[cfg(feature = "prover")]
+pub mod compression;
+#[cfg(feature = "prover")]
+pub mod distributed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod inclusion_proof;
+#[cfg(feature = "prover")]
+pub mod ivc;
+pub mod json;
+pub mod leaf_format;
+#[cfg(feature = "prover")]
 pub mod merkle_tree;
+#[cfg(feature = "prover")]
+pub mod monolithic;
+#[cfg(feature = "prover")]
+pub mod multiproof;
+#[cfg(feature = "prover")]
 pub mod pairwise_hash;
+#[cfg(feature = "prover")]
+pub mod progress;
+#[cfg(feature = "prover")]
 pub mod provable;
+#[cfg(feature = "prover")]
+pub mod proving_options;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "prover")]
 pub mod recursive_hash;
+#[cfg(feature = "prover")]
+pub mod scheduler;
+#[cfg(feature = "prover")]
+pub mod serialization;
+#[cfg(feature = "service")]
+pub mod service;
+#[cfg(feature = "prover")]
+pub mod solidity;
+#[cfg(feature = "prover")]
+pub mod sorted_hash;
+#[cfg(feature = "prover")]
+pub mod sorted_merkle_tree;
+#[cfg(feature = "prover")]
+pub mod subtree;
+#[cfg(feature = "prover")]
+pub mod test_vectors;
+#[cfg(feature = "prover")]
+pub mod uniform_hash;
+pub mod verifier;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub const D: usize = 2;
 pub type F = GoldilocksField;
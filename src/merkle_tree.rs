@@ -1,20 +1,35 @@
 use crate::{
-    circuit_compiler::ProofData,
-    pairwise_hash::PairwiseHash,
+    checkpoint::Checkpoint,
+    chunk_hash::ChunkHash,
+    circuit_compiler::{CircuitCompiler, ProofData},
+    compression::ProofCompressor,
+    distributed::{Coordinator, ProvingJob},
+    monolithic::MonolithicTreeCircuit,
+    pairwise_hash::{HashData, PairwiseHash},
     provable::Provable,
-    recursive_hash::{RecursiveHash, RecursivePairwiseHash},
+    proving_options::{ProvingBackend, ProvingOptions},
+    recursive_hash::{compile_recursive_node, RecursiveHash},
+    scheduler::ProofScheduler,
+    uniform_hash::UniformTreeCircuit,
+    verifier::verifier_key_digest,
     C, D, F,
 };
-use anyhow::Error;
+use anyhow::{ensure, Error};
 use plonky2::{
+    field::types::Field,
     hash::{hash_types::HashOut, poseidon::PoseidonHash},
     iop::witness::{PartialWitness, WitnessWrite},
-    plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig, config::Hasher},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData, VerifierCircuitData},
+        config::Hasher,
+    },
+    util::serialization::DefaultGateSerializer,
 };
-use rayon::prelude::*;
 
 /// Our implementation is inspired by the one of Plonky2:
 /// see https://github.com/mir-protocol/plonky2/blob/main/plonky2/src/hash/merkle_tree.rs#L39.
+#[derive(Clone)]
 pub struct MerkleTree {
     pub(crate) leaves: Vec<Vec<F>>,
     pub(crate) digests: Vec<HashOut<F>>,
@@ -81,29 +96,299 @@ impl MerkleTree {
             root,
         }
     }
+
+    /// Method `root`:
+    ///
+    ///     Returns the root hash of the Merkle tree.
+    pub fn root(&self) -> HashOut<F> {
+        self.root
+    }
 }
 
-impl Provable<F, C, D> for MerkleTree {
-    /// `Provable` trait method:
-    ///  
-    ///     Generates a proof for the constructed Merkle tree.
+impl MerkleTree {
+    /// Method `digest`:
+    ///
+    ///     Returns the digest of the node `index` at height `height`, height 0 being the leaves.
+    pub(crate) fn digest(&self, height: usize, index: usize) -> HashOut<F> {
+        let num_leaves = self.leaves.len();
+        let height_offset: usize = (0..height).map(|h| num_leaves >> h).sum();
+        self.digests[height_offset + index]
+    }
+
+    /// Method `proof_with_options`:
+    ///
+    ///     Generates a proof for the constructed Merkle tree, honoring the provided `ProvingOptions`
+    ///     (thread pool, zero knowledge, FRI parameters, memory budget, checkpointing and compression).
+    ///     When resuming, checkpointed node proofs are reused, and the subtrees below them are not
+    ///     proved again.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the generated ProofData or an Error if the options are invalid
+    ///     or the proof generation fails.
+    pub fn proof_with_options(
+        self,
+        proving_options: &ProvingOptions,
+    ) -> Result<ProofData<F, C, D>, Error> {
+        self.proof_with(proving_options, None, 0)
+    }
+
+    /// Method `root_verifier_data`:
+    ///
+    ///     Returns the verifier data of the root proofs of trees of `num_leaves` leaves of `leaf_len`
+    ///     field elements, proved with `proving_options`. The circuits of the tree are built height by
+    ///     height, without proving. Since node circuits embed the verifier data of the circuits of
+    ///     their children, the root circuit pins the circuits of every node, so that root proofs
+    ///     should only be accepted against this verifier data (see `verify_root_proof`).
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the VerifierCircuitData, or an Error if the number of leaves is
+    ///     not a power of two greater than one, or the options are invalid.
+    pub fn root_verifier_data(
+        num_leaves: usize,
+        leaf_len: usize,
+        proving_options: &ProvingOptions,
+    ) -> Result<VerifierCircuitData<F, C, D>, Error> {
+        ensure!(
+            num_leaves.is_power_of_two() && num_leaves > 1,
+            "the number of leaves should be a power of two greater than one"
+        );
+        proving_options.validate(num_leaves)?;
+        let circuit_config = proving_options.circuit_config();
+
+        // circuits do not depend on the leaf values, nor on the node indices
+        let leaf = vec![F::ZERO; leaf_len];
+        let leaf_hash = PoseidonHash::hash_or_noop(&leaf);
+        let circuit_data = match proving_options.backend {
+            ProvingBackend::Recursive => {
+                let leaf_chunk_size = proving_options.leaf_chunk_size;
+                let base_circuit_data = if leaf_chunk_size == 2 {
+                    PairwiseHash::new(leaf.clone(), leaf_hash, leaf, leaf_hash)
+                        .with_circuit_config(circuit_config.clone())
+                        .compile_and_build()
+                        .0
+                } else {
+                    ChunkHash::new(
+                        vec![leaf; leaf_chunk_size],
+                        vec![leaf_hash; leaf_chunk_size],
+                    )
+                    .with_circuit_config(circuit_config.clone())
+                    .compile_and_build()
+                    .0
+                };
+                (leaf_chunk_size.ilog2()..num_leaves.ilog2()).fold(
+                    base_circuit_data,
+                    |child_circuit_data: CircuitData<F, C, D>, _| {
+                        compile_recursive_node(
+                            &circuit_config,
+                            &child_circuit_data,
+                            &child_circuit_data,
+                        )
+                        .0
+                        .build::<C>()
+                    },
+                )
+            }
+            ProvingBackend::Monolithic => {
+                MonolithicTreeCircuit::new(MerkleTree::create(vec![leaf; num_leaves]))
+                    .with_circuit_config(circuit_config)
+                    .compile_and_build()
+                    .0
+            }
+            ProvingBackend::Uniform => {
+                UniformTreeCircuit::with_circuit_config(leaf_len, circuit_config)?
+                    .into_circuit_data()
+            }
+        };
+
+        let circuit_data = if proving_options.compress {
+            ProofCompressor::default().compressed_circuit_data(circuit_data)
+        } else {
+            circuit_data
+        };
+        Ok(circuit_data.verifier_data())
+    }
+
+    /// Method `root_verifier_key_digest`:
+    ///
+    ///     Returns the digest of the serialized verifier key of `MerkleTree::root_verifier_data`, to be
+    ///     pinned by verifiers of root proofs (see `verify_root_proof`).
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the digest, or an Error if the number of leaves is not a power
+    ///     of two greater than one, or the options are invalid.
+    pub fn root_verifier_key_digest(
+        num_leaves: usize,
+        leaf_len: usize,
+        proving_options: &ProvingOptions,
+    ) -> Result<HashOut<F>, Error> {
+        let verifier_key_bytes = Self::root_verifier_data(num_leaves, leaf_len, proving_options)?
+            .to_bytes(&DefaultGateSerializer)
+            .map_err(Error::msg)?;
+        Ok(verifier_key_digest(&verifier_key_bytes))
+    }
+
+    /// Method `proof_with_coordinator`:
+    ///
+    ///     Generates a proof for the constructed Merkle tree as `proof_with_options` does, but ships
+    ///     every node proof to the workers of `coordinator`. Unless set in `proving_options`, the number
+    ///     of scheduling threads matches the number of workers.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the generated ProofData or an Error if the options are invalid
+    ///     or the proof generation fails.
+    pub fn proof_with_coordinator(
+        self,
+        coordinator: &Coordinator,
+        proving_options: &ProvingOptions,
+    ) -> Result<ProofData<F, C, D>, Error> {
+        let proving_options = ProvingOptions {
+            num_threads: Some(
+                proving_options
+                    .num_threads
+                    .unwrap_or(coordinator.num_workers()),
+            ),
+            ..proving_options.clone()
+        };
+        self.proof_with(&proving_options, Some(coordinator), 0)
+    }
+
+    /// Method `proof_with`:
+    ///
+    ///     Generates a proof for the constructed Merkle tree, as the subtree of a larger tree starting at
+    ///     leaf `leaf_offset`, which should be a multiple of the number of leaves. Node proofs commit to
+    ///     their index within their level of the larger tree. With the monolithic backend, the tree is
+    ///     proved at once by a `MonolithicTreeCircuit`, on the thread pool of the scheduler. With the
+    ///     uniform backend, node proofs of the `UniformTreeCircuit` are scheduled as recursive node
+    ///     proofs are.
+    pub(crate) fn proof_with(
+        self,
+        proving_options: &ProvingOptions,
+        coordinator: Option<&Coordinator>,
+        leaf_offset: usize,
+    ) -> Result<ProofData<F, C, D>, Error> {
+        proving_options.validate(self.leaves.len())?;
+        let scheduler = proving_options.scheduler()?;
+        let proof_data = match proving_options.backend {
+            ProvingBackend::Recursive => {
+                self.proof_recursively(scheduler, proving_options, coordinator, leaf_offset)?
+            }
+            ProvingBackend::Monolithic => {
+                ensure!(
+                    coordinator.is_none(),
+                    "monolithic proofs can not be distributed"
+                );
+                let index = leaf_offset / self.leaves.len();
+                let monolithic_tree_circuit = MonolithicTreeCircuit::new(self)
+                    .with_index(index)
+                    .with_circuit_config(proving_options.circuit_config());
+                scheduler.install(|| monolithic_tree_circuit.proof())?
+            }
+            ProvingBackend::Uniform => {
+                ensure!(
+                    coordinator.is_none(),
+                    "uniform proofs can not be distributed"
+                );
+                ensure!(
+                    leaf_offset == 0,
+                    "uniform proofs do not commit to the position of a subtree"
+                );
+                let uniform_tree_circuit = UniformTreeCircuit::with_circuit_config(
+                    self.leaves[0].len(),
+                    proving_options.circuit_config(),
+                )?;
+                let proof_with_pis =
+                    self.uniform_proof_with_scheduler(&uniform_tree_circuit, &scheduler)?;
+                ProofData {
+                    proof_with_pis,
+                    circuit_data: uniform_tree_circuit.into_circuit_data(),
+                }
+            }
+        };
+        if proving_options.compress {
+            let (compressed_proof_data, _) = ProofCompressor::default().compress(proof_data)?;
+            return Ok(compressed_proof_data);
+        }
+        Ok(proof_data)
+    }
+
+    /// Proves the tree with node proofs scheduled on `scheduler`, checkpointed to the checkpoint
+    /// directory of `proving_options`, if any.
+    fn proof_recursively(
+        self,
+        scheduler: ProofScheduler,
+        proving_options: &ProvingOptions,
+        coordinator: Option<&Coordinator>,
+        leaf_offset: usize,
+    ) -> Result<ProofData<F, C, D>, Error> {
+        let scheduler = match &proving_options.checkpoint_directory {
+            Some(checkpoint_directory) => scheduler.with_checkpoint(Checkpoint::open(
+                checkpoint_directory,
+                &self.manifest(proving_options, leaf_offset),
+                proving_options.resume,
+            )?),
+            None => scheduler,
+        };
+        self.prove(&scheduler, proving_options, coordinator, leaf_offset)
+    }
+
+    /// Method `manifest`:
+    ///
+    ///     Identifies the tree and the proving options affecting node proofs, so that checkpointed
+    ///     node proofs are only reused for the same tree, proved with the same circuits.
+    pub(crate) fn manifest(&self, proving_options: &ProvingOptions, leaf_offset: usize) -> String {
+        format!(
+            "root: {:?}\nnum_leaves: {}\nleaf_offset: {}\nleaf_chunk_size: {}\ncircuit_config: {:?}\n",
+            self.root.elements,
+            self.leaves.len(),
+            leaf_offset,
+            proving_options.leaf_chunk_size,
+            proving_options.circuit_config()
+        )
+    }
+
+    /// Method `proof_with_scheduler`:
+    ///
+    ///     Generates a proof for the constructed Merkle tree with the default `ProvingOptions`,
+    ///     scheduling node proofs on the provided scheduler.
     ///
     /// Returns:
     ///
     ///     Returns a Result containing the generated ProofData or an Error if the proof generation fails.
+    pub fn proof_with_scheduler(
+        self,
+        scheduler: &ProofScheduler,
+    ) -> Result<ProofData<F, C, D>, Error> {
+        self.prove(scheduler, &ProvingOptions::default(), None, 0)
+    }
+
+    /// Method `prove`:
+    ///
+    ///     Generates a proof for the constructed Merkle tree.
     ///
     /// Description:
     ///
-    ///     This method generates a proof for the constructed Merkle tree. It first establishes a connection between the root hash of the Merkle tree and
+    ///     This method first establishes a connection between the root hash of the Merkle tree and
     ///     the hash of the last digest using a CircuitBuilder. It then constructs a PartialWitness containing hash targets for the root and last digest hashes.
     ///     The CircuitBuilder is used to create a circuit configuration, and the proof is generated using the provided circuit_data and partial_witness using the prove method.
     ///
-    ///     The method then proceeds with recursive proof generation for the tree. It calculates the height of the tree and iterates over the heights in parallel using the rayon library.
-    ///     For each height, it computes proof data for pairs of child hashes. If the current height is 0, it generates pairwise proofs using PairwiseHash instances.
-    ///     Otherwise, it generates proofs using RecursiveHash and RecursivePairwiseHash instances based on previously generated proof data.
+    ///     The method then proceeds with recursive proof generation for the tree, through the `ProofScheduler`. Base nodes are proved
+    ///     using PairwiseHash instances over pairs of leaves, or ChunkHash instances over chunks of `leaf_chunk_size` leaves, whereas higher nodes are proved using RecursiveHash and RecursivePairwiseHash instances,
+    ///     as soon as the proofs of both of their children are available. All node circuits are built with the circuit configuration of `proving_options`.
+    ///     Node proofs are generated locally, or by the workers of `coordinator` if provided. Base nodes are
+    ///     positioned from `leaf_offset`, so that node proofs commit to their index within their level.
     ///
     ///     The final root proof data is returned as the result.
-    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+    fn prove(
+        self,
+        scheduler: &ProofScheduler,
+        proving_options: &ProvingOptions,
+        coordinator: Option<&Coordinator>,
+        leaf_offset: usize,
+    ) -> Result<ProofData<F, C, D>, Error> {
         // Connect the root of the Merkle tree with the last digest. This is not strictly necessary, but we include it for completeness
         let mut circuit_builder = CircuitBuilder::new(CircuitConfig::standard_recursion_config());
         let mut partial_witness = PartialWitness::<F>::new();
@@ -120,66 +405,61 @@ impl Provable<F, C, D> for MerkleTree {
 
         // Recursive proof generation
         let merkle_tree_height = self.leaves.len().ilog2() as usize;
-        let mut proof_datas = vec![];
-        let mut current_child_hash_index = 0;
-        let mut proof_data_index = 0;
-
-        // Parallelize the inner loop using rayon
-        for height in 0..(merkle_tree_height) {
-            let chunk_size = 1 << (merkle_tree_height - height);
-
-            let thread_proof_datas: Vec<_> = if height == 0 {
-                (current_child_hash_index..current_child_hash_index + chunk_size)
-                    .into_par_iter()
-                    .step_by(2)
-                    .map(|current_child_index| {
-                        let pairwise_hash = PairwiseHash::new(
-                            self.leaves[current_child_index].clone(),
-                            self.digests[current_child_index],
-                            self.leaves[current_child_index + 1].clone(),
-                            self.digests[current_child_index + 1],
-                        );
-                        pairwise_hash.proof()
-                    })
-                    .collect::<Result<Vec<_>, _>>()?
-            } else {
-                let inner_proof_data: Vec<_> = (current_child_hash_index
-                    ..current_child_hash_index + chunk_size)
-                    .into_par_iter()
-                    .step_by(2)
-                    .zip(
-                        (proof_data_index..(proof_data_index + chunk_size))
-                            .into_par_iter()
-                            .step_by(2),
-                    )
-                    .map(|(current_child_index, proof_data_index)| {
-                        let left_recursive_hash = RecursiveHash::new(
-                            self.digests[current_child_index],
-                            &proof_datas[proof_data_index],
-                        );
-                        let right_recursive_hash = RecursiveHash::new(
-                            self.digests[current_child_index + 1],
-                            &proof_datas[proof_data_index + 1],
-                        );
-                        let recursive_pairwise_hash =
-                            RecursivePairwiseHash::new(left_recursive_hash, right_recursive_hash);
-
-                        recursive_pairwise_hash.proof() // Adjust the error handling as needed
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
-
-                proof_data_index += chunk_size;
-
-                inner_proof_data
-            };
-
-            proof_datas.extend(thread_proof_datas);
-            current_child_hash_index += chunk_size;
-        }
-
-        let root_proof_data = proof_datas.pop().expect("Failed to get last proof data");
+        let circuit_config = proving_options.circuit_config();
+
+        let leaf_chunk_size = proving_options.leaf_chunk_size;
+
+        let prove_job = |proving_job: ProvingJob| match coordinator {
+            Some(coordinator) => coordinator.prove(proving_job),
+            None => proving_job.proof(),
+        };
+
+        scheduler.prove_tree(
+            merkle_tree_height,
+            leaf_chunk_size.ilog2() as usize,
+            |index| {
+                let chunk = index * leaf_chunk_size..(index + 1) * leaf_chunk_size;
+                prove_job(ProvingJob::Base {
+                    leaves: self.leaves[chunk.clone()]
+                        .iter()
+                        .zip(&self.digests[chunk])
+                        .map(|(leaf, digest)| HashData::new(leaf.clone(), *digest))
+                        .collect(),
+                    index: leaf_offset / leaf_chunk_size + index,
+                    circuit_config: circuit_config.clone(),
+                })
+            },
+            |height, index, left_proof_data, right_proof_data| {
+                prove_job(ProvingJob::Recursive {
+                    left_recursive_hash: RecursiveHash::new(
+                        self.digest(height - 1, 2 * index),
+                        left_proof_data,
+                    ),
+                    right_recursive_hash: RecursiveHash::new(
+                        self.digest(height - 1, 2 * index + 1),
+                        right_proof_data,
+                    ),
+                    circuit_config: circuit_config.clone(),
+                })
+            },
+        )
+    }
+}
 
-        Ok(root_proof_data)
+impl Provable<F, C, D> for MerkleTree {
+    /// `Provable` trait method:
+    ///  
+    ///     Generates a proof for the constructed Merkle tree.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the generated ProofData or an Error if the proof generation fails.
+    ///
+    /// Description:
+    ///
+    ///     Generates the proof with the default `ProvingOptions`, see `MerkleTree::proof_with_options`.
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        self.proof_with_options(&ProvingOptions::default())
     }
 }
 
@@ -220,6 +500,41 @@ mod tests {
         assert!(merkle_tree.prove_and_verify().is_ok());
     }
 
+    #[test]
+    // Tests that root proofs are generated with the circuit pinned by `MerkleTree::root_verifier_data`
+    fn test_merkle_tree_root_verifier_data() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let proof_data = merkle_tree.proof().unwrap();
+
+        let root_verifier_data =
+            MerkleTree::root_verifier_data(4, 1, &ProvingOptions::default()).unwrap();
+        assert_eq!(
+            root_verifier_data.verifier_only,
+            proof_data.circuit_data.verifier_only
+        );
+        assert_eq!(
+            MerkleTree::root_verifier_key_digest(4, 1, &ProvingOptions::default()).unwrap(),
+            verifier_key_digest(&proof_data.verifier_key_to_bytes().unwrap())
+        );
+        assert!(root_verifier_data.verify(proof_data.proof_with_pis).is_ok());
+
+        // trees of another height or leaf length are proved with other circuits
+        let other_verifier_data =
+            MerkleTree::root_verifier_data(8, 1, &ProvingOptions::default()).unwrap();
+        assert_ne!(
+            other_verifier_data.verifier_only,
+            root_verifier_data.verifier_only
+        );
+        let other_verifier_data =
+            MerkleTree::root_verifier_data(4, 2, &ProvingOptions::default()).unwrap();
+        assert_ne!(
+            other_verifier_data.verifier_only,
+            root_verifier_data.verifier_only
+        );
+        assert!(MerkleTree::root_verifier_data(3, 1, &ProvingOptions::default()).is_err());
+    }
+
     #[test]
     #[should_panic]
     // Tests that the proof and verification of a ill formed `MerkleTree` instance panics
@@ -232,7 +547,7 @@ mod tests {
         let merkle_tree_leaves = vec![vec![f_one], vec![f_two], vec![f_three], vec![f_four]];
 
         let mut merkle_tree = MerkleTree::create(merkle_tree_leaves.clone());
-        merkle_tree.digests[2] = PoseidonHash::hash_or_noop(&vec![F::ZERO]);
+        merkle_tree.digests[2] = PoseidonHash::hash_or_noop(&[F::ZERO]);
         assert!(merkle_tree.prove_and_verify().is_err());
     }
 
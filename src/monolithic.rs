@@ -0,0 +1,233 @@
+use anyhow::Error;
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig},
+};
+
+use crate::{
+    circuit_compiler::{CircuitCompiler, EvaluateFillCircuit, ProofData},
+    merkle_tree::MerkleTree,
+    pairwise_hash::register_base_node_public_inputs,
+    provable::Provable,
+    C, D, F,
+};
+
+/// `MonolithicTreeCircuit` struct:
+///     Proves a whole Merkle tree with a single circuit, hashing every leaf and every inner node,
+///     instead of aggregating node proofs recursively.
+///
+/// Description:
+///
+///     The circuit grows linearly with the number of leaves and is proved at once, so that it can not
+///     be spread over a thread pool the way node proofs are, but it avoids the fixed cost of verifying
+///     proofs in circuit, which dominates the recursive prover on small trees. The proof has the
+///     public inputs of a node proof (root, height, number of leaves and index), so that it is
+///     verified with `verify_root_proof`, as recursive root proofs are.
+pub struct MonolithicTreeCircuit {
+    pub(crate) merkle_tree: MerkleTree,
+    pub(crate) index: usize,
+    pub(crate) circuit_config: CircuitConfig,
+}
+
+impl MonolithicTreeCircuit {
+    pub fn new(merkle_tree: MerkleTree) -> Self {
+        Self {
+            merkle_tree,
+            index: 0,
+            circuit_config: CircuitConfig::standard_recursion_zk_config(),
+        }
+    }
+
+    /// Method `with_index`:
+    ///
+    ///     Sets the index of the tree root within its level (0 by default), when the tree is the
+    ///     subtree of a larger tree.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Method `with_circuit_config`:
+    ///
+    ///     Sets the configuration the circuit is built with (`standard_recursion_zk_config` by default).
+    pub fn with_circuit_config(mut self, circuit_config: CircuitConfig) -> Self {
+        self.circuit_config = circuit_config;
+        self
+    }
+}
+
+impl CircuitCompiler<C, F, D> for MonolithicTreeCircuit {
+    type Targets = Vec<Vec<Target>>;
+    type OutTargets = (HashOutTarget, Target);
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(self.circuit_config.clone());
+        let leaf_targets: Vec<Vec<Target>> = self
+            .merkle_tree
+            .leaves
+            .iter()
+            .map(|leaf| circuit_builder.add_virtual_targets(leaf.len()))
+            .collect();
+
+        let mut level: Vec<HashOutTarget> = leaf_targets
+            .iter()
+            .map(|targets| circuit_builder.hash_or_noop::<PoseidonHash>(targets.clone()))
+            .collect();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| {
+                    circuit_builder
+                        .hash_or_noop::<PoseidonHash>([pair[0].elements, pair[1].elements].concat())
+                })
+                .collect();
+        }
+
+        // register public inputs
+        let root_hash_targets = circuit_builder.add_virtual_hash();
+        circuit_builder.register_public_inputs(&root_hash_targets.elements);
+        let height = self.merkle_tree.leaves.len().ilog2() as usize;
+        let index_target = register_base_node_public_inputs(&mut circuit_builder, height);
+
+        circuit_builder.connect_hashes(level[0], root_hash_targets);
+
+        (
+            circuit_builder,
+            leaf_targets,
+            (root_hash_targets, index_target),
+        )
+    }
+}
+
+impl EvaluateFillCircuit<C, F, D> for MonolithicTreeCircuit {
+    type Value = HashOut<F>;
+
+    fn evaluate(&self) -> Self::Value {
+        self.merkle_tree.root()
+    }
+
+    fn fill(
+        &self,
+        targets: Self::Targets,
+        out_targets: Self::OutTargets,
+    ) -> Result<PartialWitness<F>, Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        let (root_hash_targets, index_target) = out_targets;
+
+        targets
+            .iter()
+            .zip(&self.merkle_tree.leaves)
+            .for_each(|(leaf_targets, leaf)| partial_witness.set_target_arr(leaf_targets, leaf));
+        partial_witness.set_hash_target(root_hash_targets, self.evaluate());
+        partial_witness.set_target(index_target, F::from_canonical_usize(self.index));
+
+        Ok(partial_witness)
+    }
+}
+
+impl Provable<F, C, D> for MonolithicTreeCircuit {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        let (circuit_data, targets, out_targets) = self.compile_and_build();
+        let partial_witness = self.fill(targets, out_targets)?;
+
+        let proof_with_pis = circuit_data.prove(partial_witness)?;
+
+        Ok(ProofData {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        proving_options::{ProvingBackend, ProvingOptions},
+        verifier::{verifier_key_digest, verify_root_proof},
+    };
+
+    fn merkle_tree(num_leaves: usize) -> MerkleTree {
+        MerkleTree::create(
+            (0..num_leaves)
+                .map(|i| vec![F::from_canonical_usize(i); 1 + i % 6])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_monolithic_tree_circuit() {
+        let merkle_tree = merkle_tree(8);
+        let root = merkle_tree.root();
+        let proof_data = MonolithicTreeCircuit::new(merkle_tree).proof().unwrap();
+        assert_eq!(proof_data.root(), root);
+        assert_eq!(proof_data.height(), 3);
+        assert_eq!(proof_data.num_leaves(), 8);
+        assert_eq!(proof_data.index(), 0);
+
+        let proof_bytes = proof_data.proof_to_bytes();
+        let verifier_key_bytes = proof_data.verifier_key_to_bytes().unwrap();
+        let verifier_key_digest = verifier_key_digest(&verifier_key_bytes);
+        assert_eq!(
+            verify_root_proof(&proof_bytes, &verifier_key_bytes, root, verifier_key_digest)
+                .unwrap(),
+            8
+        );
+        assert!(verify_root_proof(
+            &proof_bytes,
+            &verifier_key_bytes,
+            HashOut::ZERO,
+            verifier_key_digest
+        )
+        .is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_monolithic_tree_circuit_fails_if_root_is_invalid() {
+        let mut merkle_tree = merkle_tree(4);
+        merkle_tree.root = HashOut::ZERO;
+        MonolithicTreeCircuit::new(merkle_tree).proof().unwrap();
+    }
+
+    #[test]
+    fn test_monolithic_backend() {
+        let proving_options = ProvingOptions {
+            backend: ProvingBackend::Monolithic,
+            num_threads: Some(2),
+            ..ProvingOptions::default()
+        };
+        let merkle_tree =
+            MerkleTree::create((0..4).map(|i| vec![F::from_canonical_u64(i)]).collect());
+        let root = merkle_tree.root();
+        let proof_data = merkle_tree.proof_with_options(&proving_options).unwrap();
+        assert_eq!(proof_data.root(), root);
+        assert_eq!(proof_data.num_leaves(), 4);
+
+        // monolithic root proofs are pinned by the monolithic circuit
+        let verifier_key_digest =
+            MerkleTree::root_verifier_key_digest(4, 1, &proving_options).unwrap();
+        assert_eq!(
+            verify_root_proof(
+                &proof_data.proof_to_bytes(),
+                &proof_data.verifier_key_to_bytes().unwrap(),
+                root,
+                verifier_key_digest
+            )
+            .unwrap(),
+            4
+        );
+        assert_ne!(
+            verifier_key_digest,
+            MerkleTree::root_verifier_key_digest(4, 1, &ProvingOptions::default()).unwrap()
+        );
+    }
+}
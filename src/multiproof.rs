@@ -0,0 +1,440 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, ensure, Error};
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig, config::Hasher},
+};
+
+use crate::{
+    circuit_compiler::{CircuitCompiler, EvaluateFillCircuit, ProofData},
+    merkle_tree::MerkleTree,
+    provable::Provable,
+    C, D, F,
+};
+
+/// `MultiProof` struct:
+///     A compact authentication of several leaves of a `MerkleTree` against a single root.
+///     Sibling digests shared between the individual authentication paths, or computable from
+///     the authenticated leaves themselves, are only included once (respectively, not at all).
+///
+/// Fields:
+///
+///     height: The height of the tree, that is, `log_2(num_leaves)`.
+///     leaf_indices: The strictly increasing indices of the authenticated leaves.
+///     leaves: The data of the authenticated leaves, in the same order as `leaf_indices`.
+///     siblings: The sibling digests needed to recompute the root, in traversal order
+///         (level by level, from the leaves to the root, by increasing index within a level).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiProof {
+    pub height: usize,
+    pub leaf_indices: Vec<usize>,
+    pub leaves: Vec<Vec<F>>,
+    pub siblings: Vec<HashOut<F>>,
+}
+
+impl MultiProof {
+    /// Method `sibling_positions`:
+    ///
+    ///     Returns, for each level of the tree, the indices (within that level) of the nodes whose
+    ///     digest must be provided as a sibling, in the order they are consumed.
+    fn sibling_positions(height: usize, leaf_indices: &[usize]) -> Vec<Vec<usize>> {
+        let mut known_indices = leaf_indices.to_vec();
+        let mut sibling_positions = vec![];
+
+        for _ in 0..height {
+            let mut level_sibling_positions = vec![];
+            let mut i = 0;
+            while i < known_indices.len() {
+                let index = known_indices[i];
+                if index & 1 == 0 && known_indices.get(i + 1) == Some(&(index + 1)) {
+                    // both children are known, no sibling needed
+                    i += 2;
+                } else {
+                    level_sibling_positions.push(index ^ 1);
+                    i += 1;
+                }
+            }
+            sibling_positions.push(level_sibling_positions);
+
+            known_indices = known_indices.iter().map(|index| index / 2).collect();
+            known_indices.dedup();
+        }
+
+        sibling_positions
+    }
+
+    /// Method `check_indices`:
+    ///
+    ///     Checks that the tree height is addressable, and that the leaf indices are non empty,
+    ///     strictly increasing and within range.
+    fn check_indices(height: usize, leaf_indices: &[usize]) -> Result<(), Error> {
+        ensure!(
+            height < usize::BITS as usize,
+            "a tree of height {} has too many leaves",
+            height
+        );
+        ensure!(
+            !leaf_indices.is_empty(),
+            "at least one leaf index is required"
+        );
+        ensure!(
+            leaf_indices.windows(2).all(|pair| pair[0] < pair[1]),
+            "leaf indices should be strictly increasing"
+        );
+        ensure!(
+            *leaf_indices.last().unwrap() < 1 << height,
+            "leaf index out of range for a tree of height {}",
+            height
+        );
+        Ok(())
+    }
+
+    /// Method `compute_root`:
+    ///
+    ///     Recomputes the root from the authenticated leaves and the provided siblings, hashing each
+    ///     internal node exactly once.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the root, or an Error if the proof is malformed.
+    pub fn compute_root(&self) -> Result<HashOut<F>, Error> {
+        Self::check_indices(self.height, &self.leaf_indices)?;
+        ensure!(
+            self.leaves.len() == self.leaf_indices.len(),
+            "the number of leaves does not match the number of leaf indices"
+        );
+
+        let mut known_hashes: BTreeMap<usize, HashOut<F>> = self
+            .leaf_indices
+            .iter()
+            .zip(&self.leaves)
+            .map(|(index, leaf)| (*index, PoseidonHash::hash_or_noop(leaf)))
+            .collect();
+        let mut siblings = self.siblings.iter();
+
+        for _ in 0..self.height {
+            let mut parent_hashes = BTreeMap::new();
+            for (index, hash) in &known_hashes {
+                if parent_hashes.contains_key(&(index / 2)) {
+                    continue;
+                }
+                let sibling_hash = match known_hashes.get(&(index ^ 1)) {
+                    Some(sibling_hash) => *sibling_hash,
+                    None => *siblings
+                        .next()
+                        .ok_or_else(|| anyhow!("not enough sibling digests"))?,
+                };
+                let (left, right) = if index & 1 == 0 {
+                    (*hash, sibling_hash)
+                } else {
+                    (sibling_hash, *hash)
+                };
+                parent_hashes.insert(
+                    index / 2,
+                    PoseidonHash::hash_or_noop(&[left.elements, right.elements].concat()),
+                );
+            }
+            known_hashes = parent_hashes;
+        }
+
+        ensure!(siblings.next().is_none(), "too many sibling digests");
+        Ok(known_hashes[&0])
+    }
+
+    /// Method `verify`:
+    ///
+    ///     Verifies the multiproof against the provided root.
+    pub fn verify(&self, root: HashOut<F>) -> Result<(), Error> {
+        ensure!(
+            self.compute_root()? == root,
+            "multiproof does not match the provided root"
+        );
+        Ok(())
+    }
+}
+
+impl MerkleTree {
+    /// Method `multiproof`:
+    ///
+    ///     Generates a compact `MultiProof` for the leaves at `leaf_indices`, reading the needed
+    ///     siblings from the tree digests.
+    ///
+    /// Arguments:
+    ///
+    ///     leaf_indices: The indices of the leaves to authenticate. They are sorted and deduplicated.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the MultiProof, or an Error if an index is out of range.
+    pub fn multiproof(&self, leaf_indices: &[usize]) -> Result<MultiProof, Error> {
+        let height = self.leaves.len().ilog2() as usize;
+        let mut leaf_indices = leaf_indices.to_vec();
+        leaf_indices.sort_unstable();
+        leaf_indices.dedup();
+        MultiProof::check_indices(height, &leaf_indices)?;
+
+        let mut siblings = vec![];
+        let mut level_offset = 0;
+        let mut level_size = self.leaves.len();
+        for level_sibling_positions in MultiProof::sibling_positions(height, &leaf_indices) {
+            siblings.extend(
+                level_sibling_positions
+                    .iter()
+                    .map(|position| self.digests[level_offset + position]),
+            );
+            level_offset += level_size;
+            level_size /= 2;
+        }
+
+        Ok(MultiProof {
+            height,
+            leaves: leaf_indices
+                .iter()
+                .map(|index| self.leaves[*index].clone())
+                .collect(),
+            leaf_indices,
+            siblings,
+        })
+    }
+}
+
+/// `MultiProofCircuit` struct:
+///     Encapsulates the circuit verifying a `MultiProof`. The shape of the circuit is fixed by the
+///     tree height, the leaf indices and the leaf lengths, each internal node being hashed once.
+///
+///     The public inputs are the root hash, followed, for each authenticated leaf, by its index and
+///     its hash. Leaf data and sibling digests are part of the witness.
+#[derive(Clone, Debug)]
+pub struct MultiProofCircuit {
+    pub(crate) multiproof: MultiProof,
+    pub(crate) root: HashOut<F>,
+}
+
+impl MultiProofCircuit {
+    pub fn new(multiproof: MultiProof) -> Result<Self, Error> {
+        let root = multiproof.compute_root()?;
+        Ok(Self { multiproof, root })
+    }
+}
+
+impl CircuitCompiler<C, F, D> for MultiProofCircuit {
+    type Targets = (Vec<Vec<Target>>, Vec<HashOutTarget>);
+    type OutTargets = HashOutTarget;
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
+
+        let root_hash_targets = circuit_builder.add_virtual_hash();
+        circuit_builder.register_public_inputs(&root_hash_targets.elements);
+
+        let leaf_data_targets: Vec<Vec<Target>> = self
+            .multiproof
+            .leaves
+            .iter()
+            .map(|leaf| circuit_builder.add_virtual_targets(leaf.len()))
+            .collect();
+
+        let mut known_hash_targets = BTreeMap::new();
+        for (index, data_targets) in self.multiproof.leaf_indices.iter().zip(&leaf_data_targets) {
+            let leaf_hash_targets =
+                circuit_builder.hash_or_noop::<PoseidonHash>(data_targets.clone());
+
+            let index_target = circuit_builder.constant(F::from_canonical_usize(*index));
+            circuit_builder.register_public_input(index_target);
+            circuit_builder.register_public_inputs(&leaf_hash_targets.elements);
+
+            known_hash_targets.insert(*index, leaf_hash_targets);
+        }
+
+        let mut sibling_hash_targets = vec![];
+        for _ in 0..self.multiproof.height {
+            let mut parent_hash_targets = BTreeMap::new();
+            for (index, hash_targets) in &known_hash_targets {
+                if parent_hash_targets.contains_key(&(index / 2)) {
+                    continue;
+                }
+                let sibling_targets = match known_hash_targets.get(&(index ^ 1)) {
+                    Some(sibling_targets) => *sibling_targets,
+                    None => {
+                        let sibling_targets = circuit_builder.add_virtual_hash();
+                        sibling_hash_targets.push(sibling_targets);
+                        sibling_targets
+                    }
+                };
+                let (left, right) = if index & 1 == 0 {
+                    (*hash_targets, sibling_targets)
+                } else {
+                    (sibling_targets, *hash_targets)
+                };
+                let parent_targets = circuit_builder
+                    .hash_or_noop::<PoseidonHash>([left.elements, right.elements].concat());
+                parent_hash_targets.insert(index / 2, parent_targets);
+            }
+            known_hash_targets = parent_hash_targets;
+        }
+
+        circuit_builder.connect_hashes(known_hash_targets[&0], root_hash_targets);
+
+        (
+            circuit_builder,
+            (leaf_data_targets, sibling_hash_targets),
+            root_hash_targets,
+        )
+    }
+}
+
+impl EvaluateFillCircuit<C, F, D> for MultiProofCircuit {
+    type Value = HashOut<F>;
+
+    fn evaluate(&self) -> Self::Value {
+        self.root
+    }
+
+    fn fill(
+        &self,
+        targets: Self::Targets,
+        out_targets: Self::OutTargets,
+    ) -> Result<PartialWitness<F>, Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        let (leaf_data_targets, sibling_hash_targets) = targets;
+
+        ensure!(
+            sibling_hash_targets.len() == self.multiproof.siblings.len(),
+            "the number of sibling digests does not match the circuit"
+        );
+
+        leaf_data_targets
+            .iter()
+            .zip(&self.multiproof.leaves)
+            .for_each(|(data_targets, leaf)| partial_witness.set_target_arr(data_targets, leaf));
+        sibling_hash_targets
+            .iter()
+            .zip(&self.multiproof.siblings)
+            .for_each(|(sibling_targets, sibling)| {
+                partial_witness.set_hash_target(*sibling_targets, *sibling)
+            });
+        partial_witness.set_hash_target(out_targets, self.root);
+
+        Ok(partial_witness)
+    }
+}
+
+impl Provable<F, C, D> for MultiProofCircuit {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        let (circuit_data, targets, out_targets) = self.compile_and_build();
+        let partial_witness = self.fill(targets, out_targets)?;
+
+        let proof_with_pis = circuit_data.prove(partial_witness)?;
+
+        Ok(ProofData {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merkle_tree(num_leaves: u64) -> MerkleTree {
+        MerkleTree::create(
+            (0..num_leaves)
+                .map(|i| vec![F::from_canonical_u64(i), F::from_canonical_u64(i + 1)])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_multiproof_deduplicates_siblings() {
+        let merkle_tree = merkle_tree(8);
+
+        // leaves 0 and 1 are siblings, their parent is the sibling of the parent of 2 and 3
+        let multiproof = merkle_tree.multiproof(&[0, 1, 2, 3]).unwrap();
+        assert_eq!(multiproof.siblings.len(), 1);
+        assert!(multiproof.verify(merkle_tree.root).is_ok());
+
+        let multiproof = merkle_tree.multiproof(&[6, 1, 1]).unwrap();
+        assert_eq!(multiproof.leaf_indices, vec![1, 6]);
+        assert_eq!(multiproof.siblings.len(), 4);
+        assert!(multiproof.verify(merkle_tree.root).is_ok());
+    }
+
+    #[test]
+    fn test_multiproof_for_every_subset() {
+        let merkle_tree = merkle_tree(4);
+
+        for subset in 1..16usize {
+            let leaf_indices: Vec<usize> = (0..4).filter(|i| subset & (1 << i) != 0).collect();
+            let multiproof = merkle_tree.multiproof(&leaf_indices).unwrap();
+            assert!(multiproof.verify(merkle_tree.root).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_multiproof_fails_for_invalid_data() {
+        let merkle_tree = merkle_tree(8);
+
+        let mut multiproof = merkle_tree.multiproof(&[2, 5]).unwrap();
+        multiproof.leaves[1] = vec![F::ZERO];
+        assert!(multiproof.verify(merkle_tree.root).is_err());
+
+        let mut multiproof = merkle_tree.multiproof(&[2, 5]).unwrap();
+        multiproof.siblings.pop();
+        assert!(multiproof.verify(merkle_tree.root).is_err());
+
+        let mut multiproof = merkle_tree.multiproof(&[2, 5]).unwrap();
+        multiproof.leaf_indices = vec![5, 2];
+        assert!(multiproof.verify(merkle_tree.root).is_err());
+
+        let mut multiproof = merkle_tree.multiproof(&[2, 5]).unwrap();
+        multiproof.height = usize::BITS as usize;
+        assert!(multiproof.verify(merkle_tree.root).is_err());
+
+        assert!(merkle_tree.multiproof(&[8]).is_err());
+        assert!(merkle_tree.multiproof(&[]).is_err());
+    }
+
+    #[test]
+    fn test_multiproof_circuit() {
+        let merkle_tree = merkle_tree(8);
+        let multiproof = merkle_tree.multiproof(&[0, 1, 5]).unwrap();
+
+        let multiproof_circuit = MultiProofCircuit::new(multiproof).unwrap();
+        let proof_data = multiproof_circuit.proof().unwrap();
+
+        assert_eq!(
+            proof_data.proof_with_pis.public_inputs[..4],
+            merkle_tree.root.elements
+        );
+        assert_eq!(
+            proof_data.proof_with_pis.public_inputs[4],
+            F::from_canonical_u64(0)
+        );
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_multiproof_circuit_fails_for_invalid_root() {
+        let merkle_tree = merkle_tree(4);
+        let multiproof = merkle_tree.multiproof(&[1, 2]).unwrap();
+
+        let mut multiproof_circuit = MultiProofCircuit::new(multiproof).unwrap();
+        multiproof_circuit.root = PoseidonHash::hash_or_noop(&[F::ZERO]);
+        assert!(multiproof_circuit.prove_and_verify().is_err());
+    }
+}
@@ -1,5 +1,8 @@
+use std::ops::Range;
+
 use anyhow::Error;
 use plonky2::{
+    field::types::{Field, PrimeField64},
     hash::{
         hash_types::{HashOut, HashOutTarget},
         poseidon::PoseidonHash,
@@ -15,6 +18,67 @@ use crate::{
     C, D, F,
 };
 
+pub use crate::verifier::{
+    HEIGHT_PUBLIC_INPUT_INDEX, INDEX_PUBLIC_INPUT_INDEX, NUM_LEAVES_PUBLIC_INPUT_INDEX,
+    NUM_NODE_PUBLIC_INPUTS,
+};
+
+/// Registers the public inputs of a base subtree, after its root: its height and number of leaves,
+/// as constants fixed by the circuit, and its index, whose returned target is filled by the prover.
+/// The index of a base node is only constrained by the recursive nodes above it, see
+/// `RecursivePairwiseHash`.
+pub(crate) fn register_base_node_public_inputs(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+) -> Target {
+    let height_target = circuit_builder.constant(F::from_canonical_usize(height));
+    let num_leaves_target = circuit_builder.constant(F::from_canonical_usize(1 << height));
+    let index_target = circuit_builder.add_virtual_target();
+    circuit_builder.register_public_input(height_target);
+    circuit_builder.register_public_input(num_leaves_target);
+    circuit_builder.register_public_input(index_target);
+    index_target
+}
+
+impl ProofData<F, C, D> {
+    /// Method `root`:
+    ///
+    ///     Returns the node hash committed to by a node proof, i.e. the root for a root proof.
+    pub fn root(&self) -> HashOut<F> {
+        HashOut::from_partial(&self.proof_with_pis.public_inputs[..4])
+    }
+
+    /// Method `height`:
+    ///
+    ///     Returns the height of the subtree committed to by a node proof.
+    pub fn height(&self) -> usize {
+        self.proof_with_pis.public_inputs[HEIGHT_PUBLIC_INPUT_INDEX].to_canonical_u64() as usize
+    }
+
+    /// Method `num_leaves`:
+    ///
+    ///     Returns the number of leaves of the subtree committed to by a node proof.
+    pub fn num_leaves(&self) -> usize {
+        self.proof_with_pis.public_inputs[NUM_LEAVES_PUBLIC_INPUT_INDEX].to_canonical_u64() as usize
+    }
+
+    /// Method `index`:
+    ///
+    ///     Returns the index, within its level, of the node committed to by a node proof. A root proof
+    ///     has index 0.
+    pub fn index(&self) -> usize {
+        self.proof_with_pis.public_inputs[INDEX_PUBLIC_INPUT_INDEX].to_canonical_u64() as usize
+    }
+
+    /// Method `leaf_range`:
+    ///
+    ///     Returns the range of leaves covered by the subtree committed to by a node proof.
+    pub fn leaf_range(&self) -> Range<usize> {
+        let num_leaves = self.num_leaves();
+        self.index() * num_leaves..(self.index() + 1) * num_leaves
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct HashData {
     pub(crate) data: Vec<F>,
@@ -28,10 +92,12 @@ impl HashData {
 }
 
 #[derive(Clone, Debug)]
-pub(crate) struct PairwiseHash {
+pub struct PairwiseHash {
     pub(crate) left_child: HashData,
     pub(crate) right_child: HashData,
     pub(crate) parent_hash: HashOut<F>,
+    pub(crate) index: usize,
+    pub(crate) circuit_config: CircuitConfig,
 }
 
 impl PairwiseHash {
@@ -50,17 +116,35 @@ impl PairwiseHash {
             left_child,
             right_child,
             parent_hash,
+            index: 0,
+            circuit_config: CircuitConfig::standard_recursion_zk_config(),
         }
     }
+
+    /// Method `with_index`:
+    ///
+    ///     Sets the index of the parent node within its level (0 by default), i.e. the left leaf is the
+    ///     leaf `2 * index` of the tree.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Method `with_circuit_config`:
+    ///
+    ///     Sets the configuration the circuit is built with (`standard_recursion_zk_config` by default).
+    pub fn with_circuit_config(mut self, circuit_config: CircuitConfig) -> Self {
+        self.circuit_config = circuit_config;
+        self
+    }
 }
 
 impl CircuitCompiler<C, F, D> for PairwiseHash {
     type Targets = (Vec<Target>, Vec<Target>, HashOutTarget, HashOutTarget);
-    type OutTargets = HashOutTarget;
+    type OutTargets = (HashOutTarget, Target);
 
     fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
-        let mut circuit_builder =
-            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(self.circuit_config.clone());
         let left_data_targets = circuit_builder.add_virtual_targets(self.left_child.data.len());
         let right_data_targets = circuit_builder.add_virtual_targets(self.right_child.data.len());
 
@@ -79,6 +163,7 @@ impl CircuitCompiler<C, F, D> for PairwiseHash {
 
         // register public inputs
         circuit_builder.register_public_inputs(&parent_hash_targets.elements);
+        let index_target = register_base_node_public_inputs(&mut circuit_builder, 1);
 
         let should_be_parent_hash_targets = circuit_builder.hash_or_noop::<PoseidonHash>(
             [left_hash_targets.elements, right_hash_targets.elements].concat(),
@@ -94,7 +179,7 @@ impl CircuitCompiler<C, F, D> for PairwiseHash {
                 left_hash_targets,
                 right_hash_targets,
             ),
-            parent_hash_targets,
+            (parent_hash_targets, index_target),
         )
     }
 }
@@ -117,7 +202,7 @@ impl EvaluateFillCircuit<C, F, D> for PairwiseHash {
         let right_data_targets = targets.1;
         let left_hash_targets = targets.2;
         let right_hash_targets = targets.3;
-        let parent_hash_targets = out_targets;
+        let (parent_hash_targets, index_target) = out_targets;
 
         (0..left_data_targets.len()).for_each(|i| {
             partial_witness.set_target(left_data_targets[i], self.left_child.data[i])
@@ -140,6 +225,7 @@ impl EvaluateFillCircuit<C, F, D> for PairwiseHash {
                 self.parent_hash.elements[i],
             );
         });
+        partial_witness.set_target(index_target, F::from_canonical_usize(self.index));
 
         Ok(partial_witness)
     }
@@ -161,8 +247,6 @@ impl Provable<F, C, D> for PairwiseHash {
 
 #[cfg(test)]
 mod tests {
-    use plonky2::field::types::Field;
-
     use super::*;
 
     #[test]
@@ -177,6 +261,41 @@ mod tests {
         assert!(pairwise_hash.prove_and_verify().is_ok());
     }
 
+    #[test]
+    fn test_pairwise_hash_commits_to_node_position() {
+        let f_0_hash = PoseidonHash::hash_or_noop(&[F::ZERO]);
+        let f_1_hash = PoseidonHash::hash_or_noop(&[F::ONE]);
+
+        let pairwise_hash = PairwiseHash::new(vec![F::ZERO], f_0_hash, vec![F::ONE], f_1_hash);
+        let parent_hash = pairwise_hash.parent_hash;
+        let proof_data = pairwise_hash.proof().unwrap();
+
+        assert_eq!(
+            proof_data.proof_with_pis.public_inputs.len(),
+            NUM_NODE_PUBLIC_INPUTS
+        );
+        assert_eq!(proof_data.root(), parent_hash);
+        assert_eq!(proof_data.height(), 1);
+        assert_eq!(proof_data.num_leaves(), 2);
+
+        let proof_data = PairwiseHash::new(vec![F::ZERO], f_0_hash, vec![F::ONE], f_1_hash)
+            .with_index(3)
+            .proof()
+            .unwrap();
+        assert_eq!(proof_data.leaf_range(), 6..8);
+    }
+
+    #[test]
+    fn test_pairwise_hash_with_circuit_config() {
+        let f_0_hash = PoseidonHash::hash_or_noop(&[F::ZERO]);
+        let f_1_hash = PoseidonHash::hash_or_noop(&[F::ONE]);
+
+        let pairwise_hash = PairwiseHash::new(vec![F::ZERO], f_0_hash, vec![F::ONE], f_1_hash)
+            .with_circuit_config(CircuitConfig::standard_recursion_config());
+        let (circuit_data, _, _) = pairwise_hash.compile_and_build();
+        assert!(!circuit_data.common.config.zero_knowledge);
+    }
+
     #[test]
     fn test_pairwise_hash_well_formed() {
         let f_0 = F::ZERO;
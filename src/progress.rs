@@ -0,0 +1,284 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Error};
+
+/// `ProofProgress` struct:
+///     A snapshot of the progress of a tree proof, reported after each node proof.
+///
+/// Fields:
+///
+///     height: The height of the node that has just been proved.
+///     nodes_proved_at_height: The number of nodes proved so far at that height.
+///     nodes_at_height: The total number of nodes to prove at that height.
+///     nodes_proved: The number of nodes proved so far, over all heights.
+///     total_nodes: The total number of node proofs of the tree.
+///     elapsed: The time elapsed since the tree proof started.
+///     estimated_remaining: An estimate of the remaining proving time, assuming that the remaining
+///         nodes take on average as long as the nodes proved so far.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofProgress {
+    pub height: usize,
+    pub nodes_proved_at_height: usize,
+    pub nodes_at_height: usize,
+    pub nodes_proved: usize,
+    pub total_nodes: usize,
+    pub elapsed: Duration,
+    pub estimated_remaining: Duration,
+}
+
+/// `ProgressObserver` trait:
+///     Receives progress reports during tree proof generation. Reports may come from any proving
+///     thread, so that implementations should be cheap and thread safe.
+pub trait ProgressObserver: Send + Sync {
+    fn on_node_proved(&self, progress: &ProofProgress);
+}
+
+impl<T> ProgressObserver for T
+where
+    T: Fn(&ProofProgress) + Send + Sync,
+{
+    fn on_node_proved(&self, progress: &ProofProgress) {
+        self(progress)
+    }
+}
+
+impl fmt::Debug for dyn ProgressObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressObserver")
+    }
+}
+
+/// `CancellationToken` struct:
+///     A cloneable flag used to abort a tree proof. It is checked before each node proof, so that
+///     cancellation takes effect once the node proofs in progress are done.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Method `cancel`:
+    ///
+    ///     Requests the cancellation of the proofs using this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Method `is_cancelled`:
+    ///
+    ///     Returns whether cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// `ProgressTracker` struct:
+///     Tracks the progress of a single tree proof, forwarding reports to the observer and checking
+///     the cancellation token, if any.
+pub(crate) struct ProgressTracker<'a> {
+    started_at: Instant,
+    base_height: usize,
+    nodes_proved_per_height: Vec<AtomicUsize>,
+    nodes_proved: AtomicUsize,
+    nodes_restored: AtomicUsize,
+    total_nodes: usize,
+    tree_height: usize,
+    progress_observer: Option<&'a dyn ProgressObserver>,
+    cancellation_token: Option<&'a CancellationToken>,
+}
+
+impl<'a> ProgressTracker<'a> {
+    /// Method `new`:
+    ///
+    ///     Starts tracking the proof of a tree of height `tree_height`, whose base proofs cover
+    ///     subtrees of height `base_height`.
+    pub(crate) fn new(
+        tree_height: usize,
+        base_height: usize,
+        progress_observer: Option<&'a dyn ProgressObserver>,
+        cancellation_token: Option<&'a CancellationToken>,
+    ) -> Self {
+        let total_nodes = (base_height..=tree_height)
+            .map(|height| 1 << (tree_height - height))
+            .sum();
+        Self {
+            started_at: Instant::now(),
+            base_height,
+            nodes_proved_per_height: (base_height..=tree_height)
+                .map(|_| AtomicUsize::new(0))
+                .collect(),
+            nodes_proved: AtomicUsize::new(0),
+            nodes_restored: AtomicUsize::new(0),
+            total_nodes,
+            tree_height,
+            progress_observer,
+            cancellation_token,
+        }
+    }
+
+    /// Method `check_cancelled`:
+    ///
+    ///     Returns an Error if cancellation has been requested.
+    pub(crate) fn check_cancelled(&self) -> Result<(), Error> {
+        match self.cancellation_token {
+            Some(cancellation_token) if cancellation_token.is_cancelled() => {
+                Err(anyhow!("proof generation was cancelled"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Method `subtree_restored`:
+    ///
+    ///     Records that the node at height `height` has been restored from a checkpoint, so that its
+    ///     whole subtree does not need to be proved. Restored nodes are not reported to the observer,
+    ///     but are accounted for in subsequent reports.
+    pub(crate) fn subtree_restored(&self, height: usize) {
+        let nodes_restored = (self.base_height..=height)
+            .map(|subtree_height| {
+                let nodes = 1 << (height - subtree_height);
+                self.nodes_proved_per_height[subtree_height - self.base_height]
+                    .fetch_add(nodes, Ordering::SeqCst);
+                nodes
+            })
+            .sum();
+        self.nodes_proved
+            .fetch_add(nodes_restored, Ordering::SeqCst);
+        self.nodes_restored
+            .fetch_add(nodes_restored, Ordering::SeqCst);
+    }
+
+    /// Method `node_proved`:
+    ///
+    ///     Records that a node at height `height` has been proved, and reports it to the observer.
+    pub(crate) fn node_proved(&self, height: usize) {
+        let nodes_proved_at_height = self.nodes_proved_per_height[height - self.base_height]
+            .fetch_add(1, Ordering::SeqCst)
+            + 1;
+        let nodes_proved = self.nodes_proved.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if let Some(progress_observer) = self.progress_observer {
+            let elapsed = self.started_at.elapsed();
+            // restored nodes took no time, the estimate only relies on the nodes proved in this run
+            let nodes_proved_in_run = nodes_proved - self.nodes_restored.load(Ordering::SeqCst);
+            let estimated_remaining = elapsed.mul_f64(
+                (self.total_nodes - nodes_proved) as f64 / nodes_proved_in_run.max(1) as f64,
+            );
+            progress_observer.on_node_proved(&ProofProgress {
+                height,
+                nodes_proved_at_height,
+                nodes_at_height: 1 << (self.tree_height - height),
+                nodes_proved,
+                total_nodes: self.total_nodes,
+                elapsed,
+                estimated_remaining,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::{merkle_tree::MerkleTree, proving_options::ProvingOptions, F};
+
+    fn merkle_tree() -> MerkleTree {
+        MerkleTree::create((0..4).map(|i| vec![F::from_canonical_u64(i)]).collect())
+    }
+
+    #[test]
+    fn test_progress_tracker_counts_nodes() {
+        let reports = Mutex::new(vec![]);
+        let observer = |progress: &ProofProgress| reports.lock().unwrap().push(progress.clone());
+
+        let progress_tracker = ProgressTracker::new(3, 1, Some(&observer), None);
+        (0..4).for_each(|_| progress_tracker.node_proved(1));
+        (0..2).for_each(|_| progress_tracker.node_proved(2));
+        progress_tracker.node_proved(3);
+
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 7);
+        assert_eq!(reports[3].nodes_proved_at_height, 4);
+        assert_eq!(reports[3].nodes_at_height, 4);
+        assert_eq!(reports[5].height, 2);
+        assert_eq!(reports[6].nodes_proved, reports[6].total_nodes);
+        assert_eq!(reports[6].estimated_remaining, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_progress_tracker_counts_restored_subtrees() {
+        let reports = Mutex::new(vec![]);
+        let observer = |progress: &ProofProgress| reports.lock().unwrap().push(progress.clone());
+
+        let progress_tracker = ProgressTracker::new(3, 1, Some(&observer), None);
+        progress_tracker.subtree_restored(2);
+        (0..2).for_each(|_| progress_tracker.node_proved(1));
+        progress_tracker.node_proved(2);
+        progress_tracker.node_proved(3);
+
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(reports[0].nodes_proved, 4);
+        assert_eq!(reports[1].nodes_proved_at_height, 4);
+        assert_eq!(reports[2].nodes_proved_at_height, 2);
+        assert_eq!(reports[3].nodes_proved, reports[3].total_nodes);
+    }
+
+    #[test]
+    fn test_progress_observer() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let observer_reports = reports.clone();
+        let proving_options = ProvingOptions {
+            progress_observer: Some(Arc::new(move |progress: &ProofProgress| {
+                observer_reports.lock().unwrap().push(progress.clone())
+            })),
+            ..Default::default()
+        };
+
+        assert!(merkle_tree().proof_with_options(&proving_options).is_ok());
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports.last().unwrap().height, 2);
+        assert_eq!(reports.last().unwrap().nodes_proved, 3);
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let proving_options = ProvingOptions {
+            cancellation_token: Some(cancellation_token),
+            ..Default::default()
+        };
+        assert!(merkle_tree().proof_with_options(&proving_options).is_err());
+
+        // cancels once the first node has been proved
+        let cancellation_token = CancellationToken::new();
+        let observer_cancellation_token = cancellation_token.clone();
+        let proving_options = ProvingOptions {
+            num_threads: Some(1),
+            progress_observer: Some(Arc::new(move |_: &ProofProgress| {
+                observer_cancellation_token.cancel()
+            })),
+            cancellation_token: Some(cancellation_token),
+            ..Default::default()
+        };
+        assert!(merkle_tree().proof_with_options(&proving_options).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{ensure, Error};
+use plonky2::{fri::FriConfig, plonk::circuit_data::CircuitConfig};
+
+use crate::{
+    progress::{CancellationToken, ProgressObserver},
+    scheduler::ProofScheduler,
+};
+
+/// `ProvingBackend` enum:
+///     Selects how the root of a Merkle tree is proved.
+///
+/// Variants:
+///
+///     Recursive: Aggregates node proofs recursively, see `MerkleTree::proof_with_options`.
+///     Monolithic: Proves the whole tree with a single `MonolithicTreeCircuit`, which is faster on
+///         small trees, but can not be checkpointed nor distributed.
+///     Uniform: Proves every node with the `UniformTreeCircuit`, whose verifier key does not depend on
+///         the tree height. Its root proofs expose the root hash followed by the verifier data of the
+///         circuit (see `UniformTreeCircuit::verify`), are not zero knowledge, and can not be
+///         checkpointed nor distributed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProvingBackend {
+    #[default]
+    Recursive,
+    Monolithic,
+    Uniform,
+}
+
+/// `ProvingOptions` struct:
+///     Gathers the knobs used to generate a Merkle tree proof.
+///
+/// Fields:
+///
+///     backend: The `ProvingBackend` the root is proved with. Only the thread pool, the circuit
+///         configuration and compression apply to the monolithic backend, and the leaf chunk size and
+///         checkpoints do not apply to the uniform backend.
+///     num_threads: The number of threads of the dedicated proving thread pool. If `None`, proofs are
+///         scheduled on the global rayon thread pool.
+///     zero_knowledge: Whether node circuits are built with zero knowledge (blinding) enabled.
+///     fri_config: The FRI parameters of node circuits.
+///     leaf_chunk_size: The number of leaves covered by each base proof, a power of two. Pairs of leaves
+///         are proved with `PairwiseHash`, larger chunks with `ChunkHash`.
+///     memory_budget: An optional bound, in bytes, on the memory held by live proofs
+///         (see `ProofScheduler::with_memory_budget`).
+///     progress_observer: An optional observer notified after each node proof.
+///     cancellation_token: An optional token aborting the proof once cancelled.
+///     checkpoint_directory: An optional directory completed node proofs are checkpointed to
+///         (see `Checkpoint`).
+///     resume: Whether to resume from the node proofs of `checkpoint_directory`, instead of
+///         discarding them.
+///     compress: Whether to wrap the root proof with the default `ProofCompressor`, to minimize
+///         its size.
+///
+/// Description:
+///
+///     The default options reproduce the behavior of `MerkleTree::proof`, that is, zero knowledge
+///     circuits with the FRI parameters of `CircuitConfig::standard_recursion_zk_config`, pairwise base
+///     proofs, scheduled on the global rayon thread pool.
+#[derive(Clone, Debug)]
+pub struct ProvingOptions {
+    pub backend: ProvingBackend,
+    pub num_threads: Option<usize>,
+    pub zero_knowledge: bool,
+    pub fri_config: FriConfig,
+    pub leaf_chunk_size: usize,
+    pub memory_budget: Option<usize>,
+    pub progress_observer: Option<Arc<dyn ProgressObserver>>,
+    pub cancellation_token: Option<CancellationToken>,
+    pub checkpoint_directory: Option<PathBuf>,
+    pub resume: bool,
+    pub compress: bool,
+}
+
+impl Default for ProvingOptions {
+    fn default() -> Self {
+        let circuit_config = CircuitConfig::standard_recursion_zk_config();
+        Self {
+            backend: ProvingBackend::Recursive,
+            num_threads: None,
+            zero_knowledge: circuit_config.zero_knowledge,
+            fri_config: circuit_config.fri_config,
+            leaf_chunk_size: 2,
+            memory_budget: None,
+            progress_observer: None,
+            cancellation_token: None,
+            checkpoint_directory: None,
+            resume: false,
+            compress: false,
+        }
+    }
+}
+
+impl ProvingOptions {
+    /// Method `circuit_config`:
+    ///
+    ///     Returns the circuit configuration node circuits should be built with.
+    pub fn circuit_config(&self) -> CircuitConfig {
+        CircuitConfig {
+            zero_knowledge: self.zero_knowledge,
+            fri_config: self.fri_config.clone(),
+            ..CircuitConfig::standard_recursion_config()
+        }
+    }
+
+    /// Method `scheduler`:
+    ///
+    ///     Returns the `ProofScheduler` node proofs should be scheduled on.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the ProofScheduler or an Error if the thread pool can not be built.
+    pub fn scheduler(&self) -> Result<ProofScheduler, Error> {
+        let scheduler = match self.num_threads {
+            Some(num_threads) => ProofScheduler::with_num_threads(num_threads)?,
+            None => ProofScheduler::default(),
+        };
+        let scheduler = match self.memory_budget {
+            Some(memory_budget) => scheduler.with_memory_budget(memory_budget),
+            None => scheduler,
+        };
+        let scheduler = match &self.progress_observer {
+            Some(progress_observer) => scheduler.with_progress_observer(progress_observer.clone()),
+            None => scheduler,
+        };
+        Ok(match &self.cancellation_token {
+            Some(cancellation_token) => {
+                scheduler.with_cancellation_token(cancellation_token.clone())
+            }
+            None => scheduler,
+        })
+    }
+
+    /// Method `validate`:
+    ///
+    ///     Checks that the options can be used to prove a tree with `num_leaves` leaves.
+    pub fn validate(&self, num_leaves: usize) -> Result<(), Error> {
+        ensure!(
+            self.num_threads != Some(0),
+            "the number of threads should be positive"
+        );
+        let circuit_config = self.circuit_config();
+        let fri_security_bits = self.fri_config.num_query_rounds * self.fri_config.rate_bits
+            + self.fri_config.proof_of_work_bits as usize;
+        ensure!(
+            fri_security_bits >= circuit_config.security_bits,
+            "FRI parameters provide {} bits of security, {} are required",
+            fri_security_bits,
+            circuit_config.security_bits
+        );
+        ensure!(
+            self.leaf_chunk_size.is_power_of_two() && self.leaf_chunk_size > 1,
+            "the leaf chunk size should be a power of two greater than one"
+        );
+        ensure!(
+            self.leaf_chunk_size <= num_leaves,
+            "the leaf chunk size should not exceed the number of leaves"
+        );
+        ensure!(
+            !self.resume || self.checkpoint_directory.is_some(),
+            "resuming requires a checkpoint directory"
+        );
+        ensure!(
+            self.backend == ProvingBackend::Recursive || self.checkpoint_directory.is_none(),
+            "{:?} proofs can not be checkpointed",
+            self.backend
+        );
+        ensure!(
+            self.backend != ProvingBackend::Uniform || !self.zero_knowledge,
+            "cyclic recursion does not support zero knowledge circuits"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::{merkle_tree::MerkleTree, F};
+
+    #[test]
+    fn test_default_proving_options() {
+        let proving_options = ProvingOptions::default();
+        assert_eq!(
+            proving_options.circuit_config(),
+            CircuitConfig::standard_recursion_zk_config()
+        );
+        assert!(proving_options.validate(4).is_ok());
+        assert!(proving_options.scheduler().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_proving_options() {
+        let proving_options = ProvingOptions {
+            num_threads: Some(0),
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+
+        let proving_options = ProvingOptions {
+            leaf_chunk_size: 3,
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+
+        let proving_options = ProvingOptions {
+            leaf_chunk_size: 8,
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+
+        let proving_options = ProvingOptions {
+            fri_config: FriConfig {
+                num_query_rounds: 20,
+                ..CircuitConfig::standard_recursion_config().fri_config
+            },
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+
+        let proving_options = ProvingOptions {
+            resume: true,
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+
+        let proving_options = ProvingOptions {
+            backend: ProvingBackend::Monolithic,
+            checkpoint_directory: Some(PathBuf::from("checkpoints")),
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+
+        let proving_options = ProvingOptions {
+            backend: ProvingBackend::Uniform,
+            ..Default::default()
+        };
+        assert!(proving_options.validate(4).is_err());
+    }
+
+    #[test]
+    fn test_merkle_tree_proof_with_leaf_chunks() {
+        let merkle_tree_leaves: Vec<Vec<F>> =
+            (0..8).map(|i| vec![F::from_canonical_u64(i)]).collect();
+
+        for leaf_chunk_size in [4, 8] {
+            let proving_options = ProvingOptions {
+                leaf_chunk_size,
+                ..Default::default()
+            };
+            let merkle_tree = MerkleTree::create(merkle_tree_leaves.clone());
+            let root = merkle_tree.root();
+
+            let proof_data = merkle_tree.proof_with_options(&proving_options).unwrap();
+            assert_eq!(proof_data.root(), root);
+            assert_eq!(proof_data.height(), 3);
+            assert_eq!(proof_data.leaf_range(), 0..8);
+            assert!(proof_data
+                .circuit_data
+                .verify(proof_data.proof_with_pis)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_proof_with_options() {
+        let proving_options = ProvingOptions {
+            num_threads: Some(2),
+            zero_knowledge: false,
+            fri_config: FriConfig {
+                rate_bits: 4,
+                num_query_rounds: 21,
+                ..CircuitConfig::standard_recursion_config().fri_config
+            },
+            ..Default::default()
+        };
+
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        let proof_data = merkle_tree.proof_with_options(&proving_options).unwrap();
+        assert_eq!(
+            proof_data.circuit_data.common.config,
+            proving_options.circuit_config()
+        );
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+}
@@ -0,0 +1,315 @@
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyBytes, PyModule},
+};
+
+use plonky2::{field::types::PrimeField64, hash::hash_types::HashOut};
+
+use crate::{
+    inclusion_proof::InclusionProof, leaf_format::canonical_element, merkle_tree::MerkleTree,
+    proving_options::ProvingOptions, verifier, F,
+};
+
+fn value_error(error: anyhow::Error) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+fn elements(values: &[u64]) -> PyResult<Vec<F>> {
+    values
+        .iter()
+        .map(|value| canonical_element(*value).map_err(value_error))
+        .collect()
+}
+
+fn hash(elements: [u64; 4]) -> PyResult<HashOut<F>> {
+    Ok(HashOut::from_partial(&self::elements(&elements)?))
+}
+
+fn hash_elements(hash: HashOut<F>) -> [u64; 4] {
+    hash.elements.map(|element| element.to_canonical_u64())
+}
+
+/// `PyMerkleTree` struct:
+///     The Python class `MerkleTree`, wrapping a `MerkleTree` built from leaves given as lists of
+///     canonical field elements (Python integers).
+#[pyclass(name = "MerkleTree", module = "recursive_merkle_tree_proofs", frozen)]
+pub struct PyMerkleTree {
+    merkle_tree: MerkleTree,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    /// Builds the tree with `MerkleTree::create`, the number of leaves being a power of two greater
+    /// than one.
+    #[new]
+    fn new(leaves: Vec<Vec<u64>>) -> PyResult<Self> {
+        if !leaves.len().is_power_of_two() || leaves.len() < 2 {
+            return Err(PyValueError::new_err(format!(
+                "the number of leaves should be a power of two, greater than one, got {}",
+                leaves.len()
+            )));
+        }
+        let leaves = leaves
+            .iter()
+            .map(|leaf| elements(leaf))
+            .collect::<PyResult<_>>()?;
+        Ok(Self {
+            merkle_tree: MerkleTree::create(leaves),
+        })
+    }
+
+    /// The number of leaves of the tree.
+    #[getter]
+    fn num_leaves(&self) -> usize {
+        self.merkle_tree.leaves.len()
+    }
+
+    /// The 4 elements of the tree root.
+    #[getter]
+    fn root(&self) -> [u64; 4] {
+        hash_elements(self.merkle_tree.root())
+    }
+
+    /// Returns the `InclusionProof` of the leaf `leaf_index`.
+    fn inclusion_proof(&self, leaf_index: usize) -> PyResult<PyInclusionProof> {
+        let inclusion_proof = self
+            .merkle_tree
+            .inclusion_proof(leaf_index)
+            .map_err(value_error)?;
+        Ok(PyInclusionProof {
+            leaf_index: inclusion_proof.leaf_index,
+            leaf: inclusion_proof
+                .leaf
+                .iter()
+                .map(|element| element.to_canonical_u64())
+                .collect(),
+            siblings: inclusion_proof
+                .siblings
+                .into_iter()
+                .map(hash_elements)
+                .collect(),
+        })
+    }
+
+    /// Proves the tree root with `Provable::proof`, on `num_threads` threads (all available threads
+    /// by default), releasing the GIL meanwhile.
+    #[pyo3(signature = (num_threads = None))]
+    fn prove(&self, py: Python<'_>, num_threads: Option<usize>) -> PyResult<PyRootProof> {
+        let merkle_tree = self.merkle_tree.clone();
+        py.allow_threads(|| {
+            let proving_options = ProvingOptions {
+                num_threads,
+                ..ProvingOptions::default()
+            };
+            let proof_data = merkle_tree.proof_with_options(&proving_options)?;
+            Ok(PyRootProof {
+                proof: proof_data.proof_to_bytes(),
+                verifier_key: proof_data.verifier_key_to_bytes()?,
+            })
+        })
+        .map_err(value_error)
+    }
+}
+
+/// `PyInclusionProof` struct:
+///     The Python class `InclusionProof`: the authentication path of a leaf, whose siblings are
+///     lists of 4 elements, from the leaf level up.
+#[pyclass(
+    name = "InclusionProof",
+    module = "recursive_merkle_tree_proofs",
+    get_all
+)]
+pub struct PyInclusionProof {
+    leaf_index: usize,
+    leaf: Vec<u64>,
+    siblings: Vec<[u64; 4]>,
+}
+
+#[pymethods]
+impl PyInclusionProof {
+    #[new]
+    fn new(leaf_index: usize, leaf: Vec<u64>, siblings: Vec<[u64; 4]>) -> Self {
+        Self {
+            leaf_index,
+            leaf,
+            siblings,
+        }
+    }
+
+    /// Returns whether the path leads to `root`, with `verifier::verify_inclusion_path`.
+    fn verify(&self, root: [u64; 4]) -> PyResult<bool> {
+        let inclusion_proof = InclusionProof {
+            leaf_index: self.leaf_index,
+            leaf: elements(&self.leaf)?,
+            siblings: self
+                .siblings
+                .iter()
+                .map(|sibling| hash(*sibling))
+                .collect::<PyResult<_>>()?,
+        };
+        Ok(verifier::verify_inclusion_path(
+            &inclusion_proof.leaf,
+            inclusion_proof.leaf_index,
+            &inclusion_proof.siblings,
+            hash(root)?,
+        )
+        .is_ok())
+    }
+}
+
+/// `PyRootProof` struct:
+///     The Python class `RootProof`: a root proof and its verifier key, serialized with
+///     `ProofData::proof_to_bytes` and `ProofData::verifier_key_to_bytes`.
+#[pyclass(name = "RootProof", module = "recursive_merkle_tree_proofs", frozen)]
+pub struct PyRootProof {
+    proof: Vec<u8>,
+    verifier_key: Vec<u8>,
+}
+
+#[pymethods]
+impl PyRootProof {
+    #[new]
+    fn new(proof: Vec<u8>, verifier_key: Vec<u8>) -> Self {
+        Self {
+            proof,
+            verifier_key,
+        }
+    }
+
+    /// The serialized proof.
+    #[getter]
+    fn proof<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.proof)
+    }
+
+    /// The serialized verifier key.
+    #[getter]
+    fn verifier_key<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.verifier_key)
+    }
+
+    /// Verifies the proof against `root` and the pinned `verifier_key_digest` (see
+    /// `root_verifier_key_digest`) with `verifier::verify_root_proof`, and returns the number of leaves
+    /// of the tree, or raises a `ValueError`.
+    fn verify(
+        &self,
+        py: Python<'_>,
+        root: [u64; 4],
+        verifier_key_digest: [u64; 4],
+    ) -> PyResult<usize> {
+        let root = hash(root)?;
+        let verifier_key_digest = hash(verifier_key_digest)?;
+        py.allow_threads(|| {
+            verifier::verify_root_proof(&self.proof, &self.verifier_key, root, verifier_key_digest)
+        })
+        .map_err(value_error)
+    }
+}
+
+/// Function `root_verifier_key_digest`:
+///
+///     Returns the digest of the verifier key of the root proofs of trees of `num_leaves` leaves of
+///     `leaf_len` elements, proved by `MerkleTree.prove`, with `MerkleTree::root_verifier_key_digest`.
+#[pyfunction]
+fn root_verifier_key_digest(
+    py: Python<'_>,
+    num_leaves: usize,
+    leaf_len: usize,
+) -> PyResult<[u64; 4]> {
+    py.allow_threads(|| {
+        MerkleTree::root_verifier_key_digest(num_leaves, leaf_len, &ProvingOptions::default())
+    })
+    .map(hash_elements)
+    .map_err(value_error)
+}
+
+/// Function `recursive_merkle_tree_proofs`:
+///
+///     The Python module, exposing the `MerkleTree`, `InclusionProof` and `RootProof` classes, and the
+///     `root_verifier_key_digest` function.
+#[pymodule]
+pub fn recursive_merkle_tree_proofs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyMerkleTree>()?;
+    module.add_class::<PyInclusionProof>()?;
+    module.add_class::<PyRootProof>()?;
+    module.add_function(wrap_pyfunction!(root_verifier_key_digest, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::*;
+
+    fn run_python(code: &str) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "recursive_merkle_tree_proofs").unwrap();
+            recursive_merkle_tree_proofs(&module).unwrap();
+            py.import("sys")
+                .unwrap()
+                .getattr("modules")
+                .unwrap()
+                .set_item("recursive_merkle_tree_proofs", module)
+                .unwrap();
+            let code = CString::new(code).unwrap();
+            if let Err(e) = py.run(&code, None, None) {
+                e.print(py);
+                panic!("the Python code failed");
+            }
+        });
+    }
+
+    #[test]
+    fn test_python_merkle_tree() {
+        run_python(
+            r#"
+from recursive_merkle_tree_proofs import InclusionProof, MerkleTree
+
+tree = MerkleTree([[i, 2 * i] for i in range(8)])
+assert tree.num_leaves == 8
+assert len(tree.root) == 4
+
+path = tree.inclusion_proof(5)
+assert path.leaf == [5, 10] and len(path.siblings) == 3
+assert path.verify(tree.root)
+assert not InclusionProof(4, path.leaf, path.siblings).verify(tree.root)
+
+for leaves in ([[0], [1], [2]], [[2**64 - 2**32 + 1], [0]]):
+    try:
+        MerkleTree(leaves)
+        raise AssertionError("invalid leaves should be rejected")
+    except ValueError:
+        pass
+"#,
+        );
+    }
+
+    #[test]
+    fn test_python_root_proof() {
+        run_python(
+            r#"
+from recursive_merkle_tree_proofs import MerkleTree, RootProof, root_verifier_key_digest
+
+tree = MerkleTree([[i] for i in range(4)])
+proof = tree.prove()
+digest = root_verifier_key_digest(4, 1)
+assert proof.verify(tree.root, digest) == 4
+
+copied_proof = RootProof(proof.proof, proof.verifier_key)
+assert copied_proof.verify(tree.root, digest) == 4
+for root, digest, message in (
+    ([0, 0, 0, 0], digest, "root"),
+    (tree.root, root_verifier_key_digest(8, 1), "verifier key"),
+):
+    try:
+        copied_proof.verify(root, digest)
+        raise AssertionError("a wrong root or verifier key should be rejected")
+    except ValueError as e:
+        assert message in str(e)
+"#,
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use anyhow::ensure;
 use plonky2::{
+    field::types::Field,
     hash::{
         hash_types::{HashOut, HashOutTarget},
         poseidon::PoseidonHash,
@@ -6,7 +8,7 @@ use plonky2::{
     iop::witness::{PartialWitness, WitnessWrite},
     plonk::{
         circuit_builder::CircuitBuilder,
-        circuit_data::{CircuitConfig, VerifierCircuitTarget},
+        circuit_data::{CircuitConfig, CircuitData},
         config::{Hasher, PoseidonGoldilocksConfig},
         proof::ProofWithPublicInputsTarget,
     },
@@ -14,6 +16,10 @@ use plonky2::{
 
 use crate::{
     circuit_compiler::{CircuitCompiler, EvaluateFillCircuit, ProofData},
+    pairwise_hash::{
+        HEIGHT_PUBLIC_INPUT_INDEX, INDEX_PUBLIC_INPUT_INDEX, NUM_LEAVES_PUBLIC_INPUT_INDEX,
+        NUM_NODE_PUBLIC_INPUTS,
+    },
     provable::Provable,
     C, D, F,
 };
@@ -33,6 +39,7 @@ pub struct RecursivePairwiseHash<'a> {
     pub(crate) left_recursive_hash: RecursiveHash<'a>,
     pub(crate) right_recursive_hash: RecursiveHash<'a>,
     pub(crate) parent_hash: HashOut<F>,
+    pub(crate) circuit_config: CircuitConfig,
 }
 
 impl<'a> RecursivePairwiseHash<'a> {
@@ -51,108 +58,148 @@ impl<'a> RecursivePairwiseHash<'a> {
             left_recursive_hash,
             right_recursive_hash,
             parent_hash,
+            circuit_config: CircuitConfig::standard_recursion_zk_config(),
         }
     }
+
+    /// Method `with_circuit_config`:
+    ///
+    ///     Sets the configuration the circuit is built with (`standard_recursion_zk_config` by default).
+    pub fn with_circuit_config(mut self, circuit_config: CircuitConfig) -> Self {
+        self.circuit_config = circuit_config;
+        self
+    }
 }
 
-impl<'a> CircuitCompiler<C, F, D> for RecursivePairwiseHash<'a> {
-    type Targets = (
-        HashOutTarget,
-        HashOutTarget,
-        ProofWithPublicInputsTarget<D>,
-        VerifierCircuitTarget,
-        ProofWithPublicInputsTarget<D>,
-        VerifierCircuitTarget,
+/// Targets of a recursive node circuit: the left and right child hashes, followed by the left and
+/// right child proofs.
+pub(crate) type RecursiveNodeTargets = (
+    HashOutTarget,
+    HashOutTarget,
+    ProofWithPublicInputsTarget<D>,
+    ProofWithPublicInputsTarget<D>,
+);
+
+/// Builds the circuit of a recursive node over children proved with the circuits of
+/// `left_circuit_data` and `right_circuit_data`. The verifier data of the child circuits are constants
+/// of the node circuit, so that proofs of any other circuit are rejected as children, and the circuit
+/// of a root proof pins the circuits of every node below it.
+///
+/// Panics if a child circuit does not expose the public inputs of a node proof.
+pub(crate) fn compile_recursive_node(
+    circuit_config: &CircuitConfig,
+    left_circuit_data: &CircuitData<F, C, D>,
+    right_circuit_data: &CircuitData<F, C, D>,
+) -> (CircuitBuilder<F, D>, RecursiveNodeTargets, HashOutTarget) {
+    // the child hash is carried by the first four public inputs, followed by the subtree height,
+    // number of leaves and index. Specialized circuits (e.g. sorted trees) may append further public
+    // inputs after them
+    assert!(
+        left_circuit_data.common.num_public_inputs >= NUM_NODE_PUBLIC_INPUTS
+            && right_circuit_data.common.num_public_inputs >= NUM_NODE_PUBLIC_INPUTS,
+        "child proofs should expose the {} public inputs of a node proof",
+        NUM_NODE_PUBLIC_INPUTS
     );
-    type OutTargets = HashOutTarget;
 
-    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
-        let mut circuit_builder =
-            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
-        let left_hash_targets = circuit_builder.add_virtual_hash();
-        let right_hash_targets = circuit_builder.add_virtual_hash();
+    let mut circuit_builder = CircuitBuilder::<F, D>::new(circuit_config.clone());
+    let left_hash_targets = circuit_builder.add_virtual_hash();
+    let right_hash_targets = circuit_builder.add_virtual_hash();
 
-        let parent_hash_targets = circuit_builder.add_virtual_hash();
-        circuit_builder.register_public_inputs(&parent_hash_targets.elements);
+    let parent_hash_targets = circuit_builder.add_virtual_hash();
+    circuit_builder.register_public_inputs(&parent_hash_targets.elements);
 
-        let should_be_parent_hash_targets = circuit_builder.hash_or_noop::<PoseidonHash>(
-            [left_hash_targets.elements, right_hash_targets.elements].concat(),
-        );
+    let should_be_parent_hash_targets = circuit_builder.hash_or_noop::<PoseidonHash>(
+        [left_hash_targets.elements, right_hash_targets.elements].concat(),
+    );
 
-        circuit_builder.connect_hashes(should_be_parent_hash_targets, parent_hash_targets);
-
-        // add targets for recursion
-        let left_proof_with_pis_targets = circuit_builder
-            .add_virtual_proof_with_pis(&self.left_recursive_hash.proof_data.circuit_data.common);
-        let left_verifier_data_targets = circuit_builder.add_virtual_verifier_data(
-            self.left_recursive_hash
-                .proof_data
-                .circuit_data
-                .common
-                .config
-                .fri_config
-                .cap_height,
-        );
+    circuit_builder.connect_hashes(should_be_parent_hash_targets, parent_hash_targets);
 
-        circuit_builder.verify_proof::<PoseidonGoldilocksConfig>(
-            &left_proof_with_pis_targets,
-            &left_verifier_data_targets,
-            &self.left_recursive_hash.proof_data.circuit_data.common,
-        );
+    // add targets for recursion, the child circuits are fixed, so that their verifier data are
+    // constants of the node circuit
+    let left_proof_with_pis_targets =
+        circuit_builder.add_virtual_proof_with_pis(&left_circuit_data.common);
+    let left_verifier_data_targets =
+        circuit_builder.constant_verifier_data::<C>(&left_circuit_data.verifier_only);
 
-        let right_proof_with_pis_targets = circuit_builder
-            .add_virtual_proof_with_pis(&self.right_recursive_hash.proof_data.circuit_data.common);
-        let right_verifier_data_targets = circuit_builder.add_virtual_verifier_data(
-            self.right_recursive_hash
-                .proof_data
-                .circuit_data
-                .common
-                .config
-                .fri_config
-                .cap_height,
-        );
+    circuit_builder.verify_proof::<PoseidonGoldilocksConfig>(
+        &left_proof_with_pis_targets,
+        &left_verifier_data_targets,
+        &left_circuit_data.common,
+    );
 
-        circuit_builder.verify_proof::<PoseidonGoldilocksConfig>(
-            &right_proof_with_pis_targets,
-            &right_verifier_data_targets,
-            &self.right_recursive_hash.proof_data.circuit_data.common,
-        );
+    let right_proof_with_pis_targets =
+        circuit_builder.add_virtual_proof_with_pis(&right_circuit_data.common);
+    let right_verifier_data_targets =
+        circuit_builder.constant_verifier_data::<C>(&right_circuit_data.verifier_only);
 
-        // we need to enforce that the public inputs of `proof_with_pis_targets` do agree
-        // with the child hash targets
-        let true_bool_target = circuit_builder._true();
-        let false_bool_target = circuit_builder._false();
-        if left_proof_with_pis_targets.public_inputs.len() != 4 {
-            circuit_builder.connect(true_bool_target.target, false_bool_target.target);
-        }
-        (0..4).for_each(|i| {
-            circuit_builder.connect(
-                left_proof_with_pis_targets.public_inputs[i],
-                left_hash_targets.elements[i],
-            )
-        });
+    circuit_builder.verify_proof::<PoseidonGoldilocksConfig>(
+        &right_proof_with_pis_targets,
+        &right_verifier_data_targets,
+        &right_circuit_data.common,
+    );
 
-        if right_proof_with_pis_targets.public_inputs.len() != 4 {
-            circuit_builder.connect(true_bool_target.target, false_bool_target.target);
-        }
-        (0..4).for_each(|i| {
-            circuit_builder.connect(
-                right_proof_with_pis_targets.public_inputs[i],
-                right_hash_targets.elements[i],
-            )
-        });
+    // we need to enforce that the public inputs of `proof_with_pis_targets` do agree
+    // with the child hash targets
+    (0..4).for_each(|i| {
+        circuit_builder.connect(
+            left_proof_with_pis_targets.public_inputs[i],
+            left_hash_targets.elements[i],
+        )
+    });
+
+    (0..4).for_each(|i| {
+        circuit_builder.connect(
+            right_proof_with_pis_targets.public_inputs[i],
+            right_hash_targets.elements[i],
+        )
+    });
+
+    // both subtrees should have the same height, so that the tree stays perfect, and the parent
+    // subtree is one level higher and covers the leaves of both
+    let left_height_target = left_proof_with_pis_targets.public_inputs[HEIGHT_PUBLIC_INPUT_INDEX];
+    let right_height_target = right_proof_with_pis_targets.public_inputs[HEIGHT_PUBLIC_INPUT_INDEX];
+    circuit_builder.connect(left_height_target, right_height_target);
+    let parent_height_target = circuit_builder.add_const(left_height_target, F::ONE);
+    let parent_num_leaves_target = circuit_builder.add(
+        left_proof_with_pis_targets.public_inputs[NUM_LEAVES_PUBLIC_INPUT_INDEX],
+        right_proof_with_pis_targets.public_inputs[NUM_LEAVES_PUBLIC_INPUT_INDEX],
+    );
 
+    // the children should be the adjacent nodes `2 * index` and `2 * index + 1` of their level,
+    // so that, from a root at index 0, every node proof covers its exact range of leaves
+    let left_index_target = left_proof_with_pis_targets.public_inputs[INDEX_PUBLIC_INPUT_INDEX];
+    let should_be_right_index_target = circuit_builder.add_const(left_index_target, F::ONE);
+    circuit_builder.connect(
+        should_be_right_index_target,
+        right_proof_with_pis_targets.public_inputs[INDEX_PUBLIC_INPUT_INDEX],
+    );
+    let parent_index_target = circuit_builder.mul_const(F::TWO.inverse(), left_index_target);
+
+    circuit_builder.register_public_input(parent_height_target);
+    circuit_builder.register_public_input(parent_num_leaves_target);
+    circuit_builder.register_public_input(parent_index_target);
+
+    (
+        circuit_builder,
         (
-            circuit_builder,
-            (
-                left_hash_targets,
-                right_hash_targets,
-                left_proof_with_pis_targets,
-                left_verifier_data_targets,
-                right_proof_with_pis_targets,
-                right_verifier_data_targets,
-            ),
-            parent_hash_targets,
+            left_hash_targets,
+            right_hash_targets,
+            left_proof_with_pis_targets,
+            right_proof_with_pis_targets,
+        ),
+        parent_hash_targets,
+    )
+}
+
+impl<'a> CircuitCompiler<C, F, D> for RecursivePairwiseHash<'a> {
+    type Targets = RecursiveNodeTargets;
+    type OutTargets = HashOutTarget;
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        compile_recursive_node(
+            &self.circuit_config,
+            &self.left_recursive_hash.proof_data.circuit_data,
+            &self.right_recursive_hash.proof_data.circuit_data,
         )
     }
 }
@@ -174,9 +221,7 @@ impl<'a> EvaluateFillCircuit<C, F, D> for RecursivePairwiseHash<'a> {
             left_hash_targets,
             right_hash_targets,
             left_proof_with_pis_targets,
-            left_verifier_data_targets,
             right_proof_with_pis_targets,
-            right_verifier_data_targets,
         ) = targets;
 
         partial_witness.set_hash_target(left_hash_targets, self.left_recursive_hash.hash);
@@ -187,27 +232,11 @@ impl<'a> EvaluateFillCircuit<C, F, D> for RecursivePairwiseHash<'a> {
             &left_proof_with_pis_targets,
             &self.left_recursive_hash.proof_data.proof_with_pis,
         );
-        partial_witness.set_verifier_data_target(
-            &left_verifier_data_targets,
-            &self
-                .left_recursive_hash
-                .proof_data
-                .circuit_data
-                .verifier_only,
-        );
 
         partial_witness.set_proof_with_pis_target(
             &right_proof_with_pis_targets,
             &self.right_recursive_hash.proof_data.proof_with_pis,
         );
-        partial_witness.set_verifier_data_target(
-            &right_verifier_data_targets,
-            &self
-                .right_recursive_hash
-                .proof_data
-                .circuit_data
-                .verifier_only,
-        );
 
         Ok(partial_witness)
     }
@@ -215,6 +244,18 @@ impl<'a> EvaluateFillCircuit<C, F, D> for RecursivePairwiseHash<'a> {
 
 impl<'a> Provable<F, C, D> for RecursivePairwiseHash<'a> {
     fn proof(self) -> Result<ProofData<F, C, D>, anyhow::Error> {
+        ensure!(
+            [&self.left_recursive_hash, &self.right_recursive_hash]
+                .iter()
+                .all(|recursive_hash| recursive_hash
+                    .proof_data
+                    .circuit_data
+                    .common
+                    .num_public_inputs
+                    >= NUM_NODE_PUBLIC_INPUTS),
+            "child proofs should expose the {} public inputs of a node proof",
+            NUM_NODE_PUBLIC_INPUTS
+        );
         let (circuit_builder, targets, out_targets) = self.compile();
         let partial_witness = self.fill(targets, out_targets)?;
 
@@ -230,57 +271,61 @@ impl<'a> Provable<F, C, D> for RecursivePairwiseHash<'a> {
 
 #[cfg(test)]
 mod tests {
-    use plonky2::field::types::Field;
-
     use super::*;
+    use crate::{
+        chunk_hash::ChunkHash,
+        merkle_tree::MerkleTree,
+        pairwise_hash::{register_base_node_public_inputs, PairwiseHash},
+        proving_options::ProvingOptions,
+    };
+
+    fn leaf_hash(leaf: u64) -> HashOut<F> {
+        PoseidonHash::hash_or_noop(&[F::from_canonical_u64(leaf)])
+    }
 
-    #[test]
-    fn test_recursive_pairwise_hash() {
-        let left_hash = PoseidonHash::hash_or_noop(&[F::ZERO]);
-        let right_hash = PoseidonHash::hash_or_noop(&[F::ONE]);
+    /// Proves the parent of the leaves `2 * index` and `2 * index + 1`, whose values are their index.
+    fn pairwise_proof_data(index: usize) -> (HashOut<F>, ProofData<F, C, D>) {
+        let left_leaf = 2 * index as u64;
+        let pairwise_hash = PairwiseHash::new(
+            vec![F::from_canonical_u64(left_leaf)],
+            leaf_hash(left_leaf),
+            vec![F::from_canonical_u64(left_leaf + 1)],
+            leaf_hash(left_leaf + 1),
+        )
+        .with_index(index);
+        (pairwise_hash.evaluate(), pairwise_hash.proof().unwrap())
+    }
 
+    /// Proves a node of a foreign circuit, which exposes the public inputs of a node proof without
+    /// hashing anything.
+    fn foreign_proof_data(hash: HashOut<F>, height: usize, index: usize) -> ProofData<F, C, D> {
         let mut circuit_builder =
-            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
         let mut partial_witness = PartialWitness::<F>::new();
 
-        let left_hash_targets = circuit_builder.add_virtual_hash();
-        circuit_builder.register_public_inputs(&left_hash_targets.elements);
-        partial_witness.set_hash_target(left_hash_targets, left_hash);
+        let hash_targets = circuit_builder.add_virtual_hash();
+        circuit_builder.register_public_inputs(&hash_targets.elements);
+        let index_target = register_base_node_public_inputs(&mut circuit_builder, height);
+        partial_witness.set_hash_target(hash_targets, hash);
+        partial_witness.set_target(index_target, F::from_canonical_usize(index));
 
         let circuit_data = circuit_builder.build::<C>();
-        let proof_with_pis = circuit_data
-            .prove(partial_witness)
-            .expect("Failed to prove left hash");
-
-        let left_proof_data = ProofData {
-            circuit_data,
+        let proof_with_pis = circuit_data.prove(partial_witness).unwrap();
+        ProofData {
             proof_with_pis,
-        };
-
-        let left_recursive_hash = RecursiveHash::new(left_hash, &left_proof_data);
-
-        let mut circuit_builder =
-            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
-        let mut partial_witness = PartialWitness::<F>::new();
-
-        let right_hash_targets = circuit_builder.add_virtual_hash();
-        circuit_builder.register_public_inputs(&right_hash_targets.elements);
-        partial_witness.set_hash_target(right_hash_targets, right_hash);
-
-        let circuit_data = circuit_builder.build::<C>();
-        let proof_with_pis = circuit_data
-            .prove(partial_witness)
-            .expect("Failed to prove left hash");
-
-        let right_proof_data = ProofData {
             circuit_data,
-            proof_with_pis,
-        };
+        }
+    }
 
-        let right_recursive_hash = RecursiveHash::new(right_hash, &right_proof_data);
+    #[test]
+    fn test_recursive_pairwise_hash() {
+        let (left_hash, left_proof_data) = pairwise_proof_data(0);
+        let (right_hash, right_proof_data) = pairwise_proof_data(1);
 
-        let recursive_pairwise_hash =
-            RecursivePairwiseHash::new(left_recursive_hash, right_recursive_hash);
+        let recursive_pairwise_hash = RecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        );
 
         assert!(recursive_pairwise_hash.prove_and_verify().is_ok());
     }
@@ -288,54 +333,107 @@ mod tests {
     #[test]
     #[should_panic]
     fn test_recursive_pairwise_hash_fails_if_hash_is_invalid() {
-        let left_hash = PoseidonHash::hash_or_noop(&[F::ZERO]);
-        let right_hash = PoseidonHash::hash_or_noop(&[F::ONE]);
+        let (left_hash, left_proof_data) = pairwise_proof_data(0);
+        let (right_hash, right_proof_data) = pairwise_proof_data(1);
 
-        let mut circuit_builder =
-            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
-        let mut partial_witness = PartialWitness::<F>::new();
-
-        let left_hash_targets = circuit_builder.add_virtual_hash();
-        circuit_builder.register_public_inputs(&left_hash_targets.elements);
-        partial_witness.set_hash_target(left_hash_targets, left_hash);
-
-        let circuit_data = circuit_builder.build::<C>();
-        let proof_with_pis = circuit_data
-            .prove(partial_witness)
-            .expect("Failed to prove left hash");
-
-        let left_proof_data = ProofData {
-            circuit_data,
-            proof_with_pis,
-        };
-
-        let left_recursive_hash = RecursiveHash::new(left_hash, &left_proof_data);
-
-        let mut circuit_builder =
-            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
-        let mut partial_witness = PartialWitness::<F>::new();
+        let mut recursive_pairwise_hash = RecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        );
 
-        let right_hash_targets = circuit_builder.add_virtual_hash();
-        circuit_builder.register_public_inputs(&right_hash_targets.elements);
-        partial_witness.set_hash_target(right_hash_targets, right_hash);
+        recursive_pairwise_hash.left_recursive_hash.hash =
+            PoseidonHash::hash_or_noop(&[F::from_canonical_u8(255)]);
+        assert!(recursive_pairwise_hash.prove_and_verify().is_err());
+    }
 
-        let circuit_data = circuit_builder.build::<C>();
-        let proof_with_pis = circuit_data
-            .prove(partial_witness)
-            .expect("Failed to prove left hash");
+    #[test]
+    fn test_recursive_pairwise_hash_commits_to_node_position() {
+        // the parent of the nodes 2 and 3 of height 1 covers the leaves 4..8 of an 8 leaf tree
+        let (left_hash, left_proof_data) = pairwise_proof_data(2);
+        let (right_hash, right_proof_data) = pairwise_proof_data(3);
+
+        let proof_data = RecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        )
+        .proof()
+        .unwrap();
+        assert_eq!(proof_data.height(), 2);
+        assert_eq!(proof_data.num_leaves(), 4);
+        assert_eq!(proof_data.index(), 1);
+        assert_eq!(proof_data.leaf_range(), 4..8);
+    }
 
-        let right_proof_data = ProofData {
-            circuit_data,
-            proof_with_pis,
-        };
+    #[test]
+    #[should_panic]
+    fn test_recursive_pairwise_hash_fails_for_unbalanced_subtrees() {
+        let (left_hash, left_proof_data) = pairwise_proof_data(0);
+
+        // a subtree of height 2, next to a subtree of height 1
+        let leaves = (4..8)
+            .map(|leaf| vec![F::from_canonical_u64(leaf)])
+            .collect();
+        let leaves_hashes = (4..8).map(leaf_hash).collect();
+        let chunk_hash = ChunkHash::new(leaves, leaves_hashes).with_index(1);
+        let right_hash = chunk_hash.evaluate();
+        let right_proof_data = chunk_hash.proof().unwrap();
+
+        let recursive_pairwise_hash = RecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        );
+        assert!(recursive_pairwise_hash.prove_and_verify().is_err());
+    }
 
-        let right_recursive_hash = RecursiveHash::new(right_hash, &right_proof_data);
+    #[test]
+    #[should_panic]
+    fn test_recursive_pairwise_hash_fails_for_non_adjacent_subtrees() {
+        // swapped children
+        let (left_hash, left_proof_data) = pairwise_proof_data(1);
+        let (right_hash, right_proof_data) = pairwise_proof_data(0);
+
+        let recursive_pairwise_hash = RecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        );
+        assert!(recursive_pairwise_hash.prove_and_verify().is_err());
+    }
 
-        let mut recursive_pairwise_hash =
-            RecursivePairwiseHash::new(left_recursive_hash, right_recursive_hash);
+    #[test]
+    fn test_recursive_pairwise_hash_rejects_foreign_children() {
+        // children claiming the nodes of height 1 of a 4 leaf tree, without hashing any leaf
+        let left_hash =
+            PoseidonHash::hash_or_noop(&[leaf_hash(0).elements, leaf_hash(1).elements].concat());
+        let right_hash =
+            PoseidonHash::hash_or_noop(&[leaf_hash(2).elements, leaf_hash(3).elements].concat());
+        let left_proof_data = foreign_proof_data(left_hash, 1, 0);
+        let right_proof_data = foreign_proof_data(right_hash, 1, 1);
+
+        let proof_data = RecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        )
+        .proof()
+        .unwrap();
+        assert_eq!(
+            proof_data.root(),
+            MerkleTree::create(
+                (0..4)
+                    .map(|leaf| vec![F::from_canonical_u64(leaf)])
+                    .collect()
+            )
+            .root()
+        );
 
-        recursive_pairwise_hash.left_recursive_hash.hash =
-            PoseidonHash::hash_or_noop(&[F::from_canonical_u8(255)]);
-        assert!(recursive_pairwise_hash.prove_and_verify().is_err());
+        // the foreign circuits are part of the node circuit, which is not the circuit of the tree
+        let root_verifier_data =
+            MerkleTree::root_verifier_data(4, 1, &ProvingOptions::default()).unwrap();
+        assert_ne!(
+            proof_data.circuit_data.verifier_only,
+            root_verifier_data.verifier_only
+        );
+        assert!(root_verifier_data
+            .verify(proof_data.proof_with_pis)
+            .is_err());
     }
 }
@@ -0,0 +1,486 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use anyhow::{bail, Error};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::{
+    checkpoint::Checkpoint,
+    circuit_compiler::ProofData,
+    progress::{CancellationToken, ProgressObserver, ProgressTracker},
+    C, D, F,
+};
+
+/// `NodeProof` trait:
+///     A node proof scheduled by `ProofScheduler`, whose memory is accounted for against the memory
+///     budget, and which can be persisted to a `Checkpoint`.
+pub(crate) trait NodeProof: Sized + Send {
+    /// Returns an estimate, in bytes, of the memory held by the proof.
+    fn estimated_size(&self) -> usize;
+
+    /// Loads the proof of the node `index` at height `height` from `checkpoint`, if present.
+    fn load(checkpoint: &Checkpoint, height: usize, index: usize) -> Result<Option<Self>, Error>;
+
+    /// Persists the proof of the node `index` at height `height` to `checkpoint`.
+    fn store(&self, checkpoint: &Checkpoint, height: usize, index: usize) -> Result<(), Error>;
+}
+
+impl NodeProof for ProofData<F, C, D> {
+    fn estimated_size(&self) -> usize {
+        ProofData::estimated_size(self)
+    }
+
+    fn load(checkpoint: &Checkpoint, height: usize, index: usize) -> Result<Option<Self>, Error> {
+        checkpoint.load(height, index)
+    }
+
+    fn store(&self, checkpoint: &Checkpoint, height: usize, index: usize) -> Result<(), Error> {
+        checkpoint.store(height, index, self)
+    }
+}
+
+/// Proofs of a single circuit (e.g. `UniformTreeCircuit`), whose circuit data is held once, outside
+/// of the scheduler.
+impl NodeProof for ProofWithPublicInputs<F, C, D> {
+    fn estimated_size(&self) -> usize {
+        self.to_bytes().len()
+    }
+
+    fn load(_: &Checkpoint, _: usize, _: usize) -> Result<Option<Self>, Error> {
+        bail!("proofs without circuit data can not be checkpointed")
+    }
+
+    fn store(&self, _: &Checkpoint, _: usize, _: usize) -> Result<(), Error> {
+        bail!("proofs without circuit data can not be checkpointed")
+    }
+}
+
+/// Bytes accounted for in a counter of a `ProofScheduler` (live proofs or forked subtrees), released
+/// when dropped, so that failing proofs do not leave them accounted for.
+struct AccountedBytes<'a> {
+    counter: &'a AtomicUsize,
+    bytes: usize,
+}
+
+impl Drop for AccountedBytes<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
+
+/// `ProofScheduler` struct:
+///     Schedules the node proofs of a Merkle tree as a dependency DAG on a work-stealing thread pool.
+///
+/// Description:
+///
+///     Each internal node proof only depends on the proofs of its two children. Instead of proving the
+///     tree height by height (waiting for the slowest node of each height), every subtree is proved
+///     through a `rayon::join` of its two child subtrees, so that a parent proof starts as soon as both
+///     of its children are done, while idle threads steal pending subtrees. With enough threads, the
+///     wall-clock time approaches `O(log_2(num_leaves))` node proofs.
+///
+///     Child proofs are released as soon as their parent has been proved, so that only the proofs of
+///     the subtrees currently in progress are kept in memory. An optional memory budget further bounds
+///     the peak memory held by live proofs: subtrees are only proved in parallel while the live proofs
+///     (plus the expected peak of the subtrees already forked) fit in the budget, and are otherwise
+///     proved depth first on the current thread, which keeps at most `height + 1` proofs alive.
+///
+///     An optional `ProgressObserver` is notified after each node proof, and an optional
+///     `CancellationToken` is checked before each node proof.
+///
+///     With a `Checkpoint`, each node proof is persisted once generated, and checkpointed node proofs
+///     are loaded instead of proving their subtrees again.
+#[derive(Default)]
+pub struct ProofScheduler {
+    thread_pool: Option<ThreadPool>,
+    memory_budget: Option<usize>,
+    live_bytes: AtomicUsize,
+    reserved_bytes: AtomicUsize,
+    peak_live_bytes: AtomicUsize,
+    max_proof_bytes: AtomicUsize,
+    progress_observer: Option<Arc<dyn ProgressObserver>>,
+    cancellation_token: Option<CancellationToken>,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl ProofScheduler {
+    /// Method `with_num_threads`:
+    ///
+    ///     Creates a scheduler backed by a dedicated thread pool with `num_threads` threads.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the ProofScheduler or an Error if the thread pool can not be built.
+    pub fn with_num_threads(num_threads: usize) -> Result<Self, Error> {
+        let thread_pool = ThreadPoolBuilder::new().num_threads(num_threads).build()?;
+        Ok(Self {
+            thread_pool: Some(thread_pool),
+            ..Default::default()
+        })
+    }
+
+    /// Method `with_memory_budget`:
+    ///
+    ///     Bounds, on a best effort basis, the peak memory (in bytes) held by live proofs. A single
+    ///     depth first path through the tree is always proved, even if it exceeds the budget.
+    pub fn with_memory_budget(mut self, memory_budget: usize) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// Method `with_progress_observer`:
+    ///
+    ///     Notifies `progress_observer` after each node proof.
+    pub fn with_progress_observer(mut self, progress_observer: Arc<dyn ProgressObserver>) -> Self {
+        self.progress_observer = Some(progress_observer);
+        self
+    }
+
+    /// Method `with_cancellation_token`:
+    ///
+    ///     Aborts tree proofs, with an Error, once `cancellation_token` has been cancelled.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// Method `with_checkpoint`:
+    ///
+    ///     Persists node proofs to `checkpoint`, and reuses the node proofs it already contains.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Method `num_threads`:
+    ///
+    ///     Returns the number of threads proofs are scheduled on.
+    pub fn num_threads(&self) -> usize {
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+
+    /// Method `install`:
+    ///
+    ///     Runs `op` on the thread pool of the scheduler, so that the parallelism of a single proof is
+    ///     bounded by its number of threads.
+    pub(crate) fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// Method `peak_live_bytes`:
+    ///
+    ///     Returns the peak estimated memory (in bytes, see `ProofData::estimated_size`) held by live
+    ///     proofs during the last call to `prove_tree`.
+    pub fn peak_live_bytes(&self) -> usize {
+        self.peak_live_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Method `prove_tree`:
+    ///
+    ///     Proves a tree of height `height` (that is, with `2^height` leaves) and returns the root proof.
+    ///     A scheduler proves one tree at a time: the memory accounting is reset by each call.
+    ///
+    /// Arguments:
+    ///
+    ///     base_height: The height of the subtrees covered by base proofs, at least 1.
+    ///     prove_base: Proves the node `index` of height `base_height`, from the leaves
+    ///         `index * 2^base_height..(index + 1) * 2^base_height`.
+    ///     prove_recursive: Proves the node `(height, index)` from the proofs of its left and right children.
+    pub(crate) fn prove_tree<P, B, R>(
+        &self,
+        height: usize,
+        base_height: usize,
+        prove_base: B,
+        prove_recursive: R,
+    ) -> Result<P, Error>
+    where
+        P: NodeProof,
+        B: Fn(usize) -> Result<P, Error> + Sync,
+        R: Fn(usize, usize, &P, &P) -> Result<P, Error> + Sync,
+    {
+        self.live_bytes.store(0, Ordering::SeqCst);
+        self.reserved_bytes.store(0, Ordering::SeqCst);
+        self.peak_live_bytes.store(0, Ordering::SeqCst);
+        self.max_proof_bytes.store(0, Ordering::SeqCst);
+
+        let progress_tracker = ProgressTracker::new(
+            height,
+            base_height,
+            self.progress_observer.as_deref(),
+            self.cancellation_token.as_ref(),
+        );
+
+        let prove_root = || {
+            self.prove_node(
+                height,
+                0,
+                base_height,
+                &progress_tracker,
+                &prove_base,
+                &prove_recursive,
+            )
+        };
+        let (root_proof_data, _root_live_bytes) = match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(prove_root),
+            None => prove_root(),
+        }?;
+
+        Ok(root_proof_data)
+    }
+
+    /// Recursively proves the node `index` at height `height`, returning its proof together with its
+    /// accounted live bytes. Child proofs are dropped as soon as their parent has been proved.
+    fn prove_node<P, B, R>(
+        &self,
+        height: usize,
+        index: usize,
+        base_height: usize,
+        progress_tracker: &ProgressTracker,
+        prove_base: &B,
+        prove_recursive: &R,
+    ) -> Result<(P, AccountedBytes<'_>), Error>
+    where
+        P: NodeProof,
+        B: Fn(usize) -> Result<P, Error> + Sync,
+        R: Fn(usize, usize, &P, &P) -> Result<P, Error> + Sync,
+    {
+        if let Some(checkpoint) = &self.checkpoint {
+            if let Some(proof_data) = P::load(checkpoint, height, index)? {
+                let live_bytes = self.track_allocation(&proof_data);
+                progress_tracker.subtree_restored(height);
+                return Ok((proof_data, live_bytes));
+            }
+        }
+
+        if height <= base_height {
+            progress_tracker.check_cancelled()?;
+            let proof_data = prove_base(index)?;
+            let live_bytes = self.track_allocation(&proof_data);
+            self.checkpoint(height, index, &proof_data)?;
+            progress_tracker.node_proved(height);
+            return Ok((proof_data, live_bytes));
+        }
+
+        let prove_left = || {
+            self.prove_node(
+                height - 1,
+                2 * index,
+                base_height,
+                progress_tracker,
+                prove_base,
+                prove_recursive,
+            )
+        };
+        let prove_right = || {
+            self.prove_node(
+                height - 1,
+                2 * index + 1,
+                base_height,
+                progress_tracker,
+                prove_base,
+                prove_recursive,
+            )
+        };
+
+        let (left, right) = match self.fork_reservation(height - 1) {
+            Some(_reserved_bytes) => rayon::join(prove_left, prove_right),
+            None => (prove_left(), prove_right()),
+        };
+        // on failure, the live bytes of the other child are released along with its proof
+        let ((left_proof_data, left_live_bytes), (right_proof_data, right_live_bytes)) =
+            (left?, right?);
+
+        progress_tracker.check_cancelled()?;
+        let proof_data = prove_recursive(height, index, &left_proof_data, &right_proof_data)?;
+        let live_bytes = self.track_allocation(&proof_data);
+        self.checkpoint(height, index, &proof_data)?;
+        progress_tracker.node_proved(height);
+
+        drop((left_proof_data, left_live_bytes));
+        drop((right_proof_data, right_live_bytes));
+
+        Ok((proof_data, live_bytes))
+    }
+
+    /// Decides whether two child subtrees of height `child_height` should be proved in parallel. When
+    /// a memory budget is set, the expected peak of the forked subtree is reserved until the returned
+    /// reservation is dropped.
+    fn fork_reservation(&self, child_height: usize) -> Option<AccountedBytes<'_>> {
+        let memory_budget = match self.memory_budget {
+            Some(memory_budget) => memory_budget,
+            None => {
+                return Some(AccountedBytes {
+                    counter: &self.reserved_bytes,
+                    bytes: 0,
+                })
+            }
+        };
+
+        // no proof size is known yet, stay sequential until the first proof is done
+        let max_proof_bytes = self.max_proof_bytes.load(Ordering::SeqCst);
+        if max_proof_bytes == 0 {
+            return None;
+        }
+
+        // a subtree proved depth first keeps at most `height + 1` proofs alive
+        let reservation = (child_height + 1) * max_proof_bytes;
+        let reserved_bytes = self.reserved_bytes.fetch_add(reservation, Ordering::SeqCst);
+        if self.live_bytes.load(Ordering::SeqCst) + reserved_bytes + 2 * reservation
+            <= memory_budget
+        {
+            Some(AccountedBytes {
+                counter: &self.reserved_bytes,
+                bytes: reservation,
+            })
+        } else {
+            self.reserved_bytes.fetch_sub(reservation, Ordering::SeqCst);
+            None
+        }
+    }
+
+    /// Persists the proof of the node `index` at height `height`, if a checkpoint is set.
+    fn checkpoint(
+        &self,
+        height: usize,
+        index: usize,
+        proof_data: &impl NodeProof,
+    ) -> Result<(), Error> {
+        match &self.checkpoint {
+            Some(checkpoint) => proof_data.store(checkpoint, height, index),
+            None => Ok(()),
+        }
+    }
+
+    /// Accounts for a newly generated proof, until the returned live bytes are dropped.
+    fn track_allocation(&self, proof_data: &impl NodeProof) -> AccountedBytes<'_> {
+        let proof_size = proof_data.estimated_size();
+        let live_bytes = self.live_bytes.fetch_add(proof_size, Ordering::SeqCst) + proof_size;
+        self.peak_live_bytes.fetch_max(live_bytes, Ordering::SeqCst);
+        self.max_proof_bytes.fetch_max(proof_size, Ordering::SeqCst);
+        AccountedBytes {
+            counter: &self.live_bytes,
+            bytes: proof_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_scheduler_with_num_threads() {
+        let scheduler = ProofScheduler::with_num_threads(2).unwrap();
+        assert_eq!(scheduler.num_threads(), 2);
+
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+
+        let proof_data = merkle_tree.proof_with_scheduler(&scheduler).unwrap();
+        assert_eq!(proof_data.root(), root);
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_scheduler_with_memory_budget() {
+        // a budget smaller than a single proof forces depth first proving
+        let scheduler = ProofScheduler::with_num_threads(2)
+            .unwrap()
+            .with_memory_budget(1);
+
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+        let proof_data = merkle_tree.proof_with_scheduler(&scheduler).unwrap();
+
+        // at most both base proofs and the root proof are alive at the same time
+        assert!(scheduler.peak_live_bytes() > 0);
+        assert!(scheduler.peak_live_bytes() <= 3 * proof_data.estimated_size());
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_scheduler_runs_every_base_proof_before_failing() {
+        let scheduler = ProofScheduler::with_num_threads(3).unwrap();
+        let num_base_proofs = AtomicUsize::new(0);
+        let num_recursive_proofs = AtomicUsize::new(0);
+
+        // base proofs fail on purpose, so that no real proof needs to be generated. Every subtree
+        // is still visited, but no recursive proof is attempted over failed children
+        let result = scheduler.prove_tree::<ProofData<F, C, D>, _, _>(
+            4,
+            1,
+            |_| {
+                num_base_proofs.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("base"))
+            },
+            |_, _, _, _| {
+                num_recursive_proofs.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("recursive"))
+            },
+        );
+
+        assert!(result.is_err());
+        assert_eq!(num_base_proofs.load(Ordering::SeqCst), 8);
+        assert_eq!(num_recursive_proofs.load(Ordering::SeqCst), 0);
+    }
+
+    /// Stands for a node proof of the given number of bytes, without proving anything.
+    struct SizedProof(usize);
+
+    impl NodeProof for SizedProof {
+        fn estimated_size(&self) -> usize {
+            self.0
+        }
+
+        fn load(_: &Checkpoint, _: usize, _: usize) -> Result<Option<Self>, Error> {
+            Ok(None)
+        }
+
+        fn store(&self, _: &Checkpoint, _: usize, _: usize) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_scheduler_releases_live_bytes_on_failure() {
+        let scheduler = ProofScheduler::with_num_threads(2)
+            .unwrap()
+            .with_memory_budget(1 << 20);
+
+        // the last base proof fails, after the other subtrees have been proved
+        let result = scheduler.prove_tree(
+            3,
+            1,
+            |index| match index {
+                3 => Err(anyhow::anyhow!("base")),
+                _ => Ok(SizedProof(100)),
+            },
+            |_, _, _, _| Ok(SizedProof(100)),
+        );
+
+        assert!(result.is_err());
+        assert!(scheduler.peak_live_bytes() >= 200);
+        assert_eq!(scheduler.live_bytes.load(Ordering::SeqCst), 0);
+        assert_eq!(scheduler.reserved_bytes.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,127 @@
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Error};
+use plonky2::{
+    plonk::{circuit_data::CircuitData, proof::ProofWithPublicInputs},
+    util::serialization::{DefaultGateSerializer, DefaultGeneratorSerializer},
+};
+
+use crate::{circuit_compiler::ProofData, C, D, F};
+
+/// Returns the generator serializer used for circuits of this crate.
+pub(crate) fn generator_serializer() -> DefaultGeneratorSerializer<C, D> {
+    DefaultGeneratorSerializer {
+        _phantom: PhantomData,
+    }
+}
+
+impl ProofData<F, C, D> {
+    /// Method `to_bytes`:
+    ///
+    ///     Serializes the proof data, that is, the full circuit data followed by the proof with its
+    ///     public inputs. The circuit data is prefixed by its length, as a little endian `u64`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the serialized bytes or an Error if the circuit uses gates or
+    ///     generators unknown to plonky2 default serializers.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let circuit_data_bytes = self
+            .circuit_data
+            .to_bytes(&DefaultGateSerializer, &generator_serializer())
+            .map_err(Error::msg)?;
+        let proof_bytes = self.proof_with_pis.to_bytes();
+
+        let mut bytes = Vec::with_capacity(8 + circuit_data_bytes.len() + proof_bytes.len());
+        bytes.extend_from_slice(&(circuit_data_bytes.len() as u64).to_le_bytes());
+        bytes.extend(circuit_data_bytes);
+        bytes.extend(proof_bytes);
+        Ok(bytes)
+    }
+
+    /// Method `from_bytes`:
+    ///
+    ///     Deserializes proof data serialized with `ProofData::to_bytes`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the ProofData or an Error if the bytes are malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        ensure!(bytes.len() >= 8, "proof data bytes are too short");
+        let (length_bytes, bytes) = bytes.split_at(8);
+        let circuit_data_length = u64::from_le_bytes(length_bytes.try_into()?) as usize;
+        ensure!(
+            bytes.len() >= circuit_data_length,
+            "proof data bytes are too short"
+        );
+        let (circuit_data_bytes, proof_bytes) = bytes.split_at(circuit_data_length);
+
+        let circuit_data = CircuitData::<F, C, D>::from_bytes(
+            circuit_data_bytes,
+            &DefaultGateSerializer,
+            &generator_serializer(),
+        )
+        .map_err(Error::msg)?;
+        let proof_with_pis =
+            ProofWithPublicInputs::from_bytes(proof_bytes.to_vec(), &circuit_data.common)?;
+
+        Ok(Self {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+
+    /// Method `proof_to_bytes`:
+    ///
+    ///     Serializes the proof with its public inputs alone, to be checked against the verifier key (see
+    ///     `ProofData::verifier_key_to_bytes` and `verify_root_proof`).
+    pub fn proof_to_bytes(&self) -> Vec<u8> {
+        self.proof_with_pis.to_bytes()
+    }
+
+    /// Method `verifier_key_to_bytes`:
+    ///
+    ///     Serializes the verifier key of the proof, i.e. the verifier data of its circuit, which is all
+    ///     a verifier needs besides the proof itself (see `ProofData::proof_to_bytes`).
+    pub fn verifier_key_to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.circuit_data
+            .verifier_data()
+            .to_bytes(&DefaultGateSerializer)
+            .map_err(Error::msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{field::types::Field, hash::poseidon::PoseidonHash, plonk::config::Hasher};
+
+    use super::*;
+    use crate::{pairwise_hash::PairwiseHash, provable::Provable};
+
+    #[test]
+    fn test_proof_data_serialization() {
+        let f_0_hash = PoseidonHash::hash_or_noop(&[F::ZERO]);
+        let f_1_hash = PoseidonHash::hash_or_noop(&[F::ONE]);
+        let proof_data = PairwiseHash::new(vec![F::ZERO], f_0_hash, vec![F::ONE], f_1_hash)
+            .proof()
+            .unwrap();
+
+        let bytes = proof_data.to_bytes().unwrap();
+        let deserialized_proof_data = ProofData::from_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            deserialized_proof_data.circuit_data,
+            proof_data.circuit_data
+        );
+        assert_eq!(
+            deserialized_proof_data.proof_with_pis,
+            proof_data.proof_with_pis
+        );
+        assert!(deserialized_proof_data
+            .circuit_data
+            .verify(deserialized_proof_data.proof_with_pis)
+            .is_ok());
+
+        assert!(ProofData::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
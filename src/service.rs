@@ -0,0 +1,544 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Cursor, Read},
+    net::{SocketAddr, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, ensure, Error};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    leaf_format::{hash_to_hex, LeafFormat},
+    merkle_tree::MerkleTree,
+    proving_options::ProvingOptions,
+    F,
+};
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Maximum length, in bytes, of a request body. Longer requests are rejected with
+/// `413 Payload Too Large`.
+pub const MAX_BODY_LEN: usize = 64 << 20;
+
+/// Default number of completed (done or failed) jobs kept by a `ProvingService`, see
+/// `ProvingService::with_max_completed_jobs`.
+pub const DEFAULT_MAX_COMPLETED_JOBS: usize = 64;
+
+/// Default number of jobs waiting to be proved by a `ProvingService`, beyond which submissions are
+/// rejected with `503 Service Unavailable`, see `ProvingService::with_max_pending_jobs`.
+pub const DEFAULT_MAX_PENDING_JOBS: usize = 16;
+
+/// `JobState` enum:
+///     The state of a proving job submitted to a `ProvingService`.
+///
+/// Variants:
+///
+///     Queued: The job waits for the jobs submitted before it.
+///     Proving: The tree root proof is being generated.
+///     Done: The root proof, and its verifier key, are available (see `ProofData::proof_to_bytes` and
+///         `ProofData::verifier_key_to_bytes`).
+///     Failed: Proving failed, with the given error message.
+#[derive(Clone, Debug)]
+pub enum JobState {
+    Queued,
+    Proving,
+    Done {
+        proof: Vec<u8>,
+        verifier_key: Vec<u8>,
+    },
+    Failed(String),
+}
+
+struct Job {
+    num_leaves: usize,
+    root: String,
+    state: JobState,
+}
+
+/// The jobs of a `ProvingService`, from which the oldest completed jobs are evicted, and whose
+/// queued jobs are bounded.
+struct Jobs {
+    jobs: HashMap<usize, Job>,
+    next_id: usize,
+    completed_ids: VecDeque<usize>,
+    max_completed_jobs: usize,
+    num_pending_jobs: usize,
+    max_pending_jobs: usize,
+}
+
+impl Jobs {
+    fn is_full(&self) -> bool {
+        self.num_pending_jobs >= self.max_pending_jobs
+    }
+
+    fn set_state(&mut self, id: usize, state: JobState) {
+        let completed = matches!(state, JobState::Done { .. } | JobState::Failed(_));
+        if let Some(job) = self.jobs.get_mut(&id) {
+            if matches!(job.state, JobState::Queued) {
+                self.num_pending_jobs -= 1;
+            }
+            job.state = state;
+        }
+        if completed {
+            self.completed_ids.push_back(id);
+            self.evict_completed_jobs();
+        }
+    }
+
+    fn evict_completed_jobs(&mut self) {
+        while self.completed_ids.len() > self.max_completed_jobs {
+            let id = self.completed_ids.pop_front().unwrap();
+            self.jobs.remove(&id);
+        }
+    }
+}
+
+/// `ProvingService` struct:
+///     A local HTTP service proving Merkle trees submitted as JSON.
+///
+/// Description:
+///
+///     Jobs are proved one at a time, in submission order, with `MerkleTree::proof_with_options`, by a
+///     background thread. The service exposes the following endpoints:
+///
+///     POST /jobs: Submits the leaves of a tree, as a JSON array of leaves (see `LeafFormat::Json`),
+///         and replies `202 Accepted` with the job id, e.g. `{"id": 0}`, or
+///         `503 Service Unavailable` if too many jobs are waiting to be proved.
+///     GET /jobs/<id>: Replies with the job status, e.g.
+///         `{"id": 0, "status": "done", "root": "0x...", "num_leaves": 4}`, along with an `error`
+///         message for failed jobs.
+///     GET /jobs/<id>/proof: Replies with the serialized root proof, once the job is done.
+///     GET /jobs/<id>/verifier-key: Replies with the serialized verifier key, once the job is done.
+///
+///     Proofs can then be checked with `verify_root_proof`, against the verifier key digest of
+///     `MerkleTree::root_verifier_key_digest` for the proving options of the service.
+///
+///     Request bodies are limited to `MAX_BODY_LEN` bytes, the number of jobs waiting to be proved is
+///     bounded (see `ProvingService::with_max_pending_jobs`), and only the most recently completed
+///     jobs are kept (see `ProvingService::with_max_completed_jobs`), older jobs being reported as
+///     unknown. The service does not authenticate its clients, and is meant to listen on a loopback
+///     address.
+pub struct ProvingService {
+    server: Server,
+    jobs: Arc<Mutex<Jobs>>,
+    queue: Sender<(usize, MerkleTree)>,
+    proving_options: ProvingOptions,
+}
+
+impl ProvingService {
+    /// Method `bind`:
+    ///
+    ///     Binds the service to `address`, and starts the thread proving its jobs with
+    ///     `proving_options`. Requests are only served once `run` is called.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the ProvingService or an Error if the address cannot be bound.
+    pub fn bind(
+        address: impl ToSocketAddrs,
+        proving_options: ProvingOptions,
+    ) -> Result<Self, Error> {
+        let server = Server::http(address).map_err(|e| anyhow!(e))?;
+        let jobs = Arc::new(Mutex::new(Jobs {
+            jobs: HashMap::new(),
+            next_id: 0,
+            completed_ids: VecDeque::new(),
+            max_completed_jobs: DEFAULT_MAX_COMPLETED_JOBS,
+            num_pending_jobs: 0,
+            max_pending_jobs: DEFAULT_MAX_PENDING_JOBS,
+        }));
+        let (queue, queued_jobs) = mpsc::channel::<(usize, MerkleTree)>();
+
+        let prover_jobs = jobs.clone();
+        let prover_options = proving_options.clone();
+        thread::spawn(move || {
+            for (id, merkle_tree) in queued_jobs {
+                prover_jobs.lock().unwrap().set_state(id, JobState::Proving);
+                // plonky2 panics on inconsistent witnesses, which should not take the service down
+                let proof = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let proof_data = merkle_tree.proof_with_options(&prover_options)?;
+                    Ok::<_, Error>((
+                        proof_data.proof_to_bytes(),
+                        proof_data.verifier_key_to_bytes()?,
+                    ))
+                }))
+                .unwrap_or_else(|_| Err(anyhow!("the proof panicked")));
+                let state = match proof {
+                    Ok((proof, verifier_key)) => JobState::Done {
+                        proof,
+                        verifier_key,
+                    },
+                    Err(e) => JobState::Failed(e.to_string()),
+                };
+                prover_jobs.lock().unwrap().set_state(id, state);
+            }
+        });
+
+        Ok(Self {
+            server,
+            jobs,
+            queue,
+            proving_options,
+        })
+    }
+
+    /// Method `with_max_completed_jobs`:
+    ///
+    ///     Keeps the `max_completed_jobs` most recently completed jobs, instead of
+    ///     `DEFAULT_MAX_COMPLETED_JOBS`, along with their proofs. Older completed jobs are evicted.
+    pub fn with_max_completed_jobs(self, max_completed_jobs: usize) -> Self {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.max_completed_jobs = max_completed_jobs;
+        jobs.evict_completed_jobs();
+        drop(jobs);
+        self
+    }
+
+    /// Method `with_max_pending_jobs`:
+    ///
+    ///     Accepts up to `max_pending_jobs` jobs waiting to be proved, instead of
+    ///     `DEFAULT_MAX_PENDING_JOBS`. Further submissions are rejected until queued jobs start.
+    pub fn with_max_pending_jobs(self, max_pending_jobs: usize) -> Self {
+        self.jobs.lock().unwrap().max_pending_jobs = max_pending_jobs;
+        self
+    }
+
+    /// Method `local_address`:
+    ///
+    ///     Returns the address the service is bound to.
+    pub fn local_address(&self) -> SocketAddr {
+        self.server
+            .server_addr()
+            .to_ip()
+            .expect("the service listens on an IP address")
+    }
+
+    /// Method `job_state`:
+    ///
+    ///     Returns the state of the job `id`, if it was submitted and has not been evicted.
+    pub fn job_state(&self, id: usize) -> Option<JobState> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .jobs
+            .get(&id)
+            .map(|job| job.state.clone())
+    }
+
+    /// Method `submit`:
+    ///
+    ///     Queues the proof of the tree with leaves `leaves`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the job id, or an Error if the tree cannot be proved with the
+    ///     service proving options, or if too many jobs are already waiting to be proved.
+    pub fn submit(&self, leaves: Vec<Vec<F>>) -> Result<usize, Error> {
+        ensure!(
+            leaves.len().is_power_of_two() && leaves.len() > 1,
+            "the number of leaves should be a power of two, greater than one, got {}",
+            leaves.len()
+        );
+        self.proving_options.validate(leaves.len())?;
+
+        let num_leaves = leaves.len();
+        let merkle_tree = MerkleTree::create(leaves);
+        let mut jobs = self.jobs.lock().unwrap();
+        ensure!(
+            !jobs.is_full(),
+            "{} jobs are already waiting to be proved",
+            jobs.num_pending_jobs
+        );
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        jobs.num_pending_jobs += 1;
+        jobs.jobs.insert(
+            id,
+            Job {
+                num_leaves,
+                root: hash_to_hex(merkle_tree.root()),
+                state: JobState::Queued,
+            },
+        );
+        self.queue.send((id, merkle_tree))?;
+        Ok(id)
+    }
+
+    /// Method `run`:
+    ///
+    ///     Serves requests until the service is dropped.
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            let (request, body) = request_body(request);
+            let response = match body.transpose() {
+                Some(body) => self.handle(&request, body),
+                None => json_response(
+                    413,
+                    json!({ "error": format!("the request body exceeds {} bytes", MAX_BODY_LEN) }),
+                ),
+            };
+            // a client hanging up early should not stop the service
+            let _ = request.respond(response);
+        }
+    }
+
+    fn handle(&self, request: &Request, body: Result<Vec<u8>, Error>) -> HttpResponse {
+        let segments: Vec<&str> = request
+            .url()
+            .trim_matches('/')
+            .split('/')
+            .collect::<Vec<_>>();
+        match (request.method(), segments.as_slice()) {
+            (Method::Post, ["jobs"]) => {
+                // the prover thread only dequeues jobs, so a queue seen as not full stays so
+                if self.jobs.lock().unwrap().is_full() {
+                    return json_response(
+                        503,
+                        json!({ "error": "too many jobs are waiting to be proved" }),
+                    );
+                }
+                let submitted = body
+                    .and_then(|body| LeafFormat::Json.parse_leaves(&body))
+                    .and_then(|leaves| self.submit(leaves));
+                match submitted {
+                    Ok(id) => json_response(202, json!({ "id": id })),
+                    Err(e) => json_response(400, json!({ "error": e.to_string() })),
+                }
+            }
+            (Method::Get, ["jobs", id, endpoint @ ..]) => {
+                let jobs = self.jobs.lock().unwrap();
+                let Some((id, job)) = id
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|id| jobs.jobs.get(&id).map(|job| (id, job)))
+                else {
+                    return json_response(404, json!({ "error": "unknown job" }));
+                };
+                match (endpoint, &job.state) {
+                    ([], state) => {
+                        let mut status = json!({
+                            "id": id,
+                            "status": match state {
+                                JobState::Queued => "queued",
+                                JobState::Proving => "proving",
+                                JobState::Done { .. } => "done",
+                                JobState::Failed(_) => "failed",
+                            },
+                            "root": job.root,
+                            "num_leaves": job.num_leaves,
+                        });
+                        if let JobState::Failed(error) = state {
+                            status["error"] = Value::from(error.as_str());
+                        }
+                        json_response(200, status)
+                    }
+                    (["proof"], JobState::Done { proof, .. }) => bytes_response(proof.clone()),
+                    (["verifier-key"], JobState::Done { verifier_key, .. }) => {
+                        bytes_response(verifier_key.clone())
+                    }
+                    (["proof"] | ["verifier-key"], _) => {
+                        json_response(409, json!({ "error": "the job is not done" }))
+                    }
+                    _ => json_response(404, json!({ "error": "unknown endpoint" })),
+                }
+            }
+            _ => json_response(404, json!({ "error": "unknown endpoint" })),
+        }
+    }
+}
+
+/// Reads the body of `request`, or returns `None` if it is longer than `MAX_BODY_LEN` bytes.
+fn request_body(mut request: Request) -> (Request, Result<Option<Vec<u8>>, Error>) {
+    if request
+        .body_length()
+        .is_some_and(|body_length| body_length > MAX_BODY_LEN)
+    {
+        return (request, Ok(None));
+    }
+    let mut body = vec![];
+    let read = request
+        .as_reader()
+        .take(MAX_BODY_LEN as u64 + 1)
+        .read_to_end(&mut body)
+        .map(|_| (body.len() <= MAX_BODY_LEN).then_some(body))
+        .map_err(Error::from);
+    (request, read)
+}
+
+fn json_response(status: u16, value: Value) -> HttpResponse {
+    Response::from_data(value.to_string().into_bytes())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+fn bytes_response(bytes: Vec<u8>) -> HttpResponse {
+    // proofs are sent with their length rather than chunked, which keeps clients simple
+    Response::from_data(bytes)
+        .with_chunked_threshold(usize::MAX)
+        .with_header(Header::from_bytes("Content-Type", "application/octet-stream").unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        time::{Duration, Instant},
+    };
+
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::verifier::verify_root_proof;
+
+    fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+
+        let header_end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let status = std::str::from_utf8(&response[9..12])
+            .unwrap()
+            .parse()
+            .unwrap();
+        (status, response[header_end + 4..].to_vec())
+    }
+
+    fn json_body(body: &[u8]) -> Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[test]
+    fn test_proving_service() {
+        let service = ProvingService::bind("127.0.0.1:0", ProvingOptions::default()).unwrap();
+        let address = service.local_address();
+        thread::spawn(move || service.run());
+
+        let (status, body) = request(address, "POST", "/jobs", "[[0, 1], [2, 3], [4, 5], [6, 7]]");
+        assert_eq!(status, 202);
+        let id = json_body(&body)["id"].as_u64().unwrap();
+
+        let started = Instant::now();
+        let status = loop {
+            let (status, body) = request(address, "GET", &format!("/jobs/{}", id), "");
+            assert_eq!(status, 200);
+            let status = json_body(&body);
+            if status["status"] == "done" || status["status"] == "failed" {
+                break status;
+            }
+            assert!(started.elapsed() < Duration::from_secs(600));
+            thread::sleep(Duration::from_millis(100));
+        };
+        assert_eq!(status["status"], "done");
+        assert_eq!(status["num_leaves"], 4);
+
+        let (status_code, proof) = request(address, "GET", &format!("/jobs/{}/proof", id), "");
+        assert_eq!(status_code, 200);
+        let (status_code, verifier_key) =
+            request(address, "GET", &format!("/jobs/{}/verifier-key", id), "");
+        assert_eq!(status_code, 200);
+
+        let leaves = (0..4)
+            .map(|i| {
+                vec![
+                    F::from_canonical_u64(2 * i),
+                    F::from_canonical_u64(2 * i + 1),
+                ]
+            })
+            .collect();
+        let root = MerkleTree::create(leaves).root();
+        assert_eq!(status["root"], hash_to_hex(root));
+        let verifier_key_digest =
+            MerkleTree::root_verifier_key_digest(4, 2, &ProvingOptions::default()).unwrap();
+        assert_eq!(
+            verify_root_proof(&proof, &verifier_key, root, verifier_key_digest).unwrap(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_proving_service_rejects_invalid_requests() {
+        let service = ProvingService::bind("127.0.0.1:0", ProvingOptions::default()).unwrap();
+        let address = service.local_address();
+        thread::spawn(move || service.run());
+
+        assert_eq!(request(address, "POST", "/jobs", "[[0], [1], [2]]").0, 400);
+        assert_eq!(request(address, "POST", "/jobs", "not json").0, 400);
+        assert_eq!(request(address, "GET", "/jobs/0", "").0, 404);
+        assert_eq!(request(address, "GET", "/jobs/zero/proof", "").0, 404);
+        assert_eq!(request(address, "GET", "/roots", "").0, 404);
+
+        // oversized bodies are rejected from their declared length, before being read
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /jobs HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_LEN + 1
+        )
+        .unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        assert_eq!(&response[9..12], b"413");
+    }
+
+    #[test]
+    fn test_proving_service_evicts_completed_jobs() {
+        let service = ProvingService::bind("127.0.0.1:0", ProvingOptions::default())
+            .unwrap()
+            .with_max_completed_jobs(1);
+        let leaves = |offset: u64| -> Vec<Vec<F>> {
+            (offset..offset + 2)
+                .map(|i| vec![F::from_canonical_u64(i)])
+                .collect()
+        };
+        let first_id = service.submit(leaves(0)).unwrap();
+        let second_id = service.submit(leaves(2)).unwrap();
+
+        let started = Instant::now();
+        while !matches!(service.job_state(second_id), Some(JobState::Done { .. })) {
+            assert!(started.elapsed() < Duration::from_secs(600));
+            thread::sleep(Duration::from_millis(100));
+        }
+        // jobs are proved in submission order, hence the first job was completed first
+        assert!(service.job_state(first_id).is_none());
+    }
+    #[test]
+    fn test_proving_service_rejects_jobs_beyond_pending_limit() {
+        let service = ProvingService::bind("127.0.0.1:0", ProvingOptions::default())
+            .unwrap()
+            .with_max_pending_jobs(1);
+        let address = service.local_address();
+        thread::spawn(move || service.run());
+
+        let leaves = "[[0], [1], [2], [3]]";
+        assert_eq!(request(address, "POST", "/jobs", leaves).0, 202);
+        let started = Instant::now();
+        while json_body(&request(address, "GET", "/jobs/0", "").1)["status"] == "queued" {
+            assert!(started.elapsed() < Duration::from_secs(600));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // the first job is being proved, a single job can wait behind it
+        assert_eq!(request(address, "POST", "/jobs", leaves).0, 202);
+        assert_eq!(request(address, "POST", "/jobs", leaves).0, 503);
+    }
+}
@@ -0,0 +1,387 @@
+use std::fmt::Write;
+
+use plonky2::{
+    field::types::{Field64, PrimeField64},
+    hash::{
+        hash_types::HashOut,
+        poseidon::{Poseidon, ALL_ROUND_CONSTANTS, HALF_N_FULL_ROUNDS, N_ROUNDS, SPONGE_WIDTH},
+    },
+};
+use serde_json::{json, Value};
+
+use crate::{json::to_envelope, merkle_tree::MerkleTree, F};
+
+/// Name of the Solidity library emitted by `solidity_verifier`.
+pub const SOLIDITY_LIBRARY_NAME: &str = "PoseidonGoldilocksMerkle";
+
+/// Envelope type of the test vectors emitted by `solidity_test_vectors`.
+pub const SOLIDITY_TEST_VECTORS_TYPE: &str = "solidity_test_vectors";
+
+/// Formats `values` as a Solidity `uint256` array literal.
+fn solidity_array(values: &[u64]) -> String {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    format!("[uint256({}), {}]", values[0], values[1..].join(", "))
+}
+
+/// Function `solidity_verifier`:
+///
+///     Emits the source of a Solidity library, `PoseidonGoldilocksMerkle`, verifying native
+///     authentication paths (see `InclusionProof`) against a Merkle root.
+///
+/// Description:
+///
+///     The library implements the Poseidon permutation over the Goldilocks field, with the round
+///     constants and MDS matrix of plonky2, and the `PoseidonHash::hash_or_noop` sponge: inputs of at
+///     most 4 elements are zero padded, longer inputs are absorbed in chunks of 8 elements, overwriting
+///     the state. Hence, `computeRoot` hashes a leaf and its siblings as `MerkleTree::create` and
+///     `InclusionProof::compute_root` do.
+///
+///     Field elements are `uint256` values, which should be canonical (below the Goldilocks order),
+///     and hashes are `uint256[4]` arrays of their elements. `verify(leaf, index, siblings, root)`
+///     returns whether the path is valid, siblings being ordered from the leaf level up.
+pub fn solidity_verifier() -> String {
+    let round_constants: String = ALL_ROUND_CONSTANTS[..SPONGE_WIDTH * N_ROUNDS].iter().fold(
+        String::new(),
+        |mut hex, constant| {
+            write!(hex, "{:016x}", constant).unwrap();
+            hex
+        },
+    );
+
+    format!(
+        r#"// SPDX-License-Identifier: UNLICENSED
+// Generated by recursive_merkle_tree_proofs (`solidity_verifier`), do not edit.
+pragma solidity ^0.8.17;
+
+/// Verifies Poseidon-Goldilocks Merkle authentication paths, as built by `MerkleTree::create`.
+library {name} {{
+    uint256 internal constant ORDER = {order};
+    uint256 internal constant WIDTH = {width};
+    uint256 internal constant RATE = 8;
+    uint256 internal constant HALF_FULL_ROUNDS = {half_full_rounds};
+    uint256 internal constant ROUNDS = {rounds};
+
+    /// Round constants, as consecutive big endian 8 byte words.
+    bytes internal constant ROUND_CONSTANTS = hex"{round_constants}";
+
+    function sbox(uint256 x) private pure returns (uint256) {{
+        uint256 x2 = mulmod(x, x, ORDER);
+        uint256 x4 = mulmod(x2, x2, ORDER);
+        uint256 x3 = mulmod(x, x2, ORDER);
+        return mulmod(x3, x4, ORDER);
+    }}
+
+    function mds(uint256[12] memory state) private pure returns (uint256[12] memory result) {{
+        uint256[12] memory circ = {circ};
+        uint256[12] memory diag = {diag};
+        for (uint256 r = 0; r < WIDTH; r++) {{
+            // elements are below 2^64 and matrix entries below 2^8, so the sum cannot overflow
+            uint256 sum = state[r] * diag[r];
+            for (uint256 i = 0; i < WIDTH; i++) {{
+                sum += state[(i + r) % WIDTH] * circ[i];
+            }}
+            result[r] = sum % ORDER;
+        }}
+    }}
+
+    /// The Poseidon permutation of plonky2, with 8 full rounds and 22 partial rounds.
+    function permute(uint256[12] memory state) internal pure returns (uint256[12] memory) {{
+        bytes memory roundConstants = ROUND_CONSTANTS;
+        for (uint256 round = 0; round < ROUNDS; round++) {{
+            for (uint256 i = 0; i < WIDTH; i++) {{
+                uint256 constant_;
+                uint256 offset = 8 * (WIDTH * round + i);
+                assembly {{
+                    constant_ := shr(192, mload(add(add(roundConstants, 32), offset)))
+                }}
+                state[i] = addmod(state[i], constant_, ORDER);
+            }}
+            if (round < HALF_FULL_ROUNDS || round >= ROUNDS - HALF_FULL_ROUNDS) {{
+                for (uint256 i = 0; i < WIDTH; i++) {{
+                    state[i] = sbox(state[i]);
+                }}
+            }} else {{
+                state[0] = sbox(state[0]);
+            }}
+            state = mds(state);
+        }}
+        return state;
+    }}
+
+    /// Hashes `input` as plonky2 `PoseidonHash::hash_or_noop` does.
+    function hashOrNoop(uint256[] memory input) internal pure returns (uint256[4] memory hash) {{
+        for (uint256 i = 0; i < input.length; i++) {{
+            require(input[i] < ORDER, "non canonical field element");
+        }}
+        if (input.length <= 4) {{
+            for (uint256 i = 0; i < input.length; i++) {{
+                hash[i] = input[i];
+            }}
+            return hash;
+        }}
+        uint256[12] memory state;
+        for (uint256 start = 0; start < input.length; start += RATE) {{
+            for (uint256 i = 0; i < RATE && start + i < input.length; i++) {{
+                state[i] = input[start + i];
+            }}
+            state = permute(state);
+        }}
+        for (uint256 i = 0; i < 4; i++) {{
+            hash[i] = state[i];
+        }}
+    }}
+
+    /// Hashes two sibling nodes into their parent.
+    function hashPair(uint256[4] memory left, uint256[4] memory right)
+        internal
+        pure
+        returns (uint256[4] memory parent)
+    {{
+        uint256[12] memory state;
+        for (uint256 i = 0; i < 4; i++) {{
+            require(left[i] < ORDER && right[i] < ORDER, "non canonical field element");
+            state[i] = left[i];
+            state[i + 4] = right[i];
+        }}
+        state = permute(state);
+        for (uint256 i = 0; i < 4; i++) {{
+            parent[i] = state[i];
+        }}
+    }}
+
+    /// Recomputes the root from a leaf, its index and its siblings, from the leaf level up.
+    function computeRoot(uint256[] memory leaf, uint256 index, uint256[4][] memory siblings)
+        internal
+        pure
+        returns (uint256[4] memory node)
+    {{
+        node = hashOrNoop(leaf);
+        for (uint256 i = 0; i < siblings.length; i++) {{
+            node = (index & 1) == 0 ? hashPair(node, siblings[i]) : hashPair(siblings[i], node);
+            index >>= 1;
+        }}
+    }}
+
+    /// Returns whether `leaf` is the leaf `index` of the tree with root `root`.
+    function verify(
+        uint256[] memory leaf,
+        uint256 index,
+        uint256[4][] memory siblings,
+        uint256[4] memory root
+    ) internal pure returns (bool) {{
+        if ((index >> siblings.length) != 0) {{
+            return false;
+        }}
+        uint256[4] memory node = computeRoot(leaf, index, siblings);
+        return node[0] == root[0] && node[1] == root[1] && node[2] == root[2] && node[3] == root[3];
+    }}
+}}
+"#,
+        name = SOLIDITY_LIBRARY_NAME,
+        order = F::ORDER,
+        width = SPONGE_WIDTH,
+        half_full_rounds = HALF_N_FULL_ROUNDS,
+        rounds = N_ROUNDS,
+        round_constants = round_constants,
+        circ = solidity_array(&<F as Poseidon>::MDS_MATRIX_CIRC),
+        diag = solidity_array(&<F as Poseidon>::MDS_MATRIX_DIAG),
+    )
+}
+
+fn solidity_elements(elements: &[F]) -> Value {
+    Value::from(
+        elements
+            .iter()
+            .map(|element| element.to_canonical_u64().to_string())
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn solidity_hash(hash: HashOut<F>) -> Value {
+    solidity_elements(&hash.elements)
+}
+
+/// Function `solidity_test_vectors`:
+///
+///     Exports test vectors for the library emitted by `solidity_verifier`, built from the
+///     authentication paths of every leaf of `merkle_tree`.
+///
+/// Description:
+///
+///     The vectors are a `solidity_test_vectors` JSON envelope (see `to_envelope`), whose data is
+///     `{"root": [..], "cases": [{"leaf": [..], "index": 2, "siblings": [[..], ..], "valid": true}, ..]}`,
+///     field elements being decimal strings, ready to be passed as `uint256` values to `verify`. Each
+///     valid path is followed by the same path with a flipped leaf index, which `verify` should reject.
+pub fn solidity_test_vectors(merkle_tree: &MerkleTree) -> Value {
+    let cases: Vec<Value> = (0..merkle_tree.leaves.len())
+        .flat_map(|leaf_index| {
+            let inclusion_proof = merkle_tree
+                .inclusion_proof(leaf_index)
+                .expect("the leaf index is in range");
+            let siblings = Value::from(
+                inclusion_proof
+                    .siblings
+                    .iter()
+                    .map(|sibling| solidity_hash(*sibling))
+                    .collect::<Vec<_>>(),
+            );
+            let leaf = solidity_elements(&inclusion_proof.leaf);
+            [
+                json!({
+                    "leaf": leaf,
+                    "index": leaf_index,
+                    "siblings": siblings,
+                    "valid": true,
+                }),
+                json!({
+                    "leaf": leaf,
+                    "index": leaf_index ^ 1,
+                    "siblings": siblings,
+                    "valid": false,
+                }),
+            ]
+        })
+        .collect();
+
+    to_envelope(
+        SOLIDITY_TEST_VECTORS_TYPE,
+        json!({
+            "root": solidity_hash(merkle_tree.root()),
+            "cases": cases,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{field::types::Field, hash::poseidon::PoseidonHash, plonk::config::Hasher};
+
+    use super::*;
+    use crate::json::from_envelope;
+
+    const ORDER: u128 = F::ORDER as u128;
+
+    // Mirrors, step by step, the `permute` function of the emitted library.
+    fn solidity_permute(mut state: [u128; SPONGE_WIDTH]) -> [u128; SPONGE_WIDTH] {
+        let sbox = |x: u128| {
+            let x2 = x * x % ORDER;
+            let x4 = x2 * x2 % ORDER;
+            let x3 = x * x2 % ORDER;
+            x3 * x4 % ORDER
+        };
+        for round in 0..N_ROUNDS {
+            for (i, element) in state.iter_mut().enumerate() {
+                *element =
+                    (*element + ALL_ROUND_CONSTANTS[SPONGE_WIDTH * round + i] as u128) % ORDER;
+            }
+            if !(HALF_N_FULL_ROUNDS..N_ROUNDS - HALF_N_FULL_ROUNDS).contains(&round) {
+                state = state.map(sbox);
+            } else {
+                state[0] = sbox(state[0]);
+            }
+            state = std::array::from_fn(|r| {
+                let sum = state[r] * <F as Poseidon>::MDS_MATRIX_DIAG[r] as u128
+                    + (0..SPONGE_WIDTH)
+                        .map(|i| {
+                            state[(i + r) % SPONGE_WIDTH]
+                                * <F as Poseidon>::MDS_MATRIX_CIRC[i] as u128
+                        })
+                        .sum::<u128>();
+                sum % ORDER
+            });
+        }
+        state
+    }
+
+    // Mirrors the `hashOrNoop` function of the emitted library.
+    fn solidity_hash_or_noop(input: &[u128]) -> [u128; 4] {
+        if input.len() <= 4 {
+            return std::array::from_fn(|i| input.get(i).copied().unwrap_or(0));
+        }
+        let mut state = [0; SPONGE_WIDTH];
+        for chunk in input.chunks(8) {
+            state[..chunk.len()].copy_from_slice(chunk);
+            state = solidity_permute(state);
+        }
+        std::array::from_fn(|i| state[i])
+    }
+
+    fn to_u128(elements: &[F]) -> Vec<u128> {
+        elements
+            .iter()
+            .map(|element| element.to_canonical_u64() as u128)
+            .collect()
+    }
+
+    #[test]
+    fn test_solidity_hash_matches_poseidon() {
+        for len in 0..20 {
+            let input: Vec<F> = (0..len)
+                .map(|i| F::NEG_ONE - F::from_canonical_u64(i))
+                .collect();
+            assert_eq!(
+                solidity_hash_or_noop(&to_u128(&input)).to_vec(),
+                to_u128(&PoseidonHash::hash_or_noop(&input).elements)
+            );
+        }
+    }
+
+    #[test]
+    fn test_solidity_verifier_source() {
+        let source = solidity_verifier();
+        assert!(source.contains(&format!("library {} {{", SOLIDITY_LIBRARY_NAME)));
+        assert!(source.contains(&format!("ORDER = {};", F::ORDER)));
+        assert!(source.contains(&format!(
+            "{:016x}{:016x}",
+            ALL_ROUND_CONSTANTS[0], ALL_ROUND_CONSTANTS[1]
+        )));
+        assert!(source.contains(&format!(
+            "{:016x}\";",
+            ALL_ROUND_CONSTANTS[SPONGE_WIDTH * N_ROUNDS - 1]
+        )));
+        assert!(source.contains(&format!(
+            "circ = {};",
+            solidity_array(&<F as Poseidon>::MDS_MATRIX_CIRC)
+        )));
+    }
+
+    #[test]
+    fn test_solidity_test_vectors() {
+        let merkle_tree = MerkleTree::create(
+            (0..8)
+                .map(|i| (0..i + 1).map(F::from_canonical_u64).collect())
+                .collect(),
+        );
+        let vectors = solidity_test_vectors(&merkle_tree);
+        let data = from_envelope(SOLIDITY_TEST_VECTORS_TYPE, &vectors).unwrap();
+        let parse = |value: &Value| -> Vec<u128> {
+            value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|element| element.as_str().unwrap().parse().unwrap())
+                .collect()
+        };
+        let root = parse(&data["root"]);
+        assert_eq!(root, to_u128(&merkle_tree.root().elements));
+
+        let cases = data["cases"].as_array().unwrap();
+        assert_eq!(cases.len(), 16);
+        for case in cases {
+            // recompute the root as `computeRoot` does
+            let mut index = case["index"].as_u64().unwrap();
+            let mut node = solidity_hash_or_noop(&parse(&case["leaf"]));
+            for sibling in case["siblings"].as_array().unwrap() {
+                let sibling = parse(sibling);
+                let (left, right) = if index & 1 == 0 {
+                    (node.to_vec(), sibling)
+                } else {
+                    (sibling, node.to_vec())
+                };
+                node = solidity_hash_or_noop(&[left, right].concat());
+                index >>= 1;
+            }
+            assert_eq!(node.to_vec() == root, case["valid"].as_bool().unwrap());
+        }
+    }
+}
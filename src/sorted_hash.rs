@@ -0,0 +1,414 @@
+use anyhow::{ensure, Error};
+use plonky2::{
+    field::types::Field,
+    hash::hash_types::{HashOut, HashOutTarget},
+    iop::{target::Target, witness::PartialWitness},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData},
+    },
+};
+
+use crate::{
+    circuit_compiler::{CircuitCompiler, EvaluateFillCircuit, ProofData},
+    pairwise_hash::{PairwiseHash, NUM_NODE_PUBLIC_INPUTS},
+    provable::Provable,
+    recursive_hash::{
+        compile_recursive_node, RecursiveHash, RecursiveNodeTargets, RecursivePairwiseHash,
+    },
+    C, D, F,
+};
+
+/// Maximum bit size of a sorted leaf key. Keeping keys below `2^62` guarantees that the
+/// difference of two keys cannot wrap around the Goldilocks modulus into the checked range.
+pub const MAX_KEY_BITS: usize = 62;
+
+/// Index, within the public inputs of a sorted node proof, of the smallest key of the subtree.
+pub const MIN_KEY_PUBLIC_INPUT_INDEX: usize = NUM_NODE_PUBLIC_INPUTS;
+/// Index, within the public inputs of a sorted node proof, of the largest key of the subtree.
+pub const MAX_KEY_PUBLIC_INPUT_INDEX: usize = NUM_NODE_PUBLIC_INPUTS + 1;
+/// Number of public inputs of a sorted node proof: the public inputs of a node proof (parent hash,
+/// height, number of leaves and index), followed by the min and max keys.
+pub const NUM_SORTED_PUBLIC_INPUTS: usize = NUM_NODE_PUBLIC_INPUTS + 2;
+
+/// Enforces `lower < upper`, for targets that have been range checked to `MAX_KEY_BITS` bits.
+fn assert_strictly_increasing(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    lower: Target,
+    upper: Target,
+) {
+    let difference = circuit_builder.sub(upper, lower);
+    let difference_minus_one = circuit_builder.add_const(difference, F::NEG_ONE);
+    circuit_builder.range_check(difference_minus_one, MAX_KEY_BITS);
+}
+
+/// `SortedPairwiseHash` struct:
+///     A `PairwiseHash` whose circuit additionally enforces that the key (first element) of the
+///     left leaf is strictly smaller than the key of the right leaf. Both keys are exposed as
+///     public inputs, after those of `PairwiseHash`.
+#[derive(Clone, Debug)]
+pub(crate) struct SortedPairwiseHash {
+    pub(crate) pairwise_hash: PairwiseHash,
+}
+
+impl SortedPairwiseHash {
+    pub fn new(
+        left_child_data: Vec<F>,
+        left_child_hash: HashOut<F>,
+        right_child_data: Vec<F>,
+        right_child_hash: HashOut<F>,
+    ) -> Self {
+        Self {
+            pairwise_hash: PairwiseHash::new(
+                left_child_data,
+                left_child_hash,
+                right_child_data,
+                right_child_hash,
+            ),
+        }
+    }
+
+    /// Method `with_index`:
+    ///
+    ///     Sets the index of the parent node within its level, see `PairwiseHash::with_index`.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.pairwise_hash = self.pairwise_hash.with_index(index);
+        self
+    }
+}
+
+impl CircuitCompiler<C, F, D> for SortedPairwiseHash {
+    type Targets = <PairwiseHash as CircuitCompiler<C, F, D>>::Targets;
+    type OutTargets = <PairwiseHash as CircuitCompiler<C, F, D>>::OutTargets;
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        let (mut circuit_builder, targets, out_targets) = self.pairwise_hash.compile();
+
+        // the key of each leaf is its first element, sorted leaves can not be empty
+        let left_key_target = targets.0[0];
+        let right_key_target = targets.1[0];
+
+        circuit_builder.range_check(left_key_target, MAX_KEY_BITS);
+        circuit_builder.range_check(right_key_target, MAX_KEY_BITS);
+        assert_strictly_increasing(&mut circuit_builder, left_key_target, right_key_target);
+
+        circuit_builder.register_public_input(left_key_target);
+        circuit_builder.register_public_input(right_key_target);
+
+        (circuit_builder, targets, out_targets)
+    }
+}
+
+impl EvaluateFillCircuit<C, F, D> for SortedPairwiseHash {
+    type Value = HashOut<F>;
+
+    fn evaluate(&self) -> Self::Value {
+        self.pairwise_hash.evaluate()
+    }
+
+    fn fill(
+        &self,
+        targets: Self::Targets,
+        out_targets: Self::OutTargets,
+    ) -> Result<PartialWitness<F>, Error> {
+        self.pairwise_hash.fill(targets, out_targets)
+    }
+}
+
+impl Provable<F, C, D> for SortedPairwiseHash {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        let (circuit_data, targets, out_targets) = self.compile_and_build();
+        let partial_witness = self.fill(targets, out_targets)?;
+
+        let proof_with_pis = circuit_data.prove(partial_witness)?;
+
+        Ok(ProofData {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+}
+
+/// `SortedRecursivePairwiseHash` struct:
+///     A `RecursivePairwiseHash` over two sorted child proofs, whose circuit additionally enforces
+///     that the largest key of the left subtree is strictly smaller than the smallest key of the
+///     right subtree. The resulting subtree key range is exposed as public inputs.
+pub struct SortedRecursivePairwiseHash<'a> {
+    pub(crate) recursive_pairwise_hash: RecursivePairwiseHash<'a>,
+}
+
+impl<'a> SortedRecursivePairwiseHash<'a> {
+    pub fn new(
+        left_recursive_hash: RecursiveHash<'a>,
+        right_recursive_hash: RecursiveHash<'a>,
+    ) -> Self {
+        Self {
+            recursive_pairwise_hash: RecursivePairwiseHash::new(
+                left_recursive_hash,
+                right_recursive_hash,
+            ),
+        }
+    }
+}
+
+/// Builds the circuit of a sorted recursive node over children proved with the sorted circuits of
+/// `left_circuit_data` and `right_circuit_data`, see `compile_recursive_node`. The verifier data of
+/// the child circuits are constants of the node circuit, so that the key ranges it orders are those
+/// of sorted node proofs.
+///
+/// Panics if a child circuit does not expose the public inputs of a sorted node proof.
+pub(crate) fn compile_sorted_recursive_node(
+    circuit_config: &CircuitConfig,
+    left_circuit_data: &CircuitData<F, C, D>,
+    right_circuit_data: &CircuitData<F, C, D>,
+) -> (CircuitBuilder<F, D>, RecursiveNodeTargets, HashOutTarget) {
+    // both children should be sorted node proofs
+    assert!(
+        left_circuit_data.common.num_public_inputs == NUM_SORTED_PUBLIC_INPUTS
+            && right_circuit_data.common.num_public_inputs == NUM_SORTED_PUBLIC_INPUTS,
+        "child proofs should expose the {} public inputs of a sorted node proof",
+        NUM_SORTED_PUBLIC_INPUTS
+    );
+
+    let (mut circuit_builder, targets, out_targets) =
+        compile_recursive_node(circuit_config, left_circuit_data, right_circuit_data);
+    let left_public_inputs = targets.2.public_inputs.clone();
+    let right_public_inputs = targets.3.public_inputs.clone();
+
+    assert_strictly_increasing(
+        &mut circuit_builder,
+        left_public_inputs[MAX_KEY_PUBLIC_INPUT_INDEX],
+        right_public_inputs[MIN_KEY_PUBLIC_INPUT_INDEX],
+    );
+
+    circuit_builder.register_public_input(left_public_inputs[MIN_KEY_PUBLIC_INPUT_INDEX]);
+    circuit_builder.register_public_input(right_public_inputs[MAX_KEY_PUBLIC_INPUT_INDEX]);
+
+    (circuit_builder, targets, out_targets)
+}
+
+impl<'a> CircuitCompiler<C, F, D> for SortedRecursivePairwiseHash<'a> {
+    type Targets = RecursiveNodeTargets;
+    type OutTargets = HashOutTarget;
+
+    fn compile(&self) -> (CircuitBuilder<F, D>, Self::Targets, Self::OutTargets) {
+        compile_sorted_recursive_node(
+            &self.recursive_pairwise_hash.circuit_config,
+            &self
+                .recursive_pairwise_hash
+                .left_recursive_hash
+                .proof_data
+                .circuit_data,
+            &self
+                .recursive_pairwise_hash
+                .right_recursive_hash
+                .proof_data
+                .circuit_data,
+        )
+    }
+}
+
+impl<'a> EvaluateFillCircuit<C, F, D> for SortedRecursivePairwiseHash<'a> {
+    type Value = HashOut<F>;
+
+    fn evaluate(&self) -> Self::Value {
+        self.recursive_pairwise_hash.evaluate()
+    }
+
+    fn fill(
+        &self,
+        targets: Self::Targets,
+        out_targets: Self::OutTargets,
+    ) -> Result<PartialWitness<F>, Error> {
+        self.recursive_pairwise_hash.fill(targets, out_targets)
+    }
+}
+
+impl<'a> Provable<F, C, D> for SortedRecursivePairwiseHash<'a> {
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        ensure!(
+            [
+                &self.recursive_pairwise_hash.left_recursive_hash,
+                &self.recursive_pairwise_hash.right_recursive_hash
+            ]
+            .iter()
+            .all(|recursive_hash| recursive_hash
+                .proof_data
+                .circuit_data
+                .common
+                .num_public_inputs
+                == NUM_SORTED_PUBLIC_INPUTS),
+            "child proofs should expose the {} public inputs of a sorted node proof",
+            NUM_SORTED_PUBLIC_INPUTS
+        );
+        let (circuit_data, targets, out_targets) = self.compile_and_build();
+        let partial_witness = self.fill(targets, out_targets)?;
+
+        let proof_with_pis = circuit_data.prove(partial_witness)?;
+
+        Ok(ProofData {
+            proof_with_pis,
+            circuit_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        hash::poseidon::PoseidonHash, iop::witness::WitnessWrite, plonk::config::Hasher,
+    };
+
+    use super::*;
+    use crate::{
+        pairwise_hash::register_base_node_public_inputs, sorted_merkle_tree::SortedMerkleTree,
+    };
+
+    fn leaf(key: u64) -> (Vec<F>, HashOut<F>) {
+        let data = vec![F::from_canonical_u64(key), F::ONE];
+        let hash = PoseidonHash::hash_or_noop(&data);
+        (data, hash)
+    }
+
+    /// Proves a node of a foreign circuit, which exposes the public inputs of a sorted node proof of
+    /// height 1 without hashing nor ordering anything.
+    fn foreign_proof_data(hash: HashOut<F>, index: usize, keys: [u64; 2]) -> ProofData<F, C, D> {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
+        let mut partial_witness = PartialWitness::<F>::new();
+
+        let hash_targets = circuit_builder.add_virtual_hash();
+        circuit_builder.register_public_inputs(&hash_targets.elements);
+        let index_target = register_base_node_public_inputs(&mut circuit_builder, 1);
+        let key_targets = circuit_builder.add_virtual_targets(2);
+        circuit_builder.register_public_inputs(&key_targets);
+        partial_witness.set_hash_target(hash_targets, hash);
+        partial_witness.set_target(index_target, F::from_canonical_usize(index));
+        for (key_target, key) in key_targets.into_iter().zip(keys) {
+            partial_witness.set_target(key_target, F::from_canonical_u64(key));
+        }
+
+        let circuit_data = circuit_builder.build::<C>();
+        let proof_with_pis = circuit_data.prove(partial_witness).unwrap();
+        ProofData {
+            proof_with_pis,
+            circuit_data,
+        }
+    }
+
+    #[test]
+    fn test_sorted_pairwise_hash() {
+        let (left_data, left_hash) = leaf(1);
+        let (right_data, right_hash) = leaf(2);
+
+        let sorted_pairwise_hash =
+            SortedPairwiseHash::new(left_data, left_hash, right_data, right_hash);
+        let proof_data = sorted_pairwise_hash.proof().unwrap();
+
+        assert_eq!(
+            proof_data.proof_with_pis.public_inputs.len(),
+            NUM_SORTED_PUBLIC_INPUTS
+        );
+        assert_eq!(
+            proof_data.proof_with_pis.public_inputs[MIN_KEY_PUBLIC_INPUT_INDEX],
+            F::ONE
+        );
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sorted_pairwise_hash_fails_for_unsorted_keys() {
+        let (left_data, left_hash) = leaf(2);
+        let (right_data, right_hash) = leaf(2);
+
+        let sorted_pairwise_hash =
+            SortedPairwiseHash::new(left_data, left_hash, right_data, right_hash);
+        assert!(sorted_pairwise_hash.prove_and_verify().is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sorted_recursive_pairwise_hash_fails_for_overlapping_subtrees() {
+        let (data_1, hash_1) = leaf(1);
+        let (data_2, hash_2) = leaf(3);
+        let (data_3, hash_3) = leaf(2);
+        let (data_4, hash_4) = leaf(4);
+
+        let left_proof_data = SortedPairwiseHash::new(data_1, hash_1, data_2, hash_2)
+            .proof()
+            .unwrap();
+        let right_proof_data = SortedPairwiseHash::new(data_3, hash_3, data_4, hash_4)
+            .with_index(1)
+            .proof()
+            .unwrap();
+
+        let left_recursive_hash = RecursiveHash::new(
+            PoseidonHash::hash_or_noop(&[hash_1.elements, hash_2.elements].concat()),
+            &left_proof_data,
+        );
+        let right_recursive_hash = RecursiveHash::new(
+            PoseidonHash::hash_or_noop(&[hash_3.elements, hash_4.elements].concat()),
+            &right_proof_data,
+        );
+
+        let sorted_recursive_pairwise_hash =
+            SortedRecursivePairwiseHash::new(left_recursive_hash, right_recursive_hash);
+        assert!(sorted_recursive_pairwise_hash.prove_and_verify().is_err());
+    }
+
+    #[test]
+    fn test_sorted_recursive_pairwise_hash_rejects_foreign_children() {
+        // children claiming ordered key ranges over unsorted leaves
+        let hashes = [3, 1, 4, 2].map(|key| leaf(key).1);
+        let left_hash =
+            PoseidonHash::hash_or_noop(&[hashes[0].elements, hashes[1].elements].concat());
+        let right_hash =
+            PoseidonHash::hash_or_noop(&[hashes[2].elements, hashes[3].elements].concat());
+        let left_proof_data = foreign_proof_data(left_hash, 0, [1, 2]);
+        let right_proof_data = foreign_proof_data(right_hash, 1, [3, 4]);
+
+        let proof_data = SortedRecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        )
+        .proof()
+        .unwrap();
+
+        // the resulting root proof is not one of the sorted tree circuits
+        let root_verifier_data = SortedMerkleTree::root_verifier_data(4, 2).unwrap();
+        assert_ne!(
+            proof_data.circuit_data.verifier_only,
+            root_verifier_data.verifier_only
+        );
+        assert!(root_verifier_data
+            .verify(proof_data.proof_with_pis)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sorted_recursive_pairwise_hash_rejects_unsorted_node_proofs() {
+        let (data_1, hash_1) = leaf(1);
+        let (data_2, hash_2) = leaf(2);
+        let (data_3, hash_3) = leaf(3);
+        let (data_4, hash_4) = leaf(4);
+
+        let left_pairwise_hash = PairwiseHash::new(data_1, hash_1, data_2, hash_2);
+        let right_pairwise_hash = PairwiseHash::new(data_3, hash_3, data_4, hash_4).with_index(1);
+        let (left_hash, right_hash) = (
+            left_pairwise_hash.evaluate(),
+            right_pairwise_hash.evaluate(),
+        );
+        let left_proof_data = left_pairwise_hash.proof().unwrap();
+        let right_proof_data = right_pairwise_hash.proof().unwrap();
+
+        let sorted_recursive_pairwise_hash = SortedRecursivePairwiseHash::new(
+            RecursiveHash::new(left_hash, &left_proof_data),
+            RecursiveHash::new(right_hash, &right_proof_data),
+        );
+        assert!(sorted_recursive_pairwise_hash.proof().is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use anyhow::{anyhow, ensure, Error};
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::{
+        circuit_data::{CircuitConfig, CircuitData, VerifierCircuitData},
+        config::Hasher,
+    },
+};
+
+use crate::{
+    circuit_compiler::{CircuitCompiler, ProofData},
+    inclusion_proof::InclusionProof,
+    merkle_tree::MerkleTree,
+    provable::Provable,
+    recursive_hash::RecursiveHash,
+    scheduler::ProofScheduler,
+    sorted_hash::{
+        compile_sorted_recursive_node, SortedPairwiseHash, SortedRecursivePairwiseHash,
+        MAX_KEY_BITS, MIN_KEY_PUBLIC_INPUT_INDEX,
+    },
+    C, D, F,
+};
+
+/// Method `leaf_key`:
+///
+///     Returns the key of a sorted leaf, that is, the canonical value of its first element.
+pub fn leaf_key(leaf: &[F]) -> u64 {
+    leaf[0].to_canonical_u64()
+}
+
+/// `SortedMerkleTree` struct:
+///     A `MerkleTree` whose leaves are strictly increasing by key, where the key of a leaf is its
+///     first element. Its recursive proof additionally enforces the ordering of the leaves, so that
+///     the root proof certifies the key range `[min_key, max_key]` of the committed leaves.
+pub struct SortedMerkleTree {
+    pub(crate) merkle_tree: MerkleTree,
+}
+
+impl SortedMerkleTree {
+    /// Method `create`:
+    ///
+    ///     Sorts the input leaves by key and builds the underlying Merkle tree.
+    ///
+    /// Arguments:
+    ///
+    ///     data: A vector of leaves. Each leaf must be non empty, its first element being its key.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the SortedMerkleTree, or an Error if the number of leaves is not
+    ///     a power of two greater than one, a leaf is empty, a key does not fit in `MAX_KEY_BITS` bits,
+    ///     or two leaves share the same key.
+    pub fn create(mut data: Vec<Vec<F>>) -> Result<Self, Error> {
+        ensure!(
+            data.len().is_power_of_two() && data.len() > 1,
+            "the number of leaves should be a power of two greater than one"
+        );
+        ensure!(
+            data.iter().all(|leaf| !leaf.is_empty()),
+            "sorted leaves can not be empty"
+        );
+        ensure!(
+            data.iter().all(|leaf| leaf_key(leaf) < 1 << MAX_KEY_BITS),
+            "leaf keys should fit in {} bits",
+            MAX_KEY_BITS
+        );
+
+        data.sort_by_key(|leaf| leaf_key(leaf));
+        if let Some(pair) = data
+            .windows(2)
+            .find(|pair| leaf_key(&pair[0]) == leaf_key(&pair[1]))
+        {
+            return Err(anyhow!("duplicate leaf key {}", leaf_key(&pair[0])));
+        }
+
+        Ok(Self {
+            merkle_tree: MerkleTree::create(data),
+        })
+    }
+
+    /// Method `root`:
+    ///
+    ///     Returns the root of the sorted tree.
+    pub fn root(&self) -> HashOut<F> {
+        self.merkle_tree.root()
+    }
+
+    /// Method `keys`:
+    ///
+    ///     Returns the sorted keys of the tree leaves.
+    pub fn keys(&self) -> Vec<u64> {
+        self.merkle_tree
+            .leaves
+            .iter()
+            .map(|leaf| leaf_key(leaf))
+            .collect()
+    }
+
+    /// Method `root_verifier_data`:
+    ///
+    ///     Returns the verifier data of the root proofs of sorted trees of `num_leaves` leaves of
+    ///     `leaf_len` field elements, see `MerkleTree::root_verifier_data`. Since sorted node circuits
+    ///     embed the verifier data of the circuits of their children, the key range of a root proof
+    ///     is only certified by proofs verified against this verifier data.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the VerifierCircuitData, or an Error if the number of leaves is
+    ///     not a power of two greater than one, or the leaves are empty.
+    pub fn root_verifier_data(
+        num_leaves: usize,
+        leaf_len: usize,
+    ) -> Result<VerifierCircuitData<F, C, D>, Error> {
+        ensure!(
+            num_leaves.is_power_of_two() && num_leaves > 1,
+            "the number of leaves should be a power of two greater than one"
+        );
+        ensure!(leaf_len > 0, "sorted leaves can not be empty");
+
+        // circuits do not depend on the leaf values, nor on the node indices
+        let leaf = vec![F::ZERO; leaf_len];
+        let leaf_hash = PoseidonHash::hash_or_noop(&leaf);
+        let base_circuit_data = SortedPairwiseHash::new(leaf.clone(), leaf_hash, leaf, leaf_hash)
+            .compile_and_build()
+            .0;
+        let circuit_config = CircuitConfig::standard_recursion_zk_config();
+        let circuit_data = (1..num_leaves.ilog2()).fold(
+            base_circuit_data,
+            |child_circuit_data: CircuitData<F, C, D>, _| {
+                compile_sorted_recursive_node(
+                    &circuit_config,
+                    &child_circuit_data,
+                    &child_circuit_data,
+                )
+                .0
+                .build::<C>()
+            },
+        );
+        Ok(circuit_data.verifier_data())
+    }
+
+    /// Method `non_membership_proof`:
+    ///
+    ///     Generates a proof that `key` is not a leaf key of the tree, given by the authentication
+    ///     paths of the adjacent leaves enclosing it. A neighbor is omitted when `key` lies outside
+    ///     the key range of the tree.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the NonMembershipProof, or an Error if `key` belongs to the tree.
+    pub fn non_membership_proof(&self, key: u64) -> Result<NonMembershipProof, Error> {
+        let keys = self.keys();
+        let position = match keys.binary_search(&key) {
+            Ok(_) => return Err(anyhow!("key {} belongs to the tree", key)),
+            Err(position) => position,
+        };
+
+        let left_neighbor = if position > 0 {
+            Some(self.merkle_tree.inclusion_proof(position - 1)?)
+        } else {
+            None
+        };
+        let right_neighbor = if position < keys.len() {
+            Some(self.merkle_tree.inclusion_proof(position)?)
+        } else {
+            None
+        };
+
+        Ok(NonMembershipProof {
+            key,
+            left_neighbor,
+            right_neighbor,
+        })
+    }
+}
+
+/// `NonMembershipProof` struct:
+///     Attests that a key does not belong to a `SortedMerkleTree`, by exhibiting the two adjacent
+///     leaves whose keys enclose it.
+#[derive(Clone, Debug)]
+pub struct NonMembershipProof {
+    pub key: u64,
+    pub left_neighbor: Option<InclusionProof>,
+    pub right_neighbor: Option<InclusionProof>,
+}
+
+impl NonMembershipProof {
+    /// Method `verify`:
+    ///
+    ///     Verifies the non membership proof against the root of a sorted tree.
+    ///
+    /// Description:
+    ///
+    ///     Both neighbor paths should authenticate against the root, the neighbors should be adjacent
+    ///     leaves and their keys should strictly enclose `key`. A missing left (resp. right) neighbor is
+    ///     only accepted if the right (resp. left) neighbor is the first (resp. last) leaf of the tree.
+    pub fn verify(&self, root: HashOut<F>) -> Result<(), Error> {
+        if let Some(left_neighbor) = &self.left_neighbor {
+            left_neighbor.verify(root)?;
+            ensure!(
+                leaf_key(&left_neighbor.leaf) < self.key,
+                "left neighbor key should be smaller than the key"
+            );
+        }
+        if let Some(right_neighbor) = &self.right_neighbor {
+            right_neighbor.verify(root)?;
+            ensure!(
+                self.key < leaf_key(&right_neighbor.leaf),
+                "right neighbor key should be larger than the key"
+            );
+        }
+
+        match (&self.left_neighbor, &self.right_neighbor) {
+            (Some(left_neighbor), Some(right_neighbor)) => ensure!(
+                left_neighbor.leaf_index + 1 == right_neighbor.leaf_index,
+                "neighbors should be adjacent leaves"
+            ),
+            (None, Some(right_neighbor)) => ensure!(
+                right_neighbor.leaf_index == 0,
+                "right neighbor should be the first leaf"
+            ),
+            (Some(left_neighbor), None) => ensure!(
+                left_neighbor.leaf_index + 1 == left_neighbor.num_leaves(),
+                "left neighbor should be the last leaf"
+            ),
+            (None, None) => return Err(anyhow!("at least one neighbor is required")),
+        }
+
+        Ok(())
+    }
+}
+
+impl Provable<F, C, D> for SortedMerkleTree {
+    /// `Provable` trait method:
+    ///
+    ///     Generates a recursive proof for the sorted tree.
+    ///
+    /// Description:
+    ///
+    ///     Mirrors `MerkleTree::proof`, scheduling node proofs through the `ProofScheduler` and replacing
+    ///     `PairwiseHash` and `RecursivePairwiseHash` by their sorted counterparts. The root proof public inputs are the root hash, the
+    ///     height, number of leaves and index (0) of the tree, followed by the smallest and largest keys of the tree.
+    fn proof(self) -> Result<ProofData<F, C, D>, Error> {
+        let merkle_tree = &self.merkle_tree;
+        let leaves = &merkle_tree.leaves;
+        let num_leaves = leaves.len();
+
+        let root_proof_data = ProofScheduler::default().prove_tree(
+            num_leaves.ilog2() as usize,
+            1,
+            |index| {
+                SortedPairwiseHash::new(
+                    leaves[2 * index].clone(),
+                    merkle_tree.digests[2 * index],
+                    leaves[2 * index + 1].clone(),
+                    merkle_tree.digests[2 * index + 1],
+                )
+                .with_index(index)
+                .proof()
+            },
+            |height, index, left_proof_data, right_proof_data| {
+                let left_recursive_hash =
+                    RecursiveHash::new(merkle_tree.digest(height - 1, 2 * index), left_proof_data);
+                let right_recursive_hash = RecursiveHash::new(
+                    merkle_tree.digest(height - 1, 2 * index + 1),
+                    right_proof_data,
+                );
+                SortedRecursivePairwiseHash::new(left_recursive_hash, right_recursive_hash).proof()
+            },
+        )?;
+
+        ensure!(
+            root_proof_data.proof_with_pis.public_inputs[..4] == self.root().elements,
+            "root proof does not commit to the tree root"
+        );
+        ensure!(
+            root_proof_data.proof_with_pis.public_inputs[MIN_KEY_PUBLIC_INPUT_INDEX..]
+                == [
+                    F::from_canonical_u64(leaf_key(&leaves[0])),
+                    F::from_canonical_u64(leaf_key(&leaves[num_leaves - 1]))
+                ],
+            "root proof does not commit to the tree key range"
+        );
+
+        Ok(root_proof_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(keys: &[u64]) -> Vec<Vec<F>> {
+        keys.iter()
+            .map(|key| vec![F::from_canonical_u64(*key), F::from_canonical_u64(key * 10)])
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_merkle_tree_sorts_leaves() {
+        let sorted_merkle_tree = SortedMerkleTree::create(leaves(&[7, 3, 11, 5])).unwrap();
+        assert_eq!(sorted_merkle_tree.keys(), vec![3, 5, 7, 11]);
+        assert_eq!(
+            sorted_merkle_tree.root(),
+            MerkleTree::create(leaves(&[3, 5, 7, 11])).root()
+        );
+    }
+
+    #[test]
+    fn test_sorted_merkle_tree_rejects_invalid_leaves() {
+        assert!(SortedMerkleTree::create(leaves(&[1, 2, 2, 3])).is_err());
+        assert!(SortedMerkleTree::create(leaves(&[1, 2, 3])).is_err());
+        assert!(SortedMerkleTree::create(leaves(&[1, 2, 3, 1 << MAX_KEY_BITS])).is_err());
+        assert!(SortedMerkleTree::create(vec![vec![], vec![F::ONE]]).is_err());
+    }
+
+    #[test]
+    fn test_non_membership_proof() {
+        let sorted_merkle_tree = SortedMerkleTree::create(leaves(&[3, 5, 7, 11])).unwrap();
+        let root = sorted_merkle_tree.root();
+
+        for key in [0, 4, 6, 9, 12] {
+            let non_membership_proof = sorted_merkle_tree.non_membership_proof(key).unwrap();
+            assert!(non_membership_proof.verify(root).is_ok());
+        }
+
+        assert!(sorted_merkle_tree.non_membership_proof(5).is_err());
+    }
+
+    #[test]
+    fn test_non_membership_proof_fails_for_non_adjacent_neighbors() {
+        let sorted_merkle_tree = SortedMerkleTree::create(leaves(&[3, 5, 7, 11])).unwrap();
+        let root = sorted_merkle_tree.root();
+
+        let mut non_membership_proof = sorted_merkle_tree.non_membership_proof(6).unwrap();
+        non_membership_proof.key = 8;
+        non_membership_proof.right_neighbor =
+            Some(sorted_merkle_tree.merkle_tree.inclusion_proof(3).unwrap());
+        assert!(non_membership_proof.verify(root).is_err());
+
+        let mut non_membership_proof = sorted_merkle_tree.non_membership_proof(12).unwrap();
+        non_membership_proof.left_neighbor =
+            Some(sorted_merkle_tree.merkle_tree.inclusion_proof(2).unwrap());
+        assert!(non_membership_proof.verify(root).is_err());
+    }
+
+    #[test]
+    fn test_sorted_merkle_tree_generate_proof() {
+        let sorted_merkle_tree = SortedMerkleTree::create(leaves(&[7, 3, 11, 5])).unwrap();
+        let proof_data = sorted_merkle_tree.proof().unwrap();
+
+        let root_verifier_data = SortedMerkleTree::root_verifier_data(4, 2).unwrap();
+        assert_eq!(
+            root_verifier_data.verifier_only,
+            proof_data.circuit_data.verifier_only
+        );
+        assert!(root_verifier_data.verify(proof_data.proof_with_pis).is_ok());
+
+        assert!(SortedMerkleTree::root_verifier_data(4, 0).is_err());
+        assert!(SortedMerkleTree::root_verifier_data(6, 2).is_err());
+    }
+}
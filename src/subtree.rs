@@ -0,0 +1,326 @@
+use std::{ops::Range, sync::Mutex};
+
+use anyhow::{anyhow, ensure, Error};
+use plonky2::{
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::config::Hasher,
+};
+
+use crate::{
+    circuit_compiler::ProofData,
+    compression::ProofCompressor,
+    merkle_tree::MerkleTree,
+    provable::Provable,
+    proving_options::ProvingOptions,
+    recursive_hash::{RecursiveHash, RecursivePairwiseHash},
+    C, D, F,
+};
+
+/// `SubtreeProof` struct:
+///     The proof of a subtree of a larger Merkle tree, covering a contiguous, aligned range of leaves.
+///
+/// Fields:
+///
+///     leaf_range: The range of leaves of the full tree covered by the subtree. Its length is a power
+///         of two, and its start a multiple of its length.
+///     root: The root of the subtree.
+///     proof_data: The proof of the subtree, whose public inputs are `root`.
+pub struct SubtreeProof {
+    pub leaf_range: Range<usize>,
+    pub root: HashOut<F>,
+    pub proof_data: ProofData<F, C, D>,
+}
+
+impl SubtreeProof {
+    /// Method `prove`:
+    ///
+    ///     Proves the subtree whose leaves are `leaves`, starting at leaf `leaf_offset` of the full tree.
+    ///     Only the leaves of the subtree are needed, so that subtrees can be proved independently.
+    ///     Subtree proofs are not compressed, as they are the children of the upper levels: `compress`
+    ///     is applied to the root proof by `stitch_subtree_proofs`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the SubtreeProof or an Error if the leaf range is not aligned or
+    ///     the proof generation fails.
+    pub fn prove(
+        leaf_offset: usize,
+        leaves: Vec<Vec<F>>,
+        proving_options: &ProvingOptions,
+    ) -> Result<Self, Error> {
+        let leaf_end = leaf_offset
+            .checked_add(leaves.len())
+            .ok_or_else(|| anyhow!("the leaf range starting at {} overflows", leaf_offset))?;
+        let leaf_range = leaf_offset..leaf_end;
+        check_leaf_range(&leaf_range)?;
+
+        let merkle_tree = MerkleTree::create(leaves);
+        let root = merkle_tree.root();
+        let subtree_proving_options = ProvingOptions {
+            compress: false,
+            ..proving_options.clone()
+        };
+        let proof_data = merkle_tree.proof_with(&subtree_proving_options, None, leaf_offset)?;
+
+        Ok(Self {
+            leaf_range,
+            root,
+            proof_data,
+        })
+    }
+
+    /// Method `verify`:
+    ///
+    ///     Verifies the subtree proof, and checks that it commits to `root` and to `leaf_range`.
+    pub fn verify(&self) -> Result<(), Error> {
+        check_leaf_range(&self.leaf_range)?;
+        ensure!(
+            self.proof_data.root() == self.root,
+            "the subtree proof of leaves {:?} does not commit to its root",
+            self.leaf_range
+        );
+        ensure!(
+            self.proof_data.leaf_range() == self.leaf_range,
+            "the subtree proof of leaves {:?} commits to leaves {:?}",
+            self.leaf_range,
+            self.proof_data.leaf_range()
+        );
+        self.proof_data
+            .circuit_data
+            .verify(self.proof_data.proof_with_pis.clone())
+    }
+}
+
+fn check_leaf_range(leaf_range: &Range<usize>) -> Result<(), Error> {
+    let num_leaves = leaf_range.len();
+    ensure!(
+        num_leaves.is_power_of_two() && num_leaves > 1,
+        "a subtree should have a power of two number of leaves, greater than one"
+    );
+    ensure!(
+        leaf_range.start & (num_leaves - 1) == 0,
+        "the leaf range {:?} of a subtree should be aligned on its length",
+        leaf_range
+    );
+    Ok(())
+}
+
+impl MerkleTree {
+    /// Method `subtree_proof`:
+    ///
+    ///     Proves the subtree of the constructed Merkle tree covering `leaf_range`, see
+    ///     `SubtreeProof::prove`.
+    pub fn subtree_proof(
+        &self,
+        leaf_range: Range<usize>,
+        proving_options: &ProvingOptions,
+    ) -> Result<SubtreeProof, Error> {
+        check_leaf_range(&leaf_range)?;
+        ensure!(
+            leaf_range.end <= self.leaves.len(),
+            "the leaf range {:?} exceeds the {} leaves of the tree",
+            leaf_range,
+            self.leaves.len()
+        );
+        SubtreeProof::prove(
+            leaf_range.start,
+            self.leaves[leaf_range].to_vec(),
+            proving_options,
+        )
+    }
+}
+
+/// Function `stitch_subtree_proofs`:
+///
+///     Combines the proofs of subtrees covering all the leaves of a tree into a proof of its root,
+///     by proving the remaining upper levels with `RecursivePairwiseHash`.
+///
+/// Arguments:
+///
+///     subtree_proofs: The proofs of subtrees of equal size, covering contiguous leaf ranges starting
+///         at leaf 0, in any order. Their number should be a power of two.
+///     proving_options: The options upper nodes are proved with. Base proof options (e.g. the leaf
+///         chunk size) are ignored. With `compress`, the root proof is compressed.
+///
+/// Returns:
+///
+///     Returns a Result containing the root hash and the ProofData of the full tree, or an Error if
+///     the subtree proofs are invalid, or do not tile the tree.
+pub fn stitch_subtree_proofs(
+    mut subtree_proofs: Vec<SubtreeProof>,
+    proving_options: &ProvingOptions,
+) -> Result<(HashOut<F>, ProofData<F, C, D>), Error> {
+    ensure!(
+        subtree_proofs.len().is_power_of_two(),
+        "the number of subtree proofs should be a power of two"
+    );
+    subtree_proofs.sort_by_key(|subtree_proof| subtree_proof.leaf_range.start);
+
+    let subtree_num_leaves = subtree_proofs[0].leaf_range.len();
+    for (index, subtree_proof) in subtree_proofs.iter().enumerate() {
+        ensure!(
+            subtree_proof.leaf_range
+                == (index * subtree_num_leaves..(index + 1) * subtree_num_leaves),
+            "the subtree proofs should cover contiguous leaf ranges of equal length, starting at 0"
+        );
+        subtree_proof.verify()?;
+    }
+
+    // digests of the upper levels, the subtree roots being at height 0
+    let mut digests = vec![subtree_proofs
+        .iter()
+        .map(|subtree_proof| subtree_proof.root)
+        .collect::<Vec<_>>()];
+    while digests.last().unwrap().len() > 1 {
+        let level_digests = digests
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| PoseidonHash::hash_or_noop(&[pair[0].elements, pair[1].elements].concat()))
+            .collect();
+        digests.push(level_digests);
+    }
+    let root = digests.last().unwrap()[0];
+
+    let upper_height = digests.len() - 1;
+    let proof_data = if upper_height == 0 {
+        subtree_proofs.pop().unwrap().proof_data
+    } else {
+        let circuit_config = proving_options.circuit_config();
+        let scheduler = proving_options.scheduler()?;
+        let subtree_proofs_data = subtree_proofs
+            .into_iter()
+            .map(|subtree_proof| Mutex::new(Some(subtree_proof.proof_data)))
+            .collect::<Vec<_>>();
+
+        scheduler.prove_tree(
+            upper_height,
+            0,
+            |index| {
+                subtree_proofs_data[index]
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .ok_or_else(|| anyhow!("the subtree proof {} was already used", index))
+            },
+            |height, index, left_proof_data, right_proof_data| {
+                RecursivePairwiseHash::new(
+                    RecursiveHash::new(digests[height - 1][2 * index], left_proof_data),
+                    RecursiveHash::new(digests[height - 1][2 * index + 1], right_proof_data),
+                )
+                .with_circuit_config(circuit_config.clone())
+                .proof()
+            },
+        )?
+    };
+
+    if proving_options.compress {
+        let (compressed_proof_data, _) = ProofCompressor::default().compress(proof_data)?;
+        return Ok((root, compressed_proof_data));
+    }
+    Ok((root, proof_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::types::Field;
+
+    use super::*;
+    use crate::compression::compression_config;
+
+    #[test]
+    fn test_stitch_subtree_proofs() {
+        let merkle_tree_leaves = (0..8).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let proving_options = ProvingOptions::default();
+
+        // proved in reverse order on purpose, stitching sorts subtree proofs
+        let subtree_proofs = vec![
+            merkle_tree.subtree_proof(4..8, &proving_options).unwrap(),
+            merkle_tree.subtree_proof(0..4, &proving_options).unwrap(),
+        ];
+        assert_eq!(subtree_proofs[0].root, merkle_tree.digest(2, 1));
+        assert_eq!(subtree_proofs[0].proof_data.leaf_range(), 4..8);
+
+        let (root, proof_data) = stitch_subtree_proofs(subtree_proofs, &proving_options).unwrap();
+        assert_eq!(root, merkle_tree.root());
+        assert_eq!(proof_data.root(), root);
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_stitch_compressed_subtree_proofs() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let proving_options = ProvingOptions {
+            compress: true,
+            ..ProvingOptions::default()
+        };
+
+        // subtree proofs are left uncompressed, only the stitched root proof is compressed
+        let subtree_proofs = vec![
+            merkle_tree.subtree_proof(0..2, &proving_options).unwrap(),
+            merkle_tree.subtree_proof(2..4, &proving_options).unwrap(),
+        ];
+        let compressed_fri_config = compression_config().fri_config;
+        for subtree_proof in &subtree_proofs {
+            assert_ne!(
+                subtree_proof
+                    .proof_data
+                    .circuit_data
+                    .common
+                    .config
+                    .fri_config,
+                compressed_fri_config
+            );
+        }
+
+        let (root, proof_data) = stitch_subtree_proofs(subtree_proofs, &proving_options).unwrap();
+        assert_eq!(proof_data.root(), root);
+        assert_eq!(
+            proof_data.circuit_data.common.config.fri_config,
+            compressed_fri_config
+        );
+        assert!(proof_data
+            .circuit_data
+            .verify(proof_data.proof_with_pis)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_subtree_proofs() {
+        let merkle_tree_leaves = (0..8).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let proving_options = ProvingOptions::default();
+
+        // unaligned, reversed and out of range leaf ranges
+        assert!(merkle_tree.subtree_proof(2..6, &proving_options).is_err());
+        let reversed_leaf_range = Range { start: 6, end: 4 };
+        assert!(merkle_tree
+            .subtree_proof(reversed_leaf_range, &proving_options)
+            .is_err());
+        assert!(merkle_tree.subtree_proof(8..10, &proving_options).is_err());
+        let leaves = merkle_tree.leaves[..2].to_vec();
+        assert!(SubtreeProof::prove(usize::MAX - 1, leaves, &proving_options).is_err());
+
+        // subtrees not covering the tree from leaf 0
+        let subtree_proofs = vec![
+            merkle_tree.subtree_proof(2..4, &proving_options).unwrap(),
+            merkle_tree.subtree_proof(4..6, &proving_options).unwrap(),
+        ];
+        assert!(stitch_subtree_proofs(subtree_proofs, &proving_options).is_err());
+
+        // a subtree proof not matching its claimed root
+        let mut subtree_proof = merkle_tree.subtree_proof(0..2, &proving_options).unwrap();
+        subtree_proof.root = merkle_tree.digest(1, 1);
+        assert!(subtree_proof.verify().is_err());
+
+        // a subtree proof not matching its claimed leaf range
+        let mut subtree_proof = merkle_tree.subtree_proof(2..4, &proving_options).unwrap();
+        subtree_proof.leaf_range = 4..6;
+        assert!(subtree_proof.verify().is_err());
+    }
+}
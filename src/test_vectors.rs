@@ -0,0 +1,204 @@
+use plonky2::field::types::{Field, Field64};
+use serde_json::{json, Value};
+
+use crate::{
+    json::{element_to_json, to_envelope},
+    leaf_format::hash_to_hex,
+    merkle_tree::MerkleTree,
+    F,
+};
+
+/// Envelope type of the test vectors emitted by `test_vectors`.
+pub const TEST_VECTORS_TYPE: &str = "test_vectors";
+
+/// Numbers of leaves of the trees of `default_test_vectors`.
+pub const DEFAULT_TEST_VECTOR_NUM_LEAVES: [usize; 6] = [2, 4, 8, 16, 32, 64];
+
+/// Leaf lengths of the trees of `default_test_vectors`, around the boundaries of
+/// `PoseidonHash::hash_or_noop`: leaves of at most 4 elements are not hashed, and longer leaves are
+/// absorbed 8 elements at a time.
+pub const DEFAULT_TEST_VECTOR_LEAF_LENS: [usize; 7] = [0, 1, 4, 5, 8, 9, 16];
+
+/// Function `test_vector_leaves`:
+///
+///     Generates `num_leaves` leaves of `leaf_len` field elements, deterministically from the tree
+///     shape, with a SplitMix64 generator: elements are spread over the whole field, so that other
+///     implementations are checked on large elements too.
+pub fn test_vector_leaves(num_leaves: usize, leaf_len: usize) -> Vec<Vec<F>> {
+    let mut state = ((num_leaves as u64) << 32) | leaf_len as u64;
+    let mut next_element = || loop {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        // rejecting non canonical values keeps the elements uniform
+        if z < F::ORDER {
+            return F::from_canonical_u64(z);
+        }
+    };
+    (0..num_leaves)
+        .map(|_| (0..leaf_len).map(|_| next_element()).collect())
+        .collect()
+}
+
+impl MerkleTree {
+    /// Method `test_vector`:
+    ///
+    ///     Exports the tree as a test vector for other implementations, i.e.
+    ///     `{"num_leaves": 4, "leaf_len": 2, "leaves": [..], "digests": [..], "root": "0x...",
+    ///     "inclusion_proofs": [..]}`.
+    ///
+    /// Description:
+    ///
+    ///     Leaf elements are decimal strings (see `element_to_json`) and hashes hexadecimal strings
+    ///     (see `hash_to_hex`). `digests` lists the digests height by height, from the leaf hashes at
+    ///     height 0 up to the root. `inclusion_proofs` lists the authentication path of every leaf, as
+    ///     the data of `InclusionProof::to_json`.
+    pub fn test_vector(&self) -> Value {
+        let num_leaves = self.leaves.len();
+        let height = num_leaves.ilog2() as usize;
+        let digests: Vec<Vec<String>> = (0..=height)
+            .map(|h| {
+                (0..num_leaves >> h)
+                    .map(|index| hash_to_hex(self.digest(h, index)))
+                    .collect()
+            })
+            .collect();
+        let inclusion_proofs: Vec<Value> = (0..num_leaves)
+            .map(|leaf_index| {
+                self.inclusion_proof(leaf_index)
+                    .expect("the leaf index is in range")
+                    .to_json()["data"]
+                    .take()
+            })
+            .collect();
+
+        json!({
+            "num_leaves": num_leaves,
+            "leaf_len": self.leaves.first().map_or(0, Vec::len),
+            "leaves": self
+                .leaves
+                .iter()
+                .map(|leaf| leaf.iter().copied().map(element_to_json).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            "digests": digests,
+            "root": hash_to_hex(self.root()),
+            "inclusion_proofs": inclusion_proofs,
+        })
+    }
+}
+
+/// Function `test_vectors`:
+///
+///     Builds a tree of `test_vector_leaves` for every pair of number of leaves and leaf length, and
+///     exports them with `MerkleTree::test_vector`, as a `test_vectors` JSON envelope (see
+///     `to_envelope`) whose data is `{"trees": [..]}`. The output only depends on the arguments.
+///
+/// Panics:
+///
+///     Panics if a number of leaves is not a power of two greater than one.
+pub fn test_vectors(num_leaves: &[usize], leaf_lens: &[usize]) -> Value {
+    let trees: Vec<Value> = num_leaves
+        .iter()
+        .flat_map(|&num_leaves| {
+            assert!(
+                num_leaves.is_power_of_two() && num_leaves > 1,
+                "the number of leaves should be a power of two, greater than one"
+            );
+            leaf_lens.iter().map(move |&leaf_len| {
+                MerkleTree::create(test_vector_leaves(num_leaves, leaf_len)).test_vector()
+            })
+        })
+        .collect();
+    to_envelope(TEST_VECTORS_TYPE, json!({ "trees": trees }))
+}
+
+/// Function `default_test_vectors`:
+///
+///     Returns the `test_vectors` of the trees with `DEFAULT_TEST_VECTOR_NUM_LEAVES` leaves of
+///     `DEFAULT_TEST_VECTOR_LEAF_LENS` elements.
+pub fn default_test_vectors() -> Value {
+    test_vectors(
+        &DEFAULT_TEST_VECTOR_NUM_LEAVES,
+        &DEFAULT_TEST_VECTOR_LEAF_LENS,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{hash::poseidon::PoseidonHash, plonk::config::Hasher};
+
+    use super::*;
+    use crate::{
+        inclusion_proof::InclusionProof,
+        json::{element_from_json, from_envelope, INCLUSION_PROOF_TYPE},
+        leaf_format::hash_from_hex,
+    };
+
+    #[test]
+    fn test_test_vectors_are_deterministic() {
+        assert_eq!(test_vector_leaves(8, 5), test_vector_leaves(8, 5));
+        assert_ne!(test_vector_leaves(8, 5), test_vector_leaves(8, 4));
+        assert_eq!(
+            test_vectors(&[2, 4], &[1, 9]),
+            test_vectors(&[2, 4], &[1, 9])
+        );
+    }
+
+    #[test]
+    fn test_test_vectors_match_merkle_tree() {
+        let vectors = test_vectors(&[2, 8], &[0, 4, 9]);
+        let trees = from_envelope(TEST_VECTORS_TYPE, &vectors).unwrap()["trees"]
+            .as_array()
+            .unwrap();
+        assert_eq!(trees.len(), 6);
+
+        for tree in trees {
+            let hash = |value: &Value| hash_from_hex(value.as_str().unwrap()).unwrap();
+            let leaves: Vec<Vec<F>> = tree["leaves"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|leaf| {
+                    leaf.as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|element| element_from_json(element).unwrap())
+                        .collect()
+                })
+                .collect();
+            assert_eq!(leaves.len(), tree["num_leaves"].as_u64().unwrap() as usize);
+            let root = MerkleTree::create(leaves.clone()).root();
+            assert_eq!(hash(&tree["root"]), root);
+
+            // recompute every level from the one below
+            let digests = tree["digests"].as_array().unwrap();
+            let leaf_hashes: Vec<Value> = leaves
+                .iter()
+                .map(|leaf| Value::from(hash_to_hex(PoseidonHash::hash_or_noop(leaf))))
+                .collect();
+            assert_eq!(digests[0].as_array().unwrap(), &leaf_hashes);
+            for level in digests.windows(2) {
+                let children = level[0].as_array().unwrap();
+                let parents = level[1].as_array().unwrap();
+                assert_eq!(children.len(), 2 * parents.len());
+                for (pair, parent) in children.chunks(2).zip(parents) {
+                    let parent_hash = PoseidonHash::hash_or_noop(
+                        &[hash(&pair[0]).elements, hash(&pair[1]).elements].concat(),
+                    );
+                    assert_eq!(hash(parent), parent_hash);
+                }
+            }
+            assert_eq!(digests.last().unwrap()[0], tree["root"]);
+
+            for inclusion_proof in tree["inclusion_proofs"].as_array().unwrap() {
+                let envelope = to_envelope(INCLUSION_PROOF_TYPE, inclusion_proof.clone());
+                assert!(InclusionProof::from_json(&envelope)
+                    .unwrap()
+                    .verify(root)
+                    .is_ok());
+            }
+        }
+    }
+}
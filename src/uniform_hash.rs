@@ -0,0 +1,516 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::{anyhow, ensure, Error};
+use plonky2::{
+    gates::{constant::ConstantGate, gate::GateRef, noop::NoopGate},
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitTarget},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+    recursion::{
+        cyclic_recursion::check_cyclic_proof_verifier_data, dummy_circuit::cyclic_base_proof,
+    },
+};
+
+use crate::{merkle_tree::MerkleTree, scheduler::ProofScheduler, C, D, F};
+
+/// The uniform circuit has `2^UNIFORM_CIRCUIT_DEGREE_BITS` gates, which fits the verification of
+/// two proofs of the circuit itself.
+const UNIFORM_CIRCUIT_DEGREE_BITS: usize = 14;
+
+/// Returns the common data of a cyclic circuit of degree `2^degree_bits`, verifying `num_proofs`
+/// proofs of itself. It is needed to build the cyclic circuit, since the circuit verifies its own
+/// proofs. The number of public inputs should be set by the caller.
+///
+/// Returns an Error if the verification of `num_proofs` proofs does not fit the degree with
+/// `circuit_config`.
+pub(crate) fn cyclic_common_data(
+    circuit_config: &CircuitConfig,
+    num_proofs: usize,
+    degree_bits: usize,
+) -> Result<CommonCircuitData<F, D>, Error> {
+    let circuit_builder = CircuitBuilder::<F, D>::new(circuit_config.clone());
+    let circuit_data = circuit_builder.build::<C>();
+
+    let mut circuit_builder = CircuitBuilder::<F, D>::new(circuit_config.clone());
+    let proof_with_pis_targets = circuit_builder.add_virtual_proof_with_pis(&circuit_data.common);
+    let verifier_data_targets =
+        circuit_builder.add_virtual_verifier_data(circuit_config.fri_config.cap_height);
+    circuit_builder.verify_proof::<C>(
+        &proof_with_pis_targets,
+        &verifier_data_targets,
+        &circuit_data.common,
+    );
+    let circuit_data = circuit_builder.build::<C>();
+
+    let mut circuit_builder = CircuitBuilder::<F, D>::new(circuit_config.clone());
+    for _ in 0..num_proofs {
+        let proof_with_pis_targets =
+            circuit_builder.add_virtual_proof_with_pis(&circuit_data.common);
+        let verifier_data_targets =
+            circuit_builder.add_virtual_verifier_data(circuit_config.fri_config.cap_height);
+        circuit_builder.verify_proof::<C>(
+            &proof_with_pis_targets,
+            &verifier_data_targets,
+            &circuit_data.common,
+        );
+    }
+    // the dummy circuits standing for missing proofs use these gates
+    circuit_builder.add_gate_to_gate_set(GateRef::new(NoopGate));
+    circuit_builder.add_gate_to_gate_set(GateRef::new(ConstantGate::new(
+        circuit_config.num_constants,
+    )));
+    // the gates added at build time (e.g. for public inputs) push the degree to the next power of two
+    while circuit_builder.num_gates() < 1 << (degree_bits - 1) {
+        circuit_builder.add_gate(NoopGate, vec![]);
+    }
+    let common_data = circuit_builder.build::<C>().common;
+    ensure!(
+        common_data.degree_bits() == degree_bits,
+        "verifying {} cyclic proofs requires a circuit of degree 2^{}, instead of 2^{}",
+        num_proofs,
+        common_data.degree_bits(),
+        degree_bits
+    );
+    Ok(common_data)
+}
+
+/// Returns `if condition { x } else { y }`.
+pub(crate) fn select_hash(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    condition: BoolTarget,
+    x: HashOutTarget,
+    y: HashOutTarget,
+) -> HashOutTarget {
+    HashOutTarget {
+        elements: std::array::from_fn(|i| {
+            circuit_builder.select(condition, x.elements[i], y.elements[i])
+        }),
+    }
+}
+
+struct UniformTargets {
+    is_internal: BoolTarget,
+    left_leaf_targets: Vec<Target>,
+    right_leaf_targets: Vec<Target>,
+    left_proof_with_pis_targets: ProofWithPublicInputsTarget<D>,
+    right_proof_with_pis_targets: ProofWithPublicInputsTarget<D>,
+    verifier_data_targets: VerifierCircuitTarget,
+}
+
+/// `UniformTreeCircuit` struct:
+///     A single circuit proving every node of a Merkle tree, whatever its height, so that a single
+///     verifier key is valid for trees of any height.
+///
+/// Description:
+///
+///     Whereas `RecursivePairwiseHash` builds a new circuit from the circuit data of each child, the
+///     uniform circuit relies on cyclic recursion: it conditionally verifies two proofs of itself.
+///
+///     - For nodes right above the leaves (`is_internal` false), the child hashes are computed from
+///       the leaf data, and dummy child proofs are verified instead.
+///     - For higher nodes (`is_internal` true), the child hashes are the public inputs of the child
+///       proofs, which are verified.
+///
+///     The public inputs are the node hash, followed by the verifier data of the uniform circuit.
+///     The verifier data public inputs of child proofs are connected to those of the parent, so that
+///     all the proofs of a tree are proofs of the uniform circuit. Verifiers should use
+///     `UniformTreeCircuit::verify`, which also checks these public inputs against the actual verifier
+///     data.
+///
+///     The circuit hashes leaves of a fixed length, so that its verifier key only depends on
+///     `leaf_len` (and the circuit configuration), not on the tree height.
+///
+///     Unlike the default tree proofs, uniform proofs are not zero knowledge, since plonky2 cyclic
+///     recursion does not support blinding.
+pub struct UniformTreeCircuit {
+    leaf_len: usize,
+    circuit_data: CircuitData<F, C, D>,
+    targets: UniformTargets,
+    base_proof_with_pis: ProofWithPublicInputs<F, C, D>,
+}
+
+impl UniformTreeCircuit {
+    /// Method `new`:
+    ///
+    ///     Builds the uniform circuit for leaves of `leaf_len` field elements, with the
+    ///     `standard_recursion_config` configuration.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the UniformTreeCircuit, or an Error if the circuit can not be built,
+    ///     see `UniformTreeCircuit::with_circuit_config`.
+    pub fn new(leaf_len: usize) -> Result<Self, Error> {
+        Self::with_circuit_config(leaf_len, CircuitConfig::standard_recursion_config())
+    }
+
+    /// Method `with_circuit_config`:
+    ///
+    ///     Builds the uniform circuit for leaves of `leaf_len` field elements, with `circuit_config`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the UniformTreeCircuit, or an Error if `circuit_config` enables
+    ///     zero knowledge, which plonky2 cyclic recursion does not support, or if the circuit, which
+    ///     verifies two proofs of itself, does not fit `2^UNIFORM_CIRCUIT_DEGREE_BITS` gates with
+    ///     `circuit_config`.
+    pub fn with_circuit_config(
+        leaf_len: usize,
+        circuit_config: CircuitConfig,
+    ) -> Result<Self, Error> {
+        ensure!(
+            !circuit_config.zero_knowledge,
+            "cyclic recursion does not support zero knowledge circuits"
+        );
+        let mut common_data = cyclic_common_data(&circuit_config, 2, UNIFORM_CIRCUIT_DEGREE_BITS)?;
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(circuit_config);
+
+        let is_internal = circuit_builder.add_virtual_bool_target_safe();
+        let left_leaf_targets = circuit_builder.add_virtual_targets(leaf_len);
+        let right_leaf_targets = circuit_builder.add_virtual_targets(leaf_len);
+
+        let parent_hash_targets = circuit_builder.add_virtual_hash();
+
+        // register public inputs, the verifier data public inputs should come last
+        circuit_builder.register_public_inputs(&parent_hash_targets.elements);
+        let verifier_data_targets = circuit_builder.add_verifier_data_public_inputs();
+        common_data.num_public_inputs = circuit_builder.num_public_inputs();
+
+        let left_proof_with_pis_targets = circuit_builder.add_virtual_proof_with_pis(&common_data);
+        let right_proof_with_pis_targets = circuit_builder.add_virtual_proof_with_pis(&common_data);
+
+        // child hashes are either the leaf hashes, or the node hashes of the child proofs
+        let left_leaf_hash_targets =
+            circuit_builder.hash_or_noop::<PoseidonHash>(left_leaf_targets.clone());
+        let right_leaf_hash_targets =
+            circuit_builder.hash_or_noop::<PoseidonHash>(right_leaf_targets.clone());
+        let left_node_hash_targets =
+            HashOutTarget::try_from(&left_proof_with_pis_targets.public_inputs[0..4]).unwrap();
+        let right_node_hash_targets =
+            HashOutTarget::try_from(&right_proof_with_pis_targets.public_inputs[0..4]).unwrap();
+        let left_hash_targets = select_hash(
+            &mut circuit_builder,
+            is_internal,
+            left_node_hash_targets,
+            left_leaf_hash_targets,
+        );
+        let right_hash_targets = select_hash(
+            &mut circuit_builder,
+            is_internal,
+            right_node_hash_targets,
+            right_leaf_hash_targets,
+        );
+
+        let should_be_parent_hash_targets = circuit_builder.hash_or_noop::<PoseidonHash>(
+            [left_hash_targets.elements, right_hash_targets.elements].concat(),
+        );
+        circuit_builder.connect_hashes(should_be_parent_hash_targets, parent_hash_targets);
+
+        for proof_with_pis_targets in [&left_proof_with_pis_targets, &right_proof_with_pis_targets]
+        {
+            circuit_builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
+                is_internal,
+                proof_with_pis_targets,
+                &common_data,
+            )?;
+        }
+
+        // plonky2 asserts that the built circuit matches the cyclic common data, which fails when
+        // the circuit outgrows the common data, e.g. for long leaves
+        let circuit_data = panic::catch_unwind(AssertUnwindSafe(|| circuit_builder.build::<C>()))
+            .map_err(|_| {
+            anyhow!(
+                "the uniform circuit does not fit 2^{} gates",
+                UNIFORM_CIRCUIT_DEGREE_BITS
+            )
+        })?;
+        let base_proof_with_pis = cyclic_base_proof(
+            &common_data,
+            &circuit_data.verifier_only,
+            Default::default(),
+        );
+
+        Ok(Self {
+            leaf_len,
+            circuit_data,
+            targets: UniformTargets {
+                is_internal,
+                left_leaf_targets,
+                right_leaf_targets,
+                left_proof_with_pis_targets,
+                right_proof_with_pis_targets,
+                verifier_data_targets,
+            },
+            base_proof_with_pis,
+        })
+    }
+
+    /// Method `circuit_data`:
+    ///
+    ///     Returns the data of the uniform circuit, whose verifier data is valid for all trees with
+    ///     leaves of `leaf_len` field elements.
+    pub fn circuit_data(&self) -> &CircuitData<F, C, D> {
+        &self.circuit_data
+    }
+
+    /// Method `into_circuit_data`:
+    ///
+    ///     Returns the data of the uniform circuit, consuming the circuit.
+    pub fn into_circuit_data(self) -> CircuitData<F, C, D> {
+        self.circuit_data
+    }
+
+    /// Method `prove_leaves`:
+    ///
+    ///     Proves the parent node of the leaves `left_leaf` and `right_leaf`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the node proof, or an Error if a leaf does not have
+    ///     `leaf_len` field elements, or the proof generation fails.
+    pub fn prove_leaves(
+        &self,
+        left_leaf: &[F],
+        right_leaf: &[F],
+    ) -> Result<ProofWithPublicInputs<F, C, D>, Error> {
+        ensure!(
+            left_leaf.len() == self.leaf_len && right_leaf.len() == self.leaf_len,
+            "the uniform circuit expects leaves of {} field elements",
+            self.leaf_len
+        );
+
+        let mut partial_witness = PartialWitness::<F>::new();
+        partial_witness.set_bool_target(self.targets.is_internal, false);
+        partial_witness.set_target_arr(&self.targets.left_leaf_targets, left_leaf);
+        partial_witness.set_target_arr(&self.targets.right_leaf_targets, right_leaf);
+        self.fill_children(
+            &mut partial_witness,
+            &self.base_proof_with_pis,
+            &self.base_proof_with_pis,
+        );
+
+        self.circuit_data.prove(partial_witness)
+    }
+
+    /// Method `prove_node`:
+    ///
+    ///     Proves a parent node from the uniform proofs of its children.
+    pub fn prove_node(
+        &self,
+        left_proof_with_pis: &ProofWithPublicInputs<F, C, D>,
+        right_proof_with_pis: &ProofWithPublicInputs<F, C, D>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>, Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        partial_witness.set_bool_target(self.targets.is_internal, true);
+        partial_witness.set_target_arr(
+            &self.targets.left_leaf_targets,
+            &vec![F::default(); self.leaf_len],
+        );
+        partial_witness.set_target_arr(
+            &self.targets.right_leaf_targets,
+            &vec![F::default(); self.leaf_len],
+        );
+        self.fill_children(
+            &mut partial_witness,
+            left_proof_with_pis,
+            right_proof_with_pis,
+        );
+
+        self.circuit_data.prove(partial_witness)
+    }
+
+    fn fill_children(
+        &self,
+        partial_witness: &mut PartialWitness<F>,
+        left_proof_with_pis: &ProofWithPublicInputs<F, C, D>,
+        right_proof_with_pis: &ProofWithPublicInputs<F, C, D>,
+    ) {
+        partial_witness.set_proof_with_pis_target(
+            &self.targets.left_proof_with_pis_targets,
+            left_proof_with_pis,
+        );
+        partial_witness.set_proof_with_pis_target(
+            &self.targets.right_proof_with_pis_targets,
+            right_proof_with_pis,
+        );
+        partial_witness.set_verifier_data_target(
+            &self.targets.verifier_data_targets,
+            &self.circuit_data.verifier_only,
+        );
+    }
+
+    /// Method `node_hash`:
+    ///
+    ///     Returns the node hash a uniform proof commits to.
+    pub fn node_hash(proof_with_pis: &ProofWithPublicInputs<F, C, D>) -> HashOut<F> {
+        HashOut::from_partial(&proof_with_pis.public_inputs[0..4])
+    }
+
+    /// Method `verify`:
+    ///
+    ///     Verifies a uniform proof, including that it was generated for the uniform circuit.
+    pub fn verify(&self, proof_with_pis: ProofWithPublicInputs<F, C, D>) -> Result<(), Error> {
+        check_cyclic_proof_verifier_data(
+            &proof_with_pis,
+            &self.circuit_data.verifier_only,
+            &self.circuit_data.common,
+        )?;
+        self.circuit_data.verify(proof_with_pis)
+    }
+}
+
+impl MerkleTree {
+    /// Method `uniform_proof`:
+    ///
+    ///     Generates a proof for the constructed Merkle tree with the uniform circuit, which should
+    ///     have been built for the length of the tree leaves. Sibling subtrees are proved in parallel, on
+    ///     the global rayon thread pool. Trees can also be proved with the uniform circuit through
+    ///     `MerkleTree::proof_with_options`, with `ProvingBackend::Uniform`.
+    ///
+    /// Returns:
+    ///
+    ///     Returns a Result containing the root proof, whose first public inputs are the root hash, or
+    ///     an Error if the proof generation fails.
+    pub fn uniform_proof(
+        &self,
+        uniform_tree_circuit: &UniformTreeCircuit,
+    ) -> Result<ProofWithPublicInputs<F, C, D>, Error> {
+        self.uniform_proof_with_scheduler(uniform_tree_circuit, &ProofScheduler::default())
+    }
+
+    /// Proves the tree with the uniform circuit, scheduling node proofs on `scheduler`.
+    pub(crate) fn uniform_proof_with_scheduler(
+        &self,
+        uniform_tree_circuit: &UniformTreeCircuit,
+        scheduler: &ProofScheduler,
+    ) -> Result<ProofWithPublicInputs<F, C, D>, Error> {
+        scheduler.prove_tree(
+            self.leaves.len().ilog2() as usize,
+            1,
+            |index| {
+                uniform_tree_circuit
+                    .prove_leaves(&self.leaves[2 * index], &self.leaves[2 * index + 1])
+            },
+            |_, _, left_proof_with_pis, right_proof_with_pis| {
+                uniform_tree_circuit.prove_node(left_proof_with_pis, right_proof_with_pis)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use plonky2::{field::types::Field, fri::FriConfig};
+
+    use super::*;
+    use crate::{
+        progress::{CancellationToken, ProofProgress},
+        proving_options::{ProvingBackend, ProvingOptions},
+    };
+
+    #[test]
+    fn test_uniform_proofs_share_verifier_key() {
+        let uniform_tree_circuit = UniformTreeCircuit::new(1).unwrap();
+
+        for num_leaves in [2, 4, 8] {
+            let merkle_tree_leaves = (0..num_leaves)
+                .map(|i| vec![F::from_canonical_u64(i)])
+                .collect();
+            let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+
+            let proof_with_pis = merkle_tree.uniform_proof(&uniform_tree_circuit).unwrap();
+            assert_eq!(
+                UniformTreeCircuit::node_hash(&proof_with_pis),
+                merkle_tree.root()
+            );
+            assert!(uniform_tree_circuit.verify(proof_with_pis).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_invalid_uniform_proofs() {
+        assert!(UniformTreeCircuit::with_circuit_config(
+            1,
+            CircuitConfig::standard_recursion_zk_config()
+        )
+        .is_err());
+
+        // verifying two proofs with twice as many FRI queries does not fit the uniform circuit
+        let circuit_config = CircuitConfig::standard_recursion_config();
+        assert!(UniformTreeCircuit::with_circuit_config(
+            1,
+            CircuitConfig {
+                fri_config: FriConfig {
+                    num_query_rounds: 2 * circuit_config.fri_config.num_query_rounds,
+                    ..circuit_config.fri_config.clone()
+                },
+                ..circuit_config
+            }
+        )
+        .is_err());
+
+        let uniform_tree_circuit = UniformTreeCircuit::new(1).unwrap();
+        assert!(uniform_tree_circuit
+            .prove_leaves(&[F::ZERO, F::ONE], &[F::ONE])
+            .is_err());
+    }
+
+    #[test]
+    fn test_uniform_backend() {
+        let num_nodes_proved = Arc::new(AtomicUsize::new(0));
+        let progress_num_nodes_proved = num_nodes_proved.clone();
+        let proving_options = ProvingOptions {
+            backend: ProvingBackend::Uniform,
+            num_threads: Some(2),
+            zero_knowledge: false,
+            progress_observer: Some(Arc::new(move |_: &ProofProgress| {
+                progress_num_nodes_proved.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..ProvingOptions::default()
+        };
+
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+        let proof_data = merkle_tree.proof_with_options(&proving_options).unwrap();
+        assert_eq!(
+            UniformTreeCircuit::node_hash(&proof_data.proof_with_pis),
+            root
+        );
+        assert_eq!(num_nodes_proved.load(Ordering::SeqCst), 3);
+
+        let root_verifier_data = MerkleTree::root_verifier_data(4, 1, &proving_options).unwrap();
+        assert_eq!(
+            root_verifier_data.verifier_only,
+            proof_data.circuit_data.verifier_only
+        );
+        assert!(root_verifier_data.verify(proof_data.proof_with_pis).is_ok());
+
+        // cancelled proofs fail before proving any node
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let proving_options = ProvingOptions {
+            cancellation_token: Some(cancellation_token),
+            ..proving_options
+        };
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        assert!(MerkleTree::create(merkle_tree_leaves)
+            .proof_with_options(&proving_options)
+            .is_err());
+        assert_eq!(num_nodes_proved.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{ensure, Error};
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::{circuit_data::VerifierCircuitData, config::Hasher, proof::ProofWithPublicInputs},
+    util::serialization::DefaultGateSerializer,
+};
+
+use crate::{inclusion_proof::InclusionProof, C, D, F};
+
+/// Index, within the public inputs of a node proof, of the height of the subtree, leaves being at
+/// height 0.
+pub const HEIGHT_PUBLIC_INPUT_INDEX: usize = 4;
+/// Index, within the public inputs of a node proof, of the number of leaves of the subtree.
+pub const NUM_LEAVES_PUBLIC_INPUT_INDEX: usize = 5;
+/// Index, within the public inputs of a node proof, of the index of the node within its level, i.e.
+/// the subtree covers the leaves `index * num_leaves..(index + 1) * num_leaves`.
+pub const INDEX_PUBLIC_INPUT_INDEX: usize = 6;
+/// Number of public inputs of a node proof: the node hash, followed by the height, the number of
+/// leaves and the index of its subtree.
+pub const NUM_NODE_PUBLIC_INPUTS: usize = 7;
+
+/// Function `verifier_key_digest`:
+///
+///     Returns the digest of a serialized verifier key (see `ProofData::verifier_key_to_bytes`), i.e. the
+///     Poseidon hash of its bytes, packed in little endian `u32` elements. It covers the circuit digest
+///     of the key, as well as its common data (gates, FRI parameters, ...), which the circuit digest
+///     of plonky2 leaves out.
+pub fn verifier_key_digest(verifier_key_bytes: &[u8]) -> HashOut<F> {
+    let elements = verifier_key_bytes
+        .chunks(4)
+        .map(|chunk| {
+            let mut bytes = [0; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            F::from_canonical_u32(u32::from_le_bytes(bytes))
+        })
+        .chain([F::from_canonical_usize(verifier_key_bytes.len())])
+        .collect::<Vec<_>>();
+    PoseidonHash::hash_no_pad(&elements)
+}
+
+/// Function `verify_root_proof`:
+///
+///     Verifies a serialized root proof against a serialized verifier key (see
+///     `ProofData::verifier_key_to_bytes`), and checks that it commits to `root`, as the node at index 0
+///     of its level. The verifier key should match `pinned_verifier_key_digest`, pinning the circuits of the
+///     tree (see `MerkleTree::root_verifier_key_digest`), since the proof of any other circuit could
+///     expose the same public inputs.
+///
+/// Returns:
+///
+///     Returns a Result containing the number of leaves of the tree, or an Error if the bytes are
+///     malformed, the verifier key is not the pinned one, or the proof is invalid.
+pub fn verify_root_proof(
+    proof_bytes: &[u8],
+    verifier_key_bytes: &[u8],
+    root: HashOut<F>,
+    pinned_verifier_key_digest: HashOut<F>,
+) -> Result<usize, Error> {
+    ensure!(
+        verifier_key_digest(verifier_key_bytes) == pinned_verifier_key_digest,
+        "the verifier key does not match the pinned verifier key digest"
+    );
+    let verifier_data = VerifierCircuitData::<F, C, D>::from_bytes(
+        verifier_key_bytes.to_vec(),
+        &DefaultGateSerializer,
+    )
+    .map_err(Error::msg)?;
+    let proof_with_pis =
+        ProofWithPublicInputs::from_bytes(proof_bytes.to_vec(), &verifier_data.common)?;
+
+    let public_inputs = &proof_with_pis.public_inputs;
+    ensure!(
+        public_inputs.len() >= NUM_NODE_PUBLIC_INPUTS,
+        "the proof is not a node proof"
+    );
+    ensure!(
+        public_inputs[..4] == root.elements,
+        "the proof does not commit to the root"
+    );
+    ensure!(
+        public_inputs[INDEX_PUBLIC_INPUT_INDEX].is_zero(),
+        "the proof does not commit to a whole tree"
+    );
+    let height = public_inputs[HEIGHT_PUBLIC_INPUT_INDEX].to_canonical_u64();
+    let num_leaves = public_inputs[NUM_LEAVES_PUBLIC_INPUT_INDEX].to_canonical_u64();
+    ensure!(
+        height < u64::from(usize::BITS) && num_leaves == 1 << height,
+        "the proof does not commit to a perfect tree"
+    );
+
+    verifier_data.verify(proof_with_pis)?;
+    Ok(num_leaves as usize)
+}
+
+/// Function `verify_inclusion_path`:
+///
+///     Verifies the authentication path of the leaf `leaf_index` against `root`, `siblings` being
+///     ordered from the leaf level up, as `InclusionProof::verify` does.
+///
+/// Returns:
+///
+///     Returns an Error if the leaf index is out of range or the path does not lead to `root`.
+pub fn verify_inclusion_path(
+    leaf: &[F],
+    leaf_index: usize,
+    siblings: &[HashOut<F>],
+    root: HashOut<F>,
+) -> Result<(), Error> {
+    InclusionProof {
+        leaf_index,
+        leaf: leaf.to_vec(),
+        siblings: siblings.to_vec(),
+    }
+    .verify(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leaf_format::hash_from_hex;
+    #[cfg(feature = "prover")]
+    use crate::{merkle_tree::MerkleTree, provable::Provable, proving_options::ProvingOptions};
+    #[cfg(feature = "prover")]
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig},
+    };
+
+    // proved with `merkle-prove prove fixtures/leaves.csv ...`, and shared with the wasm tests
+    const FIXTURE_ROOT: &str = "0x61da2dac9252842b51ffee3a2445ec6c2ec88471998965f5107497d196b1a3a5";
+    // printed by `merkle-prove digest 4 1`, the leaves of the fixture being single elements
+    const FIXTURE_VERIFIER_KEY_DIGEST: &str =
+        "0x6f1e54f3a955cab2d200d45d479ba8d2e76f4919876d908124979c0963593923";
+
+    /// Proves a foreign circuit, exposing `public_inputs` as those of a root proof, and returns the
+    /// serialized proof and verifier key.
+    #[cfg(feature = "prover")]
+    fn foreign_root_proof(public_inputs: &[F]) -> (Vec<u8>, Vec<u8>) {
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_zk_config());
+        let public_input_targets =
+            circuit_builder.add_virtual_public_input_arr::<NUM_NODE_PUBLIC_INPUTS>();
+        let mut partial_witness = PartialWitness::<F>::new();
+        partial_witness.set_target_arr(&public_input_targets, public_inputs);
+
+        let circuit_data = circuit_builder.build::<C>();
+        let proof_with_pis = circuit_data.prove(partial_witness).unwrap();
+        (
+            proof_with_pis.to_bytes(),
+            circuit_data
+                .verifier_data()
+                .to_bytes(&DefaultGateSerializer)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_verify_root_proof_fixture() {
+        let root = hash_from_hex(FIXTURE_ROOT).unwrap();
+        let pinned_verifier_key_digest = hash_from_hex(FIXTURE_VERIFIER_KEY_DIGEST).unwrap();
+        let proof_bytes = include_bytes!("../fixtures/root_proof.bin");
+        let verifier_key_bytes = include_bytes!("../fixtures/verifier_key.bin");
+        assert_eq!(
+            verify_root_proof(
+                proof_bytes,
+                verifier_key_bytes,
+                root,
+                pinned_verifier_key_digest
+            )
+            .unwrap(),
+            4
+        );
+    }
+
+    #[cfg(feature = "prover")]
+    #[test]
+    fn test_verify_root_proof() {
+        let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+        let proof_data = merkle_tree.proof().unwrap();
+        let pinned_verifier_key_digest =
+            MerkleTree::root_verifier_key_digest(4, 1, &ProvingOptions::default()).unwrap();
+        assert_eq!(
+            pinned_verifier_key_digest,
+            hash_from_hex(FIXTURE_VERIFIER_KEY_DIGEST).unwrap()
+        );
+
+        let proof_bytes = proof_data.proof_to_bytes();
+        let verifier_key_bytes = proof_data.verifier_key_to_bytes().unwrap();
+        let verify = |proof_bytes: &[u8], root| {
+            verify_root_proof(
+                proof_bytes,
+                &verifier_key_bytes,
+                root,
+                pinned_verifier_key_digest,
+            )
+        };
+        assert_eq!(verify(&proof_bytes, root).unwrap(), 4);
+        assert!(verify(&proof_bytes, HashOut::ZERO).is_err());
+        assert!(verify(&proof_bytes[1..], root).is_err());
+    }
+
+    #[cfg(feature = "prover")]
+    #[test]
+    fn test_verify_root_proof_rejects_foreign_circuits() {
+        let root = HashOut::from_partial(&[F::ONE]);
+        let public_inputs = |height: u64, num_leaves: u64| {
+            [
+                root.elements.as_slice(),
+                &[
+                    F::from_canonical_u64(height),
+                    F::from_canonical_u64(num_leaves),
+                    F::ZERO,
+                ],
+            ]
+            .concat()
+        };
+
+        // a valid proof of another circuit, exposing the public inputs of a root proof
+        let (proof_bytes, verifier_key_bytes) = foreign_root_proof(&public_inputs(2, 4));
+        let pinned_verifier_key_digest =
+            MerkleTree::root_verifier_key_digest(4, 1, &ProvingOptions::default()).unwrap();
+        assert!(verify_root_proof(
+            &proof_bytes,
+            &verifier_key_bytes,
+            root,
+            pinned_verifier_key_digest
+        )
+        .is_err());
+        assert_eq!(
+            verify_root_proof(
+                &proof_bytes,
+                &verifier_key_bytes,
+                root,
+                verifier_key_digest(&verifier_key_bytes)
+            )
+            .unwrap(),
+            4
+        );
+
+        // public inputs of a tree which is not perfect
+        let (proof_bytes, verifier_key_bytes) = foreign_root_proof(&public_inputs(2, 5));
+        assert!(verify_root_proof(
+            &proof_bytes,
+            &verifier_key_bytes,
+            root,
+            verifier_key_digest(&verifier_key_bytes)
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "prover")]
+    #[test]
+    fn test_verify_inclusion_path() {
+        let merkle_tree_leaves = (0..8).map(|i| vec![F::from_canonical_u64(i)]).collect();
+        let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+        let root = merkle_tree.root();
+        let inclusion_proof = merkle_tree.inclusion_proof(5).unwrap();
+
+        let verify = |leaf_index| {
+            verify_inclusion_path(
+                &inclusion_proof.leaf,
+                leaf_index,
+                &inclusion_proof.siblings,
+                root,
+            )
+        };
+        assert!(verify(5).is_ok());
+        assert!(verify(4).is_err());
+        assert!(verify(8 + 5).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{inclusion_proof::InclusionProof, leaf_format::hash_from_hex, verifier};
+
+fn js_error(error: anyhow::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Function `verify_root_proof`:
+///
+///     Exposes `verifier::verify_root_proof` to JavaScript as `verifyRootProof(proof, verifierKey,
+///     root, verifierKeyDigest)`, the proof and verifier key being `Uint8Array`s (see
+///     `ProofData::proof_to_bytes` and `ProofData::verifier_key_to_bytes`), and the root and pinned
+///     verifier key digest (see `MerkleTree::root_verifier_key_digest`) hexadecimal strings (see
+///     `hash_to_hex`).
+///
+/// Returns:
+///
+///     Returns a Result containing the number of leaves of the tree, or an Error, thrown in
+///     JavaScript, if the proof is invalid.
+#[wasm_bindgen(js_name = verifyRootProof)]
+pub fn verify_root_proof(
+    proof: &[u8],
+    verifier_key: &[u8],
+    root: &str,
+    verifier_key_digest: &str,
+) -> Result<usize, JsError> {
+    verifier::verify_root_proof(
+        proof,
+        verifier_key,
+        hash_from_hex(root).map_err(js_error)?,
+        hash_from_hex(verifier_key_digest).map_err(js_error)?,
+    )
+    .map_err(js_error)
+}
+
+/// Function `verify_inclusion_path`:
+///
+///     Exposes `verifier::verify_inclusion_path` to JavaScript as `verifyInclusionPath(inclusionProof,
+///     root)`, the inclusion proof being the JSON string of `InclusionProof::to_json`, and the root a
+///     hexadecimal string.
+///
+/// Returns:
+///
+///     Returns a Result which is an Error, thrown in JavaScript, if the JSON is malformed or the path
+///     does not lead to the root.
+#[wasm_bindgen(js_name = verifyInclusionPath)]
+pub fn verify_inclusion_path(inclusion_proof: &str, root: &str) -> Result<(), JsError> {
+    let inclusion_proof =
+        InclusionProof::from_json(&serde_json::from_str(inclusion_proof)?).map_err(js_error)?;
+    verifier::verify_inclusion_path(
+        &inclusion_proof.leaf,
+        inclusion_proof.leaf_index,
+        &inclusion_proof.siblings,
+        hash_from_hex(root).map_err(js_error)?,
+    )
+    .map_err(js_error)
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    // proved natively with `merkle-prove prove fixtures/leaves.csv ...`
+    const ROOT: &str = "0x61da2dac9252842b51ffee3a2445ec6c2ec88471998965f5107497d196b1a3a5";
+    const VERIFIER_KEY_DIGEST: &str =
+        "0x6f1e54f3a955cab2d200d45d479ba8d2e76f4919876d908124979c0963593923";
+    const ROOT_PROOF: &[u8] = include_bytes!("../fixtures/root_proof.bin");
+    const VERIFIER_KEY: &[u8] = include_bytes!("../fixtures/verifier_key.bin");
+    const INCLUSION_PROOF: &str = include_str!("../fixtures/inclusion_proof.json");
+
+    #[wasm_bindgen_test]
+    fn test_verify_root_proof() {
+        assert_eq!(
+            verify_root_proof(ROOT_PROOF, VERIFIER_KEY, ROOT, VERIFIER_KEY_DIGEST).unwrap(),
+            4
+        );
+        assert!(verify_root_proof(
+            ROOT_PROOF,
+            VERIFIER_KEY,
+            &ROOT.replace("61", "62"),
+            VERIFIER_KEY_DIGEST
+        )
+        .is_err());
+        assert!(
+            verify_root_proof(&ROOT_PROOF[1..], VERIFIER_KEY, ROOT, VERIFIER_KEY_DIGEST).is_err()
+        );
+        assert!(verify_root_proof(ROOT_PROOF, VERIFIER_KEY, ROOT, ROOT).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_inclusion_path() {
+        assert!(verify_inclusion_path(INCLUSION_PROOF, ROOT).is_ok());
+        assert!(verify_inclusion_path(&INCLUSION_PROOF.replace("\"2\"", "\"3\""), ROOT).is_err());
+        assert!(verify_inclusion_path("{}", ROOT).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+//! Proves a tree with `merkle-worker` processes, spawned by a `Coordinator`.
+
+use std::process::Command;
+
+use plonky2::field::types::Field;
+use recursive_merkle_tree_proofs::{
+    distributed::Coordinator, merkle_tree::MerkleTree, proving_options::ProvingOptions,
+    verifier::verify_root_proof, F,
+};
+
+#[test]
+fn test_spawned_workers_prove_tree() {
+    let coordinator =
+        Coordinator::spawn(&mut Command::new(env!("CARGO_BIN_EXE_merkle-worker")), 2).unwrap();
+    assert_eq!(coordinator.num_workers(), 2);
+
+    let merkle_tree_leaves = (0..4).map(|i| vec![F::from_canonical_u64(i)]).collect();
+    let merkle_tree = MerkleTree::create(merkle_tree_leaves);
+    let root = merkle_tree.root();
+
+    let proof_data = merkle_tree
+        .proof_with_coordinator(&coordinator, &ProvingOptions::default())
+        .unwrap();
+    assert_eq!(proof_data.root(), root);
+
+    let verifier_key_digest =
+        MerkleTree::root_verifier_key_digest(4, 1, &ProvingOptions::default()).unwrap();
+    assert_eq!(
+        verify_root_proof(
+            &proof_data.proof_to_bytes(),
+            &proof_data.verifier_key_to_bytes().unwrap(),
+            root,
+            verifier_key_digest
+        )
+        .unwrap(),
+        4
+    );
+}
@@ -0,0 +1,50 @@
+/*
+ * Exercises the C interface declared in include/merkle_tree_proofs.h: builds a tree, checks an
+ * inclusion path, then proves the root and verifies the proof against the pinned verifier key.
+ * Compiled and run by tests/ffi.rs.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "merkle_tree_proofs.h"
+
+#define CHECK(condition)                                                                           \
+    do {                                                                                           \
+        if (!(condition)) {                                                                        \
+            fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__, __LINE__, #condition,     \
+                    merkle_last_error() ? merkle_last_error() : "no error");                       \
+            return EXIT_FAILURE;                                                                   \
+        }                                                                                          \
+    } while (0)
+
+int main(void) {
+    const uint64_t leaves[4] = {0, 1, 2, 3};
+    MerkleTree *merkle_tree = merkle_tree_create(leaves, 4, 1);
+    CHECK(merkle_tree != NULL);
+    CHECK(merkle_tree_num_leaves(merkle_tree) == 4);
+
+    uint64_t root[4];
+    CHECK(merkle_tree_root(merkle_tree, root) == 0);
+
+    uint64_t siblings[8];
+    CHECK(merkle_tree_inclusion_proof(merkle_tree, 2, siblings, 8) == 0);
+    CHECK(merkle_verify_inclusion_path(&leaves[2], 1, 2, siblings, 2, root) == 0);
+    CHECK(merkle_verify_inclusion_path(&leaves[2], 1, 3, siblings, 2, root) == -1);
+
+    SerializedProofData *proof_data = merkle_tree_prove(merkle_tree, 0);
+    merkle_tree_free(merkle_tree);
+    CHECK(proof_data != NULL);
+
+    uint64_t verifier_key_digest[4];
+    CHECK(merkle_root_verifier_key_digest(4, 1, verifier_key_digest) == 0);
+    size_t num_leaves = 0;
+    CHECK(merkle_proof_verify(proof_data, root, verifier_key_digest, &num_leaves) == 0);
+    CHECK(num_leaves == 4);
+
+    root[0] ^= 1;
+    CHECK(merkle_proof_verify(proof_data, root, verifier_key_digest, NULL) == -1);
+    merkle_proof_free(proof_data);
+
+    return EXIT_SUCCESS;
+}
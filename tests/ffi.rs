@@ -0,0 +1,35 @@
+//! Compiles `tests/ffi.c` against `include/merkle_tree_proofs.h`, links it to the shared library
+//! built with the `ffi` feature and runs it.
+#![cfg(unix)]
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn test_c_program() {
+    // the shared library is built in target/<profile>, the parent of the test executable directory
+    let test_executable = env::current_exe().unwrap();
+    let library_directory = test_executable.parent().unwrap().parent().unwrap();
+    let manifest_directory = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_directory.join("tests/ffi.c"))
+        .arg("-I")
+        .arg(manifest_directory.join("include"))
+        .arg("-L")
+        .arg(library_directory)
+        .arg(format!("-Wl,-rpath,{}", library_directory.display()))
+        .arg("-lrecursive_merkle_tree_proofs")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(status.success(), "compiling tests/ffi.c failed");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}